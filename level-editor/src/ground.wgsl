@@ -0,0 +1,77 @@
+struct UniformData {
+    mouse_move: vec2<f32>,
+    mouse_click: vec2<f32>,
+    resolution: vec2<f32>,
+    time: f32,
+    point_size: f32,
+    camera_position: vec4<f32>,
+    user_params: vec4<f32>,
+}
+
+struct GroundParams {
+    line_color: vec4<f32>,
+    base_color: vec4<f32>,
+    line_spacing: f32,
+    line_width: f32,
+    fade_distance: f32,
+    _padding0: f32,
+}
+
+@group(0) @binding(0) var<uniform> uni: UniformData;
+@group(1) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+@group(2) @binding(0) var<uniform> ground: GroundParams;
+
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) model_col0: vec4<f32>,
+    @location(4) model_col1: vec4<f32>,
+    @location(5) model_col2: vec4<f32>,
+    @location(6) model_col3: vec4<f32>,
+}
+
+struct VertexOutput {
+    // Invariant so the depth prepass and the color pass compute identical depths.
+    @invariant @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let model = mat4x4<f32>(
+        in.model_col0,
+        in.model_col1,
+        in.model_col2,
+        in.model_col3,
+    );
+    let world_position = model * vec4<f32>(in.pos, 1.0);
+    out.clip_position = view_proj * world_position;
+    out.world_pos = world_position.xyz;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Grid coordinates in cells, and how many cells one pixel covers.
+    let coord = in.world_pos.xz / ground.line_spacing;
+    let cells_per_pixel = fwidth(coord);
+
+    // Distance to the nearest line in pixels, turned into an anti-aliased coverage.
+    let pixels_to_line = abs(fract(coord - 0.5) - 0.5) / cells_per_pixel;
+    let line_distance = min(pixels_to_line.x, pixels_to_line.y);
+    var coverage = clamp(ground.line_width * 0.5 + 0.5 - line_distance, 0.0, 1.0);
+
+    // Lines closer together than a pixel only produce moire, fade them out instead.
+    coverage *= 1.0 - smoothstep(0.3, 1.0, max(cells_per_pixel.x, cells_per_pixel.y));
+
+    // Fade the grid toward the horizon.
+    let camera_distance = distance(in.world_pos, uni.camera_position.xyz);
+    coverage *= 1.0 - smoothstep(0.0, ground.fade_distance, camera_distance);
+
+    let color = mix(ground.base_color.rgb, ground.line_color.rgb, coverage * ground.line_color.a);
+    let x = select(0.0, 0.3, distance(in.clip_position.xy, uni.mouse_move) < 25.0);
+    let y = select(0.0, 0.3, distance(in.clip_position.xy, uni.mouse_click) < 25.0);
+    return vec4<f32>(color + x - y, 1.0);
+}
@@ -2,12 +2,17 @@ use std::sync::mpsc::Receiver;
 use std::{cell::RefCell, rc::Rc};
 use ultraviolet::Mat4;
 use wasm_bindgen::prelude::*;
+use wgpu::util::DeviceExt;
 
 use renderer::app_setup::WebApp;
 use renderer::camera::Camera;
 use renderer::message::WindowEvent;
 use renderer::renderer as gpu_renderer;
-use renderer::renderer::scene::{mesh_vertex_layout, FrameMetadata, Mesh, MeshBuilder};
+use renderer::renderer::scene::{
+    mesh_vertex_layout, FrameMetadata, Light, Mesh, MeshBuilder, MeshId, MeshStore, SceneError,
+    UniformResource,
+};
+use renderer::renderer::{Background, Grid, SceneGraph};
 
 /// Simple vertex format.
 #[repr(C)]
@@ -17,13 +22,86 @@ struct Vertex {
     color: [f32; 3],
 }
 
+/// Look of the procedural ground grid, bound at group 2 of `ground.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug)]
+pub struct GroundParams {
+    pub line_color: [f32; 4],
+    pub base_color: [f32; 4],
+    /// World-space distance between two grid lines.
+    pub line_spacing: f32,
+    /// Line width in pixels.
+    pub line_width: f32,
+    /// Distance from the camera at which the grid has fully faded out.
+    pub fade_distance: f32,
+    _padding0: f32,
+}
+
+impl Default for GroundParams {
+    fn default() -> Self {
+        Self {
+            line_color: [0.55, 0.55, 0.55, 1.0],
+            base_color: [0.12, 0.12, 0.12, 1.0],
+            line_spacing: 1.0,
+            line_width: 1.0,
+            fade_distance: 150.0,
+            _padding0: 0.0,
+        }
+    }
+}
+
+impl GroundParams {
+    pub fn create_uniform_resource(self, device: &wgpu::Device) -> UniformResource {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ground params uniform buffer"),
+            contents: bytemuck::cast_slice(&[self]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ground bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ground bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        UniformResource {
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+}
+
 pub struct EditorScene {
     uniform_buffers: [wgpu::Buffer; 2],
-    bind_groups: [wgpu::BindGroup; 2],
-    bind_group_layouts: [wgpu::BindGroupLayout; 2],
+    bind_groups: [wgpu::BindGroup; 3],
+    bind_group_layouts: [wgpu::BindGroupLayout; 3],
+    ground_buffer: wgpu::Buffer,
+    ground_params: GroundParams,
     frame_metadata: FrameMetadata,
+    light: Light,
     cam: Camera,
-    meshes: Vec<Mesh>,
+    meshes: MeshStore,
+    background: Background,
+    grid: Grid,
+    graph: SceneGraph,
 }
 
 impl renderer::renderer::scene::Scene for EditorScene {
@@ -31,6 +109,13 @@ impl renderer::renderer::scene::Scene for EditorScene {
         renderer_context: &gpu_renderer::RendererContext,
         resources: &mut gpu_renderer::GpuResources,
     ) -> Self {
+        Self::try_setup(renderer_context, resources).expect("Failed to set up the editor scene")
+    }
+
+    fn try_setup(
+        renderer_context: &gpu_renderer::RendererContext,
+        resources: &mut gpu_renderer::GpuResources,
+    ) -> Result<Self, SceneError> {
         let dimension = ultraviolet::Vec2::new(
             renderer_context.surface_config.width as f32,
             renderer_context.surface_config.height as f32,
@@ -41,38 +126,58 @@ impl renderer::renderer::scene::Scene for EditorScene {
 
         frame_metadata.set_camera_position(camera.position());
 
-        let uniform_resource = frame_metadata.create_uniform_resource(&renderer_context.device);
+        let ground_params = GroundParams::default();
+
+        let light = Light::new(&renderer_context.device);
+        let uniform_resource =
+            frame_metadata.create_uniform_resource(&renderer_context.device, &light);
         let camera_resource = camera.create_uniform_resource(&renderer_context.device);
+        let ground_resource = ground_params.create_uniform_resource(&renderer_context.device);
 
         let bind_group_layouts = [
             uniform_resource.bind_group_layout,
             camera_resource.bind_group_layout,
+            ground_resource.bind_group_layout,
         ];
 
         resources.set_bind_group_layouts(&bind_group_layouts);
 
         let mut scene = EditorScene {
             uniform_buffers: [uniform_resource.buffer, camera_resource.buffer],
-            bind_groups: [uniform_resource.bind_group, camera_resource.bind_group],
+            bind_groups: [
+                uniform_resource.bind_group,
+                camera_resource.bind_group,
+                ground_resource.bind_group,
+            ],
             bind_group_layouts,
+            ground_buffer: ground_resource.buffer,
+            ground_params,
             frame_metadata,
+            light,
             cam: camera,
-            meshes: Vec::new(),
+            meshes: MeshStore::new(),
+            background: Background::default(),
+            grid: Grid::default(),
+            graph: SceneGraph::new(),
         };
 
         scene.create_default_scene(
             &renderer_context.device,
             resources,
             renderer_context.surface_config.format,
-        );
+        )?;
 
-        scene
+        Ok(scene)
     }
 
     fn frame_metadata_mut(&mut self) -> Option<&mut FrameMetadata> {
         Some(&mut self.frame_metadata)
     }
 
+    fn light_mut(&mut self) -> Option<&mut Light> {
+        Some(&mut self.light)
+    }
+
     fn camera_mut(&mut self) -> Option<&mut Camera> {
         Some(&mut self.cam)
     }
@@ -81,32 +186,65 @@ impl renderer::renderer::scene::Scene for EditorScene {
         Some(&self.uniform_buffers)
     }
 
+    fn background_mut(&mut self) -> Option<&mut Background> {
+        Some(&mut self.background)
+    }
+
     fn bind_groups(&self) -> &[wgpu::BindGroup] {
         &self.bind_groups
     }
 
+    fn bind_group_layouts(&self) -> Option<&[wgpu::BindGroupLayout]> {
+        Some(&self.bind_group_layouts)
+    }
+
+    fn grid(&self) -> Option<&Grid> {
+        Some(&self.grid)
+    }
+
+    fn grid_mut(&mut self) -> Option<&mut Grid> {
+        Some(&mut self.grid)
+    }
+
+    fn scene_graph(&self) -> Option<&SceneGraph> {
+        Some(&self.graph)
+    }
+
+    fn scene_graph_mut(&mut self) -> Option<&mut SceneGraph> {
+        Some(&mut self.graph)
+    }
+
     fn meshes(&self) -> &[Mesh] {
-        &self.meshes
+        self.meshes.as_slice()
     }
 
     fn handle_mouse_click(&mut self, x: f32, y: f32) {
         self.frame_metadata.mouse_click = [x, y];
     }
 
-    fn handle_zoom(&mut self, _delta_y: f32) {
-        // TODO: Implement zoom properly when Camera exposes necessary methods
-    }
-
     fn handle_orbit(&mut self, delta_x: f32, delta_y: f32) {
         self.cam.orbit(delta_x, delta_y);
     }
 
     fn clear(&mut self) {
         self.meshes.clear();
+        self.graph.clear();
     }
 
-    fn add_mesh(&mut self, mesh: Mesh) {
-        self.meshes.push(mesh);
+    fn add_mesh(&mut self, mesh: Mesh) -> MeshId {
+        self.meshes.insert(mesh)
+    }
+
+    fn remove_mesh(&mut self, id: MeshId) -> Option<Mesh> {
+        self.meshes.remove(id)
+    }
+
+    fn get_mesh_mut(&mut self, id: MeshId) -> Option<&mut Mesh> {
+        self.meshes.get_mut(id)
+    }
+
+    fn mesh_id(&self, index: usize) -> Option<MeshId> {
+        self.meshes.id_at(index)
     }
 
     fn set_camera_depth_range(&mut self, near: f32, far: f32) {
@@ -130,6 +268,16 @@ impl WebApp for LevelEditor {
 }
 
 impl EditorScene {
+    pub fn ground_params(&self) -> GroundParams {
+        self.ground_params
+    }
+
+    /// Change the grid line spacing and colors of the ground plane.
+    pub fn set_ground_params(&mut self, params: GroundParams, queue: &wgpu::Queue) {
+        self.ground_params = params;
+        queue.write_buffer(&self.ground_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
     /// Ground plane vertex data.
     const VERTICES: &[Vertex] = &[
         // First triangle of quad
@@ -168,7 +316,7 @@ impl EditorScene {
         device: &wgpu::Device,
         resources: &mut gpu_renderer::GpuResources,
         surface_format: wgpu::TextureFormat,
-    ) {
+    ) -> Result<(), SceneError> {
         let positions: Vec<[f32; 3]> = Self::VERTICES.iter().map(|v| v.pos).collect();
         // Ground plane normals point upward (Y+)
         let normals: Vec<[f32; 3]> = vec![[0.0, 1.0, 0.0]; positions.len()];
@@ -183,25 +331,31 @@ impl EditorScene {
 
         let vertex_layout = mesh_vertex_layout();
 
-        let pipeline_index = resources.get_or_create_pipeline(
-            device,
-            "ground_plane",
-            &vertex_layout,
-            include_str!("./program.wgsl"),
-            surface_format,
-        );
+        let pipeline_index = resources
+            .try_get_or_create_pipeline(
+                device,
+                "ground_plane",
+                &vertex_layout,
+                include_str!("./ground.wgsl"),
+                surface_format,
+            )
+            .map_err(|message| SceneError::Pipeline {
+                name: "ground_plane".to_owned(),
+                message,
+            })?;
 
         let scale_factor = 100.0;
         let scale_matrix = Mat4::from_scale(scale_factor);
 
         let mesh = MeshBuilder::default()
-            .with_vertices(device, resources, &positions, &normals, uvs)
+            .with_vertices(device, resources, &positions, &normals, uvs)?
             .with_indices(device, resources, Self::INDICES)
             .with_pipeline(pipeline_index)
             .with_model_matrix(device, resources, scale_matrix)
             .build();
 
-        self.meshes.push(mesh);
+        self.meshes.insert(mesh);
+        Ok(())
     }
 }
 
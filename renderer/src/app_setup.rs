@@ -1,4 +1,8 @@
+#[cfg(target_arch = "wasm32")]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Sender};
+#[cfg(target_arch = "wasm32")]
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -8,22 +12,43 @@ use web_sys::AddEventListenerOptions;
 #[cfg(target_arch = "wasm32")]
 use wgpu::Error;
 
+#[cfg(target_arch = "wasm32")]
+use crate::gltf::{self, ModelDecoder};
 use crate::message::WindowEvent;
 #[cfg(target_arch = "wasm32")]
+use crate::message::{ClickTracker, TouchTracker};
+#[cfg(target_arch = "wasm32")]
 use crate::platform::web;
 #[cfg(target_arch = "wasm32")]
+use crate::platform::web::draco::{WebDracoDecoder, DRACO_DECODER_PATH};
+#[cfg(target_arch = "wasm32")]
 use crate::platform::web::worker::MainWorker;
 #[cfg(target_arch = "wasm32")]
-use wasm_bindgen_futures::spawn_local;
+use crate::renderer::RendererConfig;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+/// Listener that re-registers itself on every device pixel ratio change, see
+/// `watch_pixel_ratio`.
+#[cfg(target_arch = "wasm32")]
+pub type PixelRatioListener = Rc<RefCell<Option<Closure<dyn FnMut()>>>>;
 
 /// Helper struct to store event listener closures
 #[cfg(target_arch = "wasm32")]
 pub struct EventListeners {
     pub resize_listener: Option<Closure<dyn FnMut()>>,
+    pub pixel_ratio_listener: Option<PixelRatioListener>,
     pub mousemove_listener: Option<Closure<dyn FnMut(web_sys::MouseEvent)>>,
     pub mousedown_listener: Option<Closure<dyn FnMut(web_sys::MouseEvent)>>,
+    pub contextmenu_listener: Option<Closure<dyn FnMut(web_sys::MouseEvent)>>,
     pub wheel_listener: Option<Closure<dyn FnMut(web_sys::WheelEvent)>>,
     pub keyboard_listener: Option<Closure<dyn FnMut(web_sys::KeyboardEvent)>>,
+    pub keyup_listener: Option<Closure<dyn FnMut(web_sys::KeyboardEvent)>>,
+    pub focus_lost_listener: Option<Closure<dyn FnMut()>>,
+    pub touchmove_listener: Option<Closure<dyn FnMut(web_sys::TouchEvent)>>,
+    pub touch_change_listener: Option<Closure<dyn FnMut(web_sys::TouchEvent)>>,
+    pub dragover_listener: Option<Closure<dyn FnMut(web_sys::DragEvent)>>,
+    pub drop_listener: Option<Closure<dyn FnMut(web_sys::DragEvent)>>,
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -31,39 +56,110 @@ impl EventListeners {
     pub fn new() -> Self {
         Self {
             resize_listener: None,
+            pixel_ratio_listener: None,
             mousemove_listener: None,
             mousedown_listener: None,
+            contextmenu_listener: None,
             wheel_listener: None,
             keyboard_listener: None,
+            keyup_listener: None,
+            focus_lost_listener: None,
+            touchmove_listener: None,
+            touch_change_listener: None,
+            dragover_listener: None,
+            drop_listener: None,
         }
     }
 }
 
-/// Setup default window event listeners that forward events to the worker thread
+/// Send the canvas size and the current device pixel ratio to the worker.
 #[cfg(target_arch = "wasm32")]
-pub fn setup_event_listeners(worker_chan: &Sender<WindowEvent>) -> Result<EventListeners, JsValue> {
+fn send_resize(worker_chan: &Sender<WindowEvent>, canvas: &web_sys::HtmlCanvasElement) {
+    use crate::message::ResizeMessage;
+
     let window = web_sys::window().unwrap();
-    let resize_worker_chan = worker_chan.clone();
+    // Size the backing store from the canvas' CSS box rather than the window, so the
+    // surface always matches what is displayed and the image is never stretched.
+    let width = canvas.client_width() as f64;
+    let height = canvas.client_height() as f64;
+
+    worker_chan
+        .send(WindowEvent::Resize(ResizeMessage {
+            width,
+            height,
+            scale_factor: window.device_pixel_ratio(),
+        }))
+        .unwrap();
+}
 
-    let resize_listener: Closure<dyn FnMut()> = Closure::new(move || {
-        use crate::message::ResizeMessage;
+/// Resend the canvas size whenever `devicePixelRatio` changes, e.g. when the window is
+/// dragged to a monitor of another pixel density, which fires no `resize` event.
+///
+/// A `(resolution: …dppx)` media query only reports leaving the ratio it was made for,
+/// so the listener is registered once per query and re-registers itself for the new
+/// ratio. It owns an Rc to itself, so it stays alive for as long as the page does.
+#[cfg(target_arch = "wasm32")]
+fn watch_pixel_ratio(
+    worker_chan: &Sender<WindowEvent>,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<PixelRatioListener, JsValue> {
+    let listener: PixelRatioListener = Rc::new(RefCell::new(None));
+    let next_change = listener.clone();
+    let worker_chan = worker_chan.clone();
+    let canvas = canvas.clone();
+
+    *listener.borrow_mut() = Some(Closure::new(move || {
+        send_resize(&worker_chan, &canvas);
+        if let Some(callback) = next_change.borrow().as_ref() {
+            if let Err(err) = on_pixel_ratio_change(callback) {
+                log::warn!("Stopped watching the device pixel ratio: {:?}", err);
+            }
+        }
+    }));
 
-        let window = web_sys::window().unwrap();
-        let width = window.inner_width().ok().unwrap().as_f64().unwrap();
-        let height = window.inner_height().ok().unwrap().as_f64().unwrap();
+    if let Some(callback) = listener.borrow().as_ref() {
+        on_pixel_ratio_change(callback)?;
+    }
+    Ok(listener)
+}
 
-        resize_worker_chan
-            .send(WindowEvent::Resize(ResizeMessage {
-                width,
-                height,
-                scale_factor: window.device_pixel_ratio(),
-            }))
-            .unwrap();
-    });
+/// Call `callback` once, when the device pixel ratio moves away from its current value.
+#[cfg(target_arch = "wasm32")]
+fn on_pixel_ratio_change(callback: &Closure<dyn FnMut()>) -> Result<(), JsValue> {
+    let window = web_sys::window().unwrap();
+    let query = format!("(resolution: {}dppx)", window.device_pixel_ratio());
+    let Some(media_query) = window.match_media(&query)? else {
+        return Ok(());
+    };
+
+    let options = AddEventListenerOptions::new();
+    options.set_once(true);
+    media_query.add_event_listener_with_callback_and_add_event_listener_options(
+        "change",
+        callback.as_ref().unchecked_ref(),
+        &options,
+    )
+}
+
+/// Setup default window event listeners that forward events to the worker thread
+#[cfg(target_arch = "wasm32")]
+pub fn setup_event_listeners(
+    worker_chan: &Sender<WindowEvent>,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<EventListeners, JsValue> {
+    let window = web_sys::window().unwrap();
+    let resize_worker_chan = worker_chan.clone();
+    let resize_canvas = canvas.clone();
+
+    let resize_listener: Closure<dyn FnMut()> =
+        Closure::new(move || send_resize(&resize_worker_chan, &resize_canvas));
 
     window.add_event_listener_with_callback("resize", resize_listener.as_ref().unchecked_ref())?;
 
+    let pixel_ratio_listener = watch_pixel_ratio(worker_chan, canvas)?;
+
     let mousemove_worker_chan = worker_chan.clone();
+    let mut clicks = ClickTracker::default();
     let mousemove_listener: Closure<dyn FnMut(web_sys::MouseEvent)> =
         Closure::new(move |event: web_sys::MouseEvent| {
             use crate::message::MouseMessage;
@@ -78,6 +174,18 @@ pub fn setup_event_listeners(worker_chan: &Sender<WindowEvent>) -> Result<EventL
             }
 
             mousemove_worker_chan.clone().send(event_data).unwrap();
+
+            let double_click = event.type_() == "click"
+                && clicks.click(
+                    event.time_stamp(),
+                    mouse_event_data.client_x,
+                    mouse_event_data.client_y,
+                );
+            if double_click {
+                mousemove_worker_chan
+                    .send(WindowEvent::PointerDoubleClick(mouse_event_data))
+                    .unwrap();
+            }
         });
 
     window.add_event_listener_with_callback(
@@ -100,6 +208,17 @@ pub fn setup_event_listeners(worker_chan: &Sender<WindowEvent>) -> Result<EventL
         mousedown_listener.as_ref().unchecked_ref(),
     )?;
 
+    // Right drag pans the camera, so the canvas has no context menu.
+    let contextmenu_listener: Closure<dyn FnMut(web_sys::MouseEvent)> =
+        Closure::new(move |event: web_sys::MouseEvent| {
+            event.prevent_default();
+        });
+
+    canvas.add_event_listener_with_callback(
+        "contextmenu",
+        contextmenu_listener.as_ref().unchecked_ref(),
+    )?;
+
     let wheel_worker_chan = worker_chan.clone();
     let wheel_listener: Closure<dyn FnMut(web_sys::WheelEvent)> =
         Closure::new(move |event: web_sys::WheelEvent| {
@@ -140,45 +259,252 @@ pub fn setup_event_listeners(worker_chan: &Sender<WindowEvent>) -> Result<EventL
     window
         .add_event_listener_with_callback("keydown", keyboard_listener.as_ref().unchecked_ref())?;
 
+    let keyup_worker_chan = worker_chan.clone();
+    let keyup_listener: Closure<dyn FnMut(web_sys::KeyboardEvent)> =
+        Closure::new(move |event: web_sys::KeyboardEvent| {
+            use crate::message::KeyboardMessage;
+
+            keyup_worker_chan
+                .send(WindowEvent::KeyboardUp(KeyboardMessage::from_evt(event)))
+                .unwrap();
+        });
+
+    window.add_event_listener_with_callback("keyup", keyup_listener.as_ref().unchecked_ref())?;
+
+    // Keys released while the page is in the background never send a keyup.
+    let focus_lost_worker_chan = worker_chan.clone();
+    let focus_lost_listener: Closure<dyn FnMut()> = Closure::new(move || {
+        focus_lost_worker_chan.send(WindowEvent::FocusLost).unwrap();
+    });
+
+    window
+        .add_event_listener_with_callback("blur", focus_lost_listener.as_ref().unchecked_ref())?;
+    if let Some(document) = window.document() {
+        document.add_event_listener_with_callback(
+            "visibilitychange",
+            focus_lost_listener.as_ref().unchecked_ref(),
+        )?;
+    }
+
+    let touch_tracker = Rc::new(RefCell::new(TouchTracker::default()));
+    let active_touches = |event: &web_sys::TouchEvent| -> Vec<(i32, f64, f64)> {
+        let list = event.touches();
+        (0..list.length())
+            .filter_map(|i| list.item(i))
+            .map(|touch| {
+                (
+                    touch.identifier(),
+                    touch.client_x() as f64,
+                    touch.client_y() as f64,
+                )
+            })
+            .collect()
+    };
+
+    let touchmove_worker_chan = worker_chan.clone();
+    let touchmove_tracker = touch_tracker.clone();
+    let touchmove_listener: Closure<dyn FnMut(web_sys::TouchEvent)> =
+        Closure::new(move |event: web_sys::TouchEvent| {
+            // Keep the page from scrolling or zooming under the gesture.
+            event.prevent_default();
+
+            let scale_factor = web_sys::window().unwrap().device_pixel_ratio();
+            let message = touchmove_tracker
+                .borrow_mut()
+                .moved(&active_touches(&event), scale_factor);
+            if let Some(message) = message {
+                touchmove_worker_chan.send(message).unwrap();
+            }
+        });
+
+    // Fingers touching down or lifting only change which touches the gesture follows.
+    let touch_change_listener: Closure<dyn FnMut(web_sys::TouchEvent)> =
+        Closure::new(move |event: web_sys::TouchEvent| {
+            touch_tracker.borrow_mut().reset(&active_touches(&event));
+        });
+
+    let touch_options = {
+        let options = AddEventListenerOptions::new();
+        options.set_passive(false);
+        options
+    };
+
+    canvas.add_event_listener_with_callback_and_add_event_listener_options(
+        "touchmove",
+        touchmove_listener.as_ref().unchecked_ref(),
+        &touch_options,
+    )?;
+    for event in ["touchstart", "touchend", "touchcancel"] {
+        canvas.add_event_listener_with_callback(
+            event,
+            touch_change_listener.as_ref().unchecked_ref(),
+        )?;
+    }
+
+    // Cancelling dragover is what lets files be dropped instead of opened by the browser.
+    let dragover_listener: Closure<dyn FnMut(web_sys::DragEvent)> =
+        Closure::new(move |event: web_sys::DragEvent| {
+            event.prevent_default();
+        });
+
+    window
+        .add_event_listener_with_callback("dragover", dragover_listener.as_ref().unchecked_ref())?;
+
+    let drop_worker_chan = worker_chan.clone();
+    let drop_listener: Closure<dyn FnMut(web_sys::DragEvent)> =
+        Closure::new(move |event: web_sys::DragEvent| {
+            event.prevent_default();
+
+            let Some(file) = event
+                .data_transfer()
+                .and_then(|data| data.files())
+                .and_then(|files| files.get(0))
+            else {
+                return;
+            };
+
+            let drop_worker_chan = drop_worker_chan.clone();
+            spawn_local(async move {
+                let bytes = match JsFuture::from(file.array_buffer()).await {
+                    Ok(buffer) => js_sys::Uint8Array::new(&buffer).to_vec(),
+                    Err(err) => {
+                        log::error!("Failed to read {}: {:?}", file.name(), err);
+                        return;
+                    }
+                };
+
+                // Check before handing possibly large bytes to the worker.
+                if gltf::ModelFormat::sniff(&bytes).is_none() {
+                    log::warn!("Ignoring {}: not a glTF or GLB file", file.name());
+                    return;
+                }
+
+                drop_worker_chan
+                    .send(WindowEvent::LoadModel(bytes))
+                    .unwrap();
+            });
+        });
+
+    window.add_event_listener_with_callback("drop", drop_listener.as_ref().unchecked_ref())?;
+
     Ok(EventListeners {
         resize_listener: Some(resize_listener),
+        pixel_ratio_listener: Some(pixel_ratio_listener),
         mousemove_listener: Some(mousemove_listener),
         mousedown_listener: Some(mousedown_listener),
+        contextmenu_listener: Some(contextmenu_listener),
         wheel_listener: Some(wheel_listener),
         keyboard_listener: Some(keyboard_listener),
+        keyup_listener: Some(keyup_listener),
+        focus_lost_listener: Some(focus_lost_listener),
+        touchmove_listener: Some(touchmove_listener),
+        touch_change_listener: Some(touch_change_listener),
+        dragover_listener: Some(dragover_listener),
+        drop_listener: Some(drop_listener),
     })
 }
 
+/// Id of the next spawned worker, shared by every runtime on the page so the workers of
+/// several canvases never get the same one.
+#[cfg(target_arch = "wasm32")]
+static NEXT_WORKER_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[cfg(target_arch = "wasm32")]
+fn next_worker_id() -> usize {
+    NEXT_WORKER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Name of the auxiliary worker that parses glTF files for the render worker.
+#[cfg(target_arch = "wasm32")]
+pub const DECODE_WORKER_NAME: &str = "gltf-decoder";
+
 /// Runtime resources required to keep a WASM application running.
 #[cfg(target_arch = "wasm32")]
 pub struct WebAppRuntime {
     worker: MainWorker,
     worker_chan: Sender<WindowEvent>,
+    auxiliary_workers: HashMap<String, MainWorker>,
     _event_listeners: EventListeners,
 }
 
 #[cfg(target_arch = "wasm32")]
 impl WebAppRuntime {
     /// Initialize the web worker, canvas ownership, and event listeners.
-    pub fn new<T: crate::renderer::scene::Scene + 'static>(worker_name: &str, canvas_selector: &str) -> Result<Self, JsValue> {
+    ///
+    /// Each runtime drives one canvas, pages with several views create one per canvas
+    /// with a distinct `worker_name`.
+    pub fn new<T: crate::renderer::scene::Scene + 'static>(
+        worker_name: &str,
+        canvas_selector: &str,
+        config: RendererConfig,
+    ) -> Result<Self, JsValue> {
         let (sender, receiver) = mpsc::channel::<WindowEvent>();
+        let (model_decoder, decode_requests) = ModelDecoder::channel();
 
         let canvas = web::get_canvas_element(canvas_selector);
-        let worker = MainWorker::spawn(worker_name, 1, move || {
+        let worker = MainWorker::spawn(worker_name, next_worker_id(), move || {
             spawn_local(async move {
-                MainWorker::run_render_loop::<T>(receiver).await;
+                MainWorker::run_render_loop::<T>(receiver, model_decoder, config).await;
             });
         })?;
 
         worker.transfer_ownership(&canvas);
 
-        let event_listeners = setup_event_listeners(&sender)?;
+        let event_listeners = setup_event_listeners(&sender, &canvas)?;
 
-        Ok(Self {
+        let mut runtime = Self {
             worker,
             worker_chan: sender,
+            auxiliary_workers: HashMap::new(),
             _event_listeners: event_listeners,
-        })
+        };
+
+        runtime.spawn_worker(DECODE_WORKER_NAME, move || {
+            spawn_local(async move {
+                let draco = WebDracoDecoder::load(DRACO_DECODER_PATH).await;
+                let draco = draco.as_ref().map(|draco| draco as &dyn gltf::DracoDecoder);
+                gltf::run_decode_loop(decode_requests, draco);
+            });
+        })?;
+
+        Ok(runtime)
+    }
+
+    /// Spawn an auxiliary worker next to the render worker and keep it alive with the runtime.
+    ///
+    /// `f` runs inside the new worker once the WASM module is initialized there. Spawning
+    /// a worker under a name that is already taken terminates the previous one. Names
+    /// are per runtime, the browser sees them prefixed with the render worker's name.
+    pub fn spawn_worker(
+        &mut self,
+        name: &str,
+        f: impl FnOnce() + Send + 'static,
+    ) -> Result<&MainWorker, JsValue> {
+        let qualified = format!("{}/{name}", self.worker.name());
+        let worker = MainWorker::spawn(&qualified, next_worker_id(), f)?;
+
+        self.auxiliary_workers.insert(name.to_owned(), worker);
+        Ok(&self.auxiliary_workers[name])
+    }
+
+    /// Look up an auxiliary worker by the name it was spawned with.
+    pub fn worker_named(&self, name: &str) -> Option<&MainWorker> {
+        self.auxiliary_workers.get(name)
+    }
+
+    /// All auxiliary workers, keyed by name.
+    pub fn auxiliary_workers(&self) -> &HashMap<String, MainWorker> {
+        &self.auxiliary_workers
+    }
+
+    /// Post a message to the auxiliary worker registered under `name`.
+    ///
+    /// The worker only sees it if its entry closure installed an `onmessage` handler.
+    pub fn post_to(&self, name: &str, message: &JsValue) -> Result<(), JsValue> {
+        let worker = self
+            .worker_named(name)
+            .ok_or_else(|| JsValue::from_str(&format!("no worker named {name}")))?;
+        worker.post_message(message)
     }
 
     /// Access the worker channel sender for dispatching custom window events.
@@ -186,6 +512,49 @@ impl WebAppRuntime {
         &self.worker_chan
     }
 
+    /// Send new WGSL for the pipeline registered as `name` to the render worker.
+    ///
+    /// The worker keeps the current shader if the new one fails to compile, and logs why.
+    pub fn reload_shader(&self, name: &str, source: &str) {
+        let event = WindowEvent::ReloadShader {
+            name: name.to_owned(),
+            source: source.to_owned(),
+        };
+        if self.worker_chan.send(event).is_err() {
+            log::warn!("Render worker is gone, dropping shader reload for {name}");
+        }
+    }
+
+    /// Ask the render worker for a PNG of the current frame.
+    ///
+    /// It arrives as a `frame-captured` message, with the bytes in `png`, through the
+    /// `renderer-message` event on `window`.
+    pub fn capture_frame(&self) {
+        if self.worker_chan.send(WindowEvent::CaptureFrame).is_err() {
+            log::warn!("Render worker is gone, dropping frame capture");
+        }
+    }
+
+    /// Stop or resume drawing frames in the render worker, e.g. while the page is hidden.
+    /// Input is still handled while paused.
+    pub fn set_paused(&self, paused: bool) {
+        if self
+            .worker_chan
+            .send(WindowEvent::SetPaused(paused))
+            .is_err()
+        {
+            log::warn!("Render worker is gone, dropping pause change");
+        }
+    }
+
+    /// Have the render worker only draw frames when the scene or camera changed.
+    pub fn set_render_on_demand(&self, on_demand: bool) {
+        let event = WindowEvent::SetRenderOnDemand(on_demand);
+        if self.worker_chan.send(event).is_err() {
+            log::warn!("Render worker is gone, dropping render mode change");
+        }
+    }
+
     /// Access the spawned worker reference.
     pub fn worker(&self) -> &MainWorker {
         &self.worker
@@ -197,7 +566,8 @@ impl WebAppRuntime {
 pub trait WebApp {
     type Scene: crate::renderer::scene::Scene + 'static;
 
-    /// Name used for the spawned `MainWorker`.
+    /// Name used for the spawned `MainWorker`, see `setup_runtime_for` for pages with
+    /// several canvases.
     fn worker_name() -> &'static str {
         "main-worker"
     }
@@ -207,15 +577,38 @@ pub trait WebApp {
         "#canvas0"
     }
 
+    /// Device, surface and presentation setup the render worker starts with.
+    fn renderer_config() -> RendererConfig {
+        RendererConfig::default()
+    }
+
+    /// Spawn the application's auxiliary workers, e.g. for asset decoding.
+    ///
+    /// Runs before `on_runtime_initialized`; use `WebAppRuntime::spawn_worker` for each one.
+    fn spawn_auxiliary_workers(_runtime: &mut WebAppRuntime) -> Result<(), JsValue> {
+        Ok(())
+    }
+
     /// Hook invoked after the runtime has been created.
     fn on_runtime_initialized(_runtime: &mut WebAppRuntime) {}
 
     /// Perform the default WASM initialization routine.
     fn setup_runtime() -> Result<WebAppRuntime, JsValue> {
+        Self::setup_runtime_for(Self::canvas_selector(), Self::worker_name())
+    }
+
+    /// Like `setup_runtime`, for the canvas at `canvas_selector` drawn by its own render
+    /// worker named `worker_name`, e.g. one view of a multi-view editor.
+    fn setup_runtime_for(
+        canvas_selector: &str,
+        worker_name: &str,
+    ) -> Result<WebAppRuntime, JsValue> {
         let mut runtime = WebAppRuntime::new::<Self::Scene>(
-            Self::worker_name(),
-            Self::canvas_selector(),
+            worker_name,
+            canvas_selector,
+            Self::renderer_config(),
         )?;
+        Self::spawn_auxiliary_workers(&mut runtime)?;
         Self::on_runtime_initialized(&mut runtime);
         Ok(runtime)
     }
@@ -1,16 +1,25 @@
 use std::f32::consts::PI;
 
-use ultraviolet::{projection, Bivec3, Mat4, Rotor3, Vec3};
+use ultraviolet::{projection, Bivec3, Mat4, Rotor3, Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt;
 
-use crate::{message::WheelMessage, renderer::scene::UniformResource};
+use crate::{gltf::ModelBounds, message::WheelMessage, renderer::scene::UniformResource};
 
 const MIN_DISTANCE: f32 = 0.1;
 const MAX_PITCH: f32 = PI / 2.0 - 0.01;
 const ORBIT_SENSITIVITY: f32 = 0.005;
 const ZOOM_SENSITIVITY: f32 = 0.002;
+const PAN_SENSITIVITY: f32 = 0.001;
+const FOV_SENSITIVITY: f32 = 0.001;
+const MIN_FOV: f32 = PI / 18.0;
+const MAX_FOV: f32 = PI * 2.0 / 3.0;
+/// Highest damping factor, any closer to 1 and the camera would hardly move.
+const MAX_DAMPING: f32 = 0.99;
+/// Damped motion stops once this close to its goal, relative to the orbit radius.
+const SETTLE_TOLERANCE: f32 = 1e-4;
 
 #[repr(C)]
+#[derive(Clone)]
 pub struct Camera {
     // Hot data - cached computed matrix (64 bytes, 1 cache line)
     pub view_proj: [[f32; 4]; 4],
@@ -20,20 +29,156 @@ pub struct Camera {
     target: Vec3,
     up: Vec3,
 
-    // Cold data - projection parameters (16 bytes)
-    fov: f32,
+    // Cold data - projection parameters (21 bytes)
+    projection: ProjectionMode,
     aspect_ratio: f32,
     z_near: f32,
     z_far: f32,
+    reverse_z: bool,
 
     // Rotor orientation for orbit camera behaviour
     rotor: Rotor3,
     distance: f32,
 
+    // Orbit, pan and zoom move the goal position and target instead while damped, the
+    // camera catches up in `advance`
+    damping: f32,
+    goal: Option<(Vec3, Vec3)>,
+
+    zoom_mode: ZoomMode,
+
+    // Viewpoint the camera was created at, which `reset` returns to
+    home: Option<CameraState>,
+
     // Dirty flag for lazy evaluation
     dirty: bool,
 }
 
+/// How the view volume is projected onto the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProjectionMode {
+    /// Vertical field of view `fov` in radians, distant objects look smaller.
+    Perspective { fov: f32 },
+    /// Parallel projection showing `height` world units vertically, e.g. for top, front
+    /// and side views.
+    Orthographic { height: f32 },
+}
+
+/// Viewpoint of a camera that can be saved and restored, e.g. as a named bookmark in
+/// the editor or shared in a URL, see `Camera::to_state` and `Camera::from_state`.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CameraState {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    /// Vertical field of view in radians, see `Camera::fov`.
+    pub fov: f32,
+    /// View height of an orthographic camera, `None` for a perspective one.
+    pub orthographic_height: Option<f32>,
+    pub distance: f32,
+    /// Orbit orientation as the scalar and the xy, xz and yz bivector parts of the rotor.
+    pub rotor: [f32; 4],
+    pub aspect_ratio: f32,
+    pub z_near: f32,
+    pub z_far: f32,
+    pub reverse_z: bool,
+}
+
+/// How keyboard input moves the camera.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Dragging orbits around the target, keys leave the camera alone.
+    #[default]
+    Orbit,
+    /// Held W/A/S/D/Q/E keys also move the camera and its target along the view axes.
+    Fly,
+}
+
+/// What the mouse wheel changes in `Camera::zoom`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZoomMode {
+    /// Move the camera toward or away from the point under the cursor.
+    #[default]
+    Dolly,
+    /// Narrow or widen the field of view, keeping the camera in place.
+    Fov,
+}
+
+/// Direction to fly in for the held key codes, as right, up and forward components in
+/// `[-1, 1]`. Codes follow key positions, so the keys work on any keyboard layout.
+pub fn fly_direction<'a>(codes: impl IntoIterator<Item = &'a str>) -> Vec3 {
+    codes
+        .into_iter()
+        .fold(Vec3::zero(), |direction, code| match code {
+            "KeyD" => direction + Vec3::unit_x(),
+            "KeyA" => direction - Vec3::unit_x(),
+            "KeyE" => direction + Vec3::unit_y(),
+            "KeyQ" => direction - Vec3::unit_y(),
+            "KeyW" => direction + Vec3::unit_z(),
+            "KeyS" => direction - Vec3::unit_z(),
+            _ => direction,
+        })
+}
+
+/// Half-line from `origin` along the normalized `direction`, in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Where the ray hits the `y = 0` ground plane, `None` if it runs parallel to the
+    /// plane or points away from it.
+    pub fn intersect_ground(&self) -> Option<Vec3> {
+        if self.direction.y.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let distance = -self.origin.y / self.direction.y;
+        (distance > 0.0).then(|| self.origin + self.direction * distance)
+    }
+}
+
+/// The six planes bounding what a view-projection matrix shows, facing inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    // Normal in xyz and offset in w, points inside have a positive distance
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Planes of the clip volume of `view_proj`, with wgpu's `[0, 1]` depth range. Either
+    /// depth convention gives the same planes.
+    pub fn from_view_proj(view_proj: Mat4) -> Self {
+        let row = |i: usize| {
+            let c = &view_proj.cols;
+            Vec4::new(c[0][i], c[1][i], c[2][i], c[3][i])
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z],
+        }
+    }
+
+    /// Whether any part of the box may be visible. Boxes entirely behind one plane are
+    /// not, a few others near the corners are let through.
+    pub fn intersects(&self, bounds: &ModelBounds) -> bool {
+        let (min, max) = (Vec3::from(bounds.min), Vec3::from(bounds.max));
+
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal
+            let corner = Vec3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.xyz().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
 struct OrthonormalBasis {
     right: Vec3,
     up: Vec3,
@@ -87,34 +232,249 @@ impl Camera {
             position: Vec3::new(0.0, 0.5, 3.0),
             target: Vec3::new(0.0, 0.0, 0.0),
             up: Vec3::unit_y(),
-            fov: PI / 3.0,
+            projection: ProjectionMode::Perspective { fov: PI / 3.0 },
             aspect_ratio,
             z_near: 0.1,
             z_far: 100000.0,
+            reverse_z: false,
             rotor: Rotor3::identity(),
             distance: 1.0,
+            damping: 0.0,
+            goal: None,
+            zoom_mode: ZoomMode::Dolly,
+            home: None,
             dirty: true,
         };
 
         camera.compute_rotor();
         camera.compute_view_proj_mat();
+        camera.home = Some(camera.to_state());
 
         camera
     }
 
+    /// Current viewpoint, to restore later with `from_state`.
+    ///
+    /// Damped motion still under way is left out, the state is where the camera is now.
+    pub fn to_state(&self) -> CameraState {
+        let orthographic_height = match self.projection {
+            ProjectionMode::Perspective { .. } => None,
+            ProjectionMode::Orthographic { height } => Some(height),
+        };
+
+        CameraState {
+            position: self.position.into(),
+            target: self.target.into(),
+            up: self.up.into(),
+            fov: self.fov(),
+            orthographic_height,
+            distance: self.distance,
+            rotor: [
+                self.rotor.s,
+                self.rotor.bv.xy,
+                self.rotor.bv.xz,
+                self.rotor.bv.yz,
+            ],
+            aspect_ratio: self.aspect_ratio,
+            z_near: self.z_near,
+            z_far: self.z_far,
+            reverse_z: self.reverse_z,
+        }
+    }
+
+    /// Camera at a viewpoint saved with `to_state`, with the same `view_proj`.
+    ///
+    /// The rotor and distance are recomputed from the position and target rather than
+    /// trusted from the state. Call `update_aspect_ratio` when restoring into a viewport
+    /// of another shape.
+    pub fn from_state(state: CameraState) -> Self {
+        let projection = match state.orthographic_height {
+            Some(height) => ProjectionMode::Orthographic { height },
+            None => ProjectionMode::Perspective { fov: state.fov },
+        };
+
+        let mut camera = Camera {
+            position: state.position.into(),
+            target: state.target.into(),
+            up: state.up.into(),
+            projection,
+            aspect_ratio: state.aspect_ratio,
+            z_near: state.z_near,
+            z_far: state.z_far,
+            reverse_z: state.reverse_z,
+            home: Some(state),
+            ..Camera::new(state.aspect_ratio)
+        };
+        camera.compute_rotor();
+        camera.compute_view_proj_mat();
+
+        camera
+    }
+
+    /// Jump back to where the camera was created, e.g. for a "home" button after the
+    /// user orbited, panned and zoomed around.
+    pub fn reset(&mut self) {
+        if let Some(home) = self.home {
+            self.reset_to(home);
+        }
+    }
+
+    /// Jump to the position, target, up vector, projection and depth range of `state`.
+    ///
+    /// Unlike `from_state` the current aspect ratio and depth convention are kept, as they
+    /// follow the viewport and the pipelines rather than the viewpoint. Damped motion
+    /// under way is dropped.
+    pub fn reset_to(&mut self, state: CameraState) {
+        self.goal = None;
+        self.position = state.position.into();
+        self.target = state.target.into();
+        self.up = state.up.into();
+        self.projection = match state.orthographic_height {
+            Some(height) => ProjectionMode::Orthographic { height },
+            None => ProjectionMode::Perspective { fov: state.fov },
+        };
+        self.z_near = state.z_near;
+        self.z_far = state.z_far;
+
+        self.compute_rotor();
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    /// World to view space transform.
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at(self.position, self.target, self.up)
+    }
+
+    /// View to clip space transform, with wgpu's `[0, 1]` depth range.
+    ///
+    /// With reverse-Z the near plane maps to depth 1 and the far plane to 0.
+    pub fn projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            ProjectionMode::Perspective { fov } if self.reverse_z => {
+                projection::rh_yup::perspective_reversed_z_wgpu_dx_gl(
+                    fov,
+                    self.aspect_ratio,
+                    self.z_near,
+                    self.z_far,
+                )
+            }
+            ProjectionMode::Perspective { fov } => projection::rh_yup::perspective_wgpu_dx(
+                fov,
+                self.aspect_ratio,
+                self.z_near,
+                self.z_far,
+            ),
+            ProjectionMode::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+                // Swapping the planes maps depth linearly from 1 at near to 0 at far.
+                let (near, far) = if self.reverse_z {
+                    (self.z_far, self.z_near)
+                } else {
+                    (self.z_near, self.z_far)
+                };
+                projection::rh_yup::orthographic_wgpu_dx(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
     pub fn compute_view_proj_mat(&mut self) {
-        let view = Mat4::look_at(self.position, self.target, self.up);
-        let proj = projection::rh_yup::perspective_wgpu_dx(
-            self.fov,
-            self.aspect_ratio,
-            self.z_near,
-            self.z_far,
-        );
-        self.view_proj = (proj * view).into();
+        self.view_proj = (self.projection_matrix() * self.view_matrix()).into();
         self.dirty = false;
     }
 
+    /// Pixel position of `world` in a viewport `viewport` pixels large, from its top-left
+    /// corner, e.g. to anchor a label to an object.
+    ///
+    /// `None` when the point is behind the camera or outside the view frustum.
+    pub fn world_to_screen(&self, world: Vec3, viewport: Vec2) -> Option<Vec2> {
+        let clip = Mat4::from(self.view_proj) * world.into_homogeneous_point();
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = clip.xyz() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || !(0.0..=1.0).contains(&ndc.z) {
+            return None;
+        }
+
+        Some(Vec2::new(
+            (ndc.x + 1.0) * 0.5 * viewport.x,
+            (1.0 - ndc.y) * 0.5 * viewport.y,
+        ))
+    }
+
+    /// World position drawn at the pixel `screen` of a viewport `viewport` pixels large,
+    /// with `depth` as stored in the depth buffer there (near is 1 with reverse-Z).
+    ///
+    /// Unprojected with the inverse view-projection, so the result is only as precise as
+    /// the depth value, which degrades far from the near plane without reverse-Z.
+    pub fn screen_to_world(&self, screen: Vec2, viewport: Vec2, depth: f32) -> Vec3 {
+        let ndc = Vec3::new(
+            screen.x / viewport.x.max(1.0) * 2.0 - 1.0,
+            1.0 - screen.y / viewport.y.max(1.0) * 2.0,
+            depth,
+        );
+        let world = Mat4::from(self.view_proj).inversed() * ndc.into_homogeneous_point();
+
+        world.xyz() / world.w
+    }
+
+    /// Ray from the camera through the pixel `screen` of a viewport `viewport` pixels
+    /// large, the inverse of `world_to_screen`.
+    pub fn screen_ray(&self, screen: Vec2, viewport: Vec2) -> Ray {
+        self.ndc_ray(Vec2::new(
+            screen.x / viewport.x.max(1.0) * 2.0 - 1.0,
+            1.0 - screen.y / viewport.y.max(1.0) * 2.0,
+        ))
+    }
+
+    /// Ray from the camera through `ndc`, -1 to 1 from the left and bottom edges.
+    fn ndc_ray(&self, ndc: Vec2) -> Ray {
+        let (ndc_x, ndc_y) = (ndc.x, ndc.y);
+
+        // Built from the view basis rather than by unprojecting with the inverse
+        // view-projection, which loses precision with a far plane this distant.
+        let basis = OrthonormalBasis::from_camera(self);
+        match self.projection {
+            ProjectionMode::Perspective { fov } => {
+                let half_height = (fov / 2.0).tan();
+                let half_width = half_height * self.aspect_ratio;
+                let direction = basis.forward
+                    + basis.right * (ndc_x * half_width)
+                    + basis.up * (ndc_y * half_height);
+
+                Ray {
+                    origin: self.position,
+                    direction: direction.normalized(),
+                }
+            }
+            // Parallel rays, starting from the pixel's spot on the camera plane.
+            ProjectionMode::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+                let origin = self.position
+                    + basis.right * (ndc_x * half_width)
+                    + basis.up * (ndc_y * half_height);
+
+                Ray {
+                    origin,
+                    direction: basis.forward,
+                }
+            }
+        }
+    }
+
     pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        self.goal = None;
         self.position = position;
         self.target = target;
         self.up = Vec3::unit_y();
@@ -123,6 +483,50 @@ impl Camera {
         self.compute_view_proj_mat();
     }
 
+    /// Look at the box from `min` to `max` from slightly above, close enough that its
+    /// bounding sphere just fits in view, with a depth range covering it.
+    pub fn frame_bounds(&mut self, min: Vec3, max: Vec3) {
+        let bounds = ModelBounds::new(min.into(), max.into());
+        let center = bounds.center();
+        let radius = bounds.radius();
+
+        let distance = match self.projection {
+            ProjectionMode::Perspective { fov } => {
+                // The sphere has to fit the narrower of the two fields of view.
+                let half_height = (fov / 2.0).tan();
+                let half_fov = half_height.min(half_height * self.aspect_ratio).atan();
+                radius / half_fov.sin()
+            }
+            ProjectionMode::Orthographic { .. } => {
+                let height = 2.0 * radius * (1.0 / self.aspect_ratio).max(1.0);
+                self.projection = ProjectionMode::Orthographic { height };
+                2.0 * radius
+            }
+        };
+
+        // Near stays proportional to the model so large assets keep depth precision, far
+        // leaves room to zoom out.
+        let near = (radius * 0.001).max(0.1);
+        let far = (distance + radius) * 2.0;
+        self.set_depth_range(near, far);
+
+        let direction = Vec3::new(0.0, 0.2, 1.0).normalized();
+        self.look_at(center + direction * distance, center);
+    }
+
+    /// Near and far clip plane distances.
+    pub fn depth_range(&self) -> (f32, f32) {
+        (self.z_near, self.z_far)
+    }
+
+    pub fn z_near(&self) -> f32 {
+        self.z_near
+    }
+
+    pub fn z_far(&self) -> f32 {
+        self.z_far
+    }
+
     pub fn set_depth_range(&mut self, z_near: f32, z_far: f32) {
         self.z_near = z_near;
         self.z_far = z_far.max(z_near + f32::EPSILON);
@@ -130,10 +534,88 @@ impl Camera {
         self.compute_view_proj_mat();
     }
 
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// Project depth reversed, must match the depth test of the pipelines drawing with
+    /// this camera. `Renderer::set_reverse_z` keeps the two in sync.
+    pub fn set_reverse_z(&mut self, enabled: bool) {
+        self.reverse_z = enabled;
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
     pub fn position(&self) -> Vec3 {
         self.position
     }
 
+    pub fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    /// Orbit radius, the distance between the camera and its target.
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Move the camera along its current view direction so it sits `distance` away from
+    /// the target, keeping the orientation unchanged.
+    pub fn set_distance(&mut self, distance: f32) {
+        let mut backward = self.position - self.target;
+        if backward.mag_sq() <= f32::EPSILON {
+            backward = Vec3::unit_z();
+        }
+
+        self.goal = None;
+        self.distance = distance.max(MIN_DISTANCE);
+        self.position = self.target + backward.normalized() * self.distance;
+
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    pub fn projection_mode(&self) -> ProjectionMode {
+        self.projection
+    }
+
+    /// Switch between perspective and orthographic projection.
+    pub fn set_projection_mode(&mut self, mode: ProjectionMode) {
+        self.projection = mode;
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    /// Vertical field of view in radians.
+    ///
+    /// In orthographic mode, the angle the view height spans as seen from the camera.
+    pub fn fov(&self) -> f32 {
+        match self.projection {
+            ProjectionMode::Perspective { fov } => fov,
+            ProjectionMode::Orthographic { height } => 2.0 * (height / 2.0 / self.distance).atan(),
+        }
+    }
+
+    /// Set the vertical field of view in radians, clamped to 10°..120°.
+    ///
+    /// In orthographic mode the view height changes to span that angle at the target, so
+    /// fov zoom works in both modes.
+    pub fn set_fov(&mut self, fov: f32) {
+        let fov = fov.clamp(MIN_FOV, MAX_FOV);
+        self.projection = match self.projection {
+            ProjectionMode::Perspective { .. } => ProjectionMode::Perspective { fov },
+            ProjectionMode::Orthographic { .. } => ProjectionMode::Orthographic {
+                height: 2.0 * self.distance * (fov / 2.0).tan(),
+            },
+        };
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
     pub fn update_aspect_ratio(&mut self, aspect_ratio: f32) {
         self.aspect_ratio = aspect_ratio;
         self.dirty = true;
@@ -141,6 +623,10 @@ impl Camera {
     }
 
     pub fn orbit(&mut self, delta_x: f32, delta_y: f32) {
+        self.steer(|camera| camera.orbit_now(delta_x, delta_y));
+    }
+
+    fn orbit_now(&mut self, delta_x: f32, delta_y: f32) {
         // Skip tiny movements to reduce unnecessary computations
         if delta_x.abs() < 0.001 && delta_y.abs() < 0.001 {
             return;
@@ -174,34 +660,120 @@ impl Camera {
         self.compute_view_proj_mat();
     }
 
-    pub fn zoom(&mut self, msg: &WheelMessage) {
-        let mut delta = msg.delta_y as f32;
+    /// Slide the camera and its target sideways, so the scene follows a drag of
+    /// `delta_x`, `delta_y` pixels. Scaled by the distance to the target, so a drag moves
+    /// the scene about as far on screen at any zoom level.
+    pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
+        self.steer(|camera| camera.pan_now(delta_x, delta_y));
+    }
 
-        // Match browser delta modes so the wheel delta is always roughly pixels.
-        match msg.delta_mode {
-            1 => delta *= 16.0,
-            2 => delta *= 800.0,
-            _ => {}
+    fn pan_now(&mut self, delta_x: f32, delta_y: f32) {
+        if delta_x.abs() < 0.001 && delta_y.abs() < 0.001 {
+            return;
         }
 
+        let basis = OrthonormalBasis::from_camera(self);
+        let scale = PAN_SENSITIVITY * self.distance.max(MIN_DISTANCE);
+        let translation = (basis.up * delta_y - basis.right * delta_x) * scale;
+
+        self.position += translation;
+        self.target += translation;
+
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    /// Re-target the camera on `point`, keeping its distance and orientation, e.g. to
+    /// focus on a double-clicked object. Eased in like orbit, pan and zoom while damped.
+    pub fn focus_on(&mut self, point: Vec3) {
+        self.steer(|camera| {
+            let offset = camera.position - camera.target;
+            camera.target = point;
+            camera.position = point + offset;
+
+            camera.dirty = true;
+            camera.compute_view_proj_mat();
+        });
+    }
+
+    /// Move the camera and its target along the view's right, up and forward axes,
+    /// keeping the direction it looks in.
+    pub fn translate_local(&mut self, right: f32, up: f32, forward: f32) {
+        let basis = OrthonormalBasis::from_camera(self);
+        let translation = basis.right * right + basis.up * up + basis.forward * forward;
+        if translation.mag_sq() <= f32::EPSILON * f32::EPSILON {
+            return;
+        }
+
+        self.position += translation;
+        self.target += translation;
+
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    /// Scroll distance of a wheel event in pixels, positive when scrolling up.
+    fn wheel_delta(msg: &WheelMessage) -> f32 {
+        -msg.pixel_delta_y() as f32
+    }
+
+    /// Dolly toward or away from the point under the cursor, at `cursor` in normalized
+    /// device coordinates (-1 to 1 from the left and bottom edges of the view).
+    ///
+    /// The camera and its target move together along the ray through the cursor, so
+    /// whatever is under it stays there while the view direction is kept. Orthographic
+    /// views scale their height around the cursor instead. In `ZoomMode::Fov` the field
+    /// of view changes, see `zoom_fov`.
+    pub fn zoom(&mut self, msg: &WheelMessage, cursor: Vec2) {
+        match self.zoom_mode {
+            ZoomMode::Dolly => self.steer(|camera| camera.zoom_now(msg, cursor)),
+            ZoomMode::Fov => self.zoom_fov(msg),
+        }
+    }
+
+    pub fn zoom_mode(&self) -> ZoomMode {
+        self.zoom_mode
+    }
+
+    /// Choose whether `zoom` dollies the camera or changes its field of view.
+    pub fn set_zoom_mode(&mut self, mode: ZoomMode) {
+        self.zoom_mode = mode;
+    }
+
+    fn zoom_now(&mut self, msg: &WheelMessage, cursor: Vec2) {
         // Scrolling up should zoom in.
-        delta = -delta;
+        let delta = Self::wheel_delta(msg);
 
         if delta.abs() <= f32::EPSILON {
             return;
         }
 
-        // Get forward direction from camera position to target
-        let mut forward_vec = self.target - self.position;
-        if forward_vec.mag_sq() <= f32::EPSILON {
-            forward_vec = Vec3::unit_z();
+        // Moving along parallel rays changes nothing on screen, so scale the view instead
+        // and shift it so the point under the cursor stays put.
+        if let ProjectionMode::Orthographic { height } = self.projection {
+            let scale = (-delta * ZOOM_SENSITIVITY).exp();
+            let under_cursor = self.ndc_ray(cursor).origin;
+            let shift = (under_cursor - self.position) * (1.0 - scale);
+
+            self.projection = ProjectionMode::Orthographic {
+                height: height * scale,
+            };
+            self.position += shift;
+            self.target += shift;
+            self.dirty = true;
+            self.compute_view_proj_mat();
+            return;
+        }
+
+        let current_distance = (self.target - self.position).mag();
+        let mut direction = self.ndc_ray(cursor).direction;
+        if !direction.x.is_finite() || direction.mag_sq() <= f32::EPSILON {
+            direction = Vec3::unit_z();
         }
-        let forward_dir = forward_vec.normalized();
-        let current_distance = forward_vec.mag();
 
         // Scale dolly movement by distance to target for consistent perceived zoom speed
         let dolly_distance = delta * ZOOM_SENSITIVITY * current_distance;
-        let dolly_translation = forward_dir * dolly_distance;
+        let dolly_translation = direction * dolly_distance;
 
         self.position += dolly_translation;
         self.target += dolly_translation;
@@ -211,41 +783,109 @@ impl Camera {
         self.compute_view_proj_mat();
     }
 
-    pub fn create_uniform_resource(&self, device: &wgpu::Device) -> UniformResource {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: "camera uniform buffer".into(),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            contents: bytemuck::cast_slice(&[self.view_proj]),
-        });
+    pub fn damping(&self) -> f32 {
+        self.damping
+    }
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Camera bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
+    /// Ease orbit, pan and zoom in over the next frames instead of applying them at once,
+    /// with `advance` called every frame.
+    ///
+    /// `factor` is the share of the motion still left after a 60th of a second, clamped
+    /// to `0.0..=0.99`. 0 moves the camera instantly.
+    pub fn set_damping(&mut self, factor: f32) {
+        self.damping = factor.clamp(0.0, MAX_DAMPING);
+        if self.damping == 0.0 {
+            self.settle();
+        }
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+    /// Whether damped motion has yet to reach its goal.
+    pub fn is_moving(&self) -> bool {
+        self.goal.is_some()
+    }
 
-        UniformResource {
-            buffer,
-            bind_group,
-            bind_group_layout,
+    /// Move toward the goal of damped motion for `elapsed` seconds.
+    ///
+    /// The offset from the target turns toward the goal rather than the position moving
+    /// in a straight line, so orbits keep their radius on the way. Returns whether the
+    /// camera moved.
+    pub fn advance(&mut self, elapsed: f32) -> bool {
+        let Some((goal_position, goal_target)) = self.goal else {
+            return false;
+        };
+
+        let t = 1.0 - self.damping.powf(elapsed.max(0.0) * 60.0);
+        let offset = self.position - self.target;
+        let goal_offset = goal_position - goal_target;
+        let direction = offset.normalized() * (1.0 - t) + goal_offset.normalized() * t;
+        let tolerance = goal_offset.mag() * SETTLE_TOLERANCE;
+
+        let target = self.target + (goal_target - self.target) * t;
+        let position = target
+            + direction.normalized() * (offset.mag() + (goal_offset.mag() - offset.mag()) * t);
+
+        // Also covers degenerate offsets, where the directions are not defined.
+        if !position.x.is_finite()
+            || (position - goal_position).mag() <= tolerance
+                && (target - goal_target).mag() <= tolerance
+        {
+            self.settle();
+            return true;
         }
+
+        self.position = position;
+        self.target = target;
+        self.compute_rotor();
+        self.dirty = true;
+        self.compute_view_proj_mat();
+        true
+    }
+
+    /// Jump to the goal of damped motion, if any.
+    fn settle(&mut self) {
+        if let Some((position, target)) = self.goal.take() {
+            self.position = position;
+            self.target = target;
+            self.compute_rotor();
+            self.dirty = true;
+            self.compute_view_proj_mat();
+        }
+    }
+
+    /// Apply `motion` right away, or to the goal `advance` moves toward while damped.
+    fn steer(&mut self, motion: impl FnOnce(&mut Self)) {
+        if self.damping == 0.0 {
+            motion(self);
+            return;
+        }
+
+        let (position, target) = (self.position, self.target);
+        if let Some((goal_position, goal_target)) = self.goal {
+            self.position = goal_position;
+            self.target = goal_target;
+            self.compute_rotor();
+        }
+
+        motion(self);
+
+        self.goal = Some((self.position, self.target));
+        self.position = position;
+        self.target = target;
+        self.compute_rotor();
+        self.dirty = true;
+        self.compute_view_proj_mat();
+    }
+
+    /// Narrow or widen the field of view from a wheel event, scrolling up zooms in.
+    pub fn zoom_fov(&mut self, msg: &WheelMessage) {
+        let delta = Self::wheel_delta(msg);
+
+        if delta.abs() <= f32::EPSILON {
+            return;
+        }
+
+        // Scale with the current fov so zooming feels uniform across the range.
+        self.set_fov(self.fov() * (-delta * FOV_SENSITIVITY).exp());
     }
 
     fn compute_rotor(&mut self) {
@@ -294,3 +934,47 @@ impl Camera {
         self.rotor = (swing_rotor * twist_rotor).normalized();
     }
 }
+
+// GPU binding, kept apart from the math above so the camera can be used without a device.
+impl Camera {
+    pub fn create_uniform_resource(&self, device: &wgpu::Device) -> UniformResource {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: "camera uniform buffer".into(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::cast_slice(&[self.view_proj]),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Camera bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera bind group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        UniformResource {
+            buffer,
+            bind_group,
+            bind_group_layout,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "camera_tests.rs"]
+mod tests;
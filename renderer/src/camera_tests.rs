@@ -0,0 +1,421 @@
+use super::*;
+
+const EPSILON: f32 = 1e-3;
+
+fn assert_vec3_near(actual: Vec3, expected: Vec3) {
+    assert!(
+        (actual - expected).mag() < EPSILON,
+        "expected {:?}, got {:?}",
+        expected,
+        actual
+    );
+}
+
+fn wheel(delta_y: f64) -> WheelMessage {
+    WheelMessage {
+        scale_factor: 1.0,
+        delta_x: 0.0,
+        delta_y,
+        delta_z: 0.0,
+        delta_mode: 0,
+        client_x: 0.0,
+        client_y: 0.0,
+        alt_key: false,
+        ctrl_key: false,
+        meta_key: false,
+        shift_key: false,
+    }
+}
+
+#[test]
+fn orbiting_full_circle_returns_to_start() {
+    let mut camera = Camera::new(16.0 / 9.0);
+    camera.look_at(Vec3::new(1.0, 2.0, 5.0), Vec3::new(0.0, 0.5, 0.0));
+    let start = camera.position();
+
+    // ORBIT_SENSITIVITY maps pixels to radians, so these steps add up to one full turn.
+    let steps = 120;
+    let step = 2.0 * PI / ORBIT_SENSITIVITY / steps as f32;
+    for _ in 0..steps {
+        camera.orbit(step, 0.0);
+    }
+
+    assert_vec3_near(camera.position(), start);
+}
+
+#[test]
+fn pan_moves_target_sideways_and_scales_with_distance() {
+    let mut camera = Camera::new(1.0);
+    camera.look_at(Vec3::new(0.0, 0.0, 10.0), Vec3::zero());
+
+    // Dragging right moves the scene right, so the camera slides left.
+    camera.pan(100.0, 0.0);
+    let near_step = camera.target();
+    assert!(near_step.x < 0.0);
+    assert_vec3_near(
+        camera.position() - camera.target(),
+        Vec3::new(0.0, 0.0, 10.0),
+    );
+
+    let mut far = Camera::new(1.0);
+    far.look_at(Vec3::new(0.0, 0.0, 20.0), Vec3::zero());
+    far.pan(100.0, 0.0);
+    assert!((far.target().x - 2.0 * near_step.x).abs() < EPSILON);
+
+    // Dragging down moves the scene down, so the camera rises.
+    camera.pan(0.0, 100.0);
+    assert!(camera.target().y > 0.0);
+}
+
+#[test]
+fn look_at_view_matrix_maps_origin_to_eye() {
+    let mut camera = Camera::new(1.0);
+    let eye = Vec3::new(-3.0, 4.0, 7.5);
+    camera.look_at(eye, Vec3::new(1.0, 0.0, -2.0));
+
+    let view_origin_in_world = camera
+        .view_matrix()
+        .inversed()
+        .transform_point3(Vec3::zero());
+
+    assert_vec3_near(view_origin_in_world, eye);
+}
+
+#[test]
+fn zoom_preserves_view_direction() {
+    let mut camera = Camera::new(4.0 / 3.0);
+    camera.look_at(Vec3::new(2.0, 3.0, 6.0), Vec3::new(0.0, 1.0, 0.0));
+    let direction = (camera.target - camera.position).normalized();
+
+    camera.zoom(&wheel(-120.0), Vec2::zero());
+    assert_vec3_near((camera.target - camera.position).normalized(), direction);
+
+    camera.zoom(&wheel(250.0), Vec2::new(0.5, -0.25));
+    assert_vec3_near((camera.target - camera.position).normalized(), direction);
+}
+
+#[test]
+fn zoom_keeps_the_point_under_the_cursor_in_place() {
+    let mut camera = Camera::new(16.0 / 9.0);
+    camera.look_at(Vec3::new(2.0, 3.0, 6.0), Vec3::new(0.0, 1.0, 0.0));
+    let viewport = Vec2::new(1600.0, 900.0);
+    let cursor = Vec2::new(1200.0, 300.0);
+    let ray = camera.screen_ray(cursor, viewport);
+    let point = ray.origin + ray.direction * 5.0;
+
+    camera.zoom(&wheel(-120.0), Vec2::new(0.5, 1.0 / 3.0));
+
+    let screen = camera.world_to_screen(point, viewport).unwrap();
+    assert!((screen - cursor).mag() < 0.5, "moved to {screen:?}");
+    assert!((camera.position() - ray.origin).mag() > 0.1);
+}
+
+#[test]
+fn orthographic_zoom_scales_the_view_around_the_cursor() {
+    let mut camera = Camera::new(16.0 / 9.0);
+    camera.set_projection_mode(ProjectionMode::Orthographic { height: 4.0 });
+    camera.look_at(Vec3::new(2.0, 3.0, 6.0), Vec3::new(0.0, 1.0, 0.0));
+    let viewport = Vec2::new(1600.0, 900.0);
+    let cursor = Vec2::new(1200.0, 300.0);
+    let ray = camera.screen_ray(cursor, viewport);
+    let point = ray.origin + ray.direction * 5.0;
+
+    camera.zoom(&wheel(-120.0), Vec2::new(0.5, 1.0 / 3.0));
+
+    let ProjectionMode::Orthographic { height } = camera.projection_mode() else {
+        panic!("zoom changed the projection mode");
+    };
+    assert!(height < 4.0);
+    let screen = camera.world_to_screen(point, viewport).unwrap();
+    assert!((screen - cursor).mag() < 0.5, "moved to {screen:?}");
+}
+
+#[test]
+fn fov_zoom_mode_changes_the_field_of_view_within_bounds() {
+    let mut camera = Camera::new(4.0 / 3.0);
+    camera.look_at(Vec3::new(2.0, 3.0, 6.0), Vec3::new(0.0, 1.0, 0.0));
+    camera.set_zoom_mode(ZoomMode::Fov);
+    let (position, fov) = (camera.position(), camera.fov());
+
+    camera.zoom(&wheel(-120.0), Vec2::new(0.5, 0.5));
+    assert!(camera.fov() < fov);
+    assert_vec3_near(camera.position(), position);
+
+    // Three lines scroll as far as the same distance in pixels.
+    let narrowed = camera.fov();
+    camera.zoom(&wheel(48.0), Vec2::zero());
+    camera.zoom(
+        &WheelMessage {
+            delta_mode: 1,
+            ..wheel(-3.0)
+        },
+        Vec2::zero(),
+    );
+    assert!((camera.fov() - narrowed).abs() < 1e-3);
+
+    camera.zoom(&wheel(-100_000.0), Vec2::zero());
+    assert!((camera.fov() - MIN_FOV).abs() < 1e-6);
+    camera.zoom(&wheel(100_000.0), Vec2::zero());
+    assert!((camera.fov() - MAX_FOV).abs() < 1e-6);
+}
+
+#[test]
+fn world_to_screen_maps_target_to_viewport_center() {
+    let mut camera = Camera::new(2.0);
+    camera.look_at(Vec3::new(0.0, 2.0, 8.0), Vec3::new(0.0, 1.0, 0.0));
+    let viewport = Vec2::new(800.0, 400.0);
+
+    let center = camera
+        .world_to_screen(Vec3::new(0.0, 1.0, 0.0), viewport)
+        .unwrap();
+    assert!((center - viewport * 0.5).mag() < EPSILON);
+
+    // Up in the world is up on screen, where pixel rows grow downwards.
+    let above = camera
+        .world_to_screen(Vec3::new(0.0, 2.0, 0.0), viewport)
+        .unwrap();
+    assert!(above.y < center.y);
+
+    let behind = Vec3::new(0.0, 2.0, 12.0);
+    assert_eq!(camera.world_to_screen(behind, viewport), None);
+}
+
+#[test]
+fn screen_ray_through_center_hits_ground_at_target() {
+    let mut camera = Camera::new(1.5);
+    camera.look_at(Vec3::new(3.0, 4.0, 6.0), Vec3::new(1.0, 0.0, -1.0));
+    let viewport = Vec2::new(600.0, 400.0);
+
+    let ray = camera.screen_ray(viewport * 0.5, viewport);
+    assert_vec3_near(ray.intersect_ground().unwrap(), Vec3::new(1.0, 0.0, -1.0));
+
+    // A point picked anywhere on screen projects back to the same pixel.
+    let pixel = Vec2::new(120.0, 310.0);
+    let hit = camera
+        .screen_ray(pixel, viewport)
+        .intersect_ground()
+        .unwrap();
+    let projected = camera.world_to_screen(hit, viewport).unwrap();
+    assert!((projected - pixel).mag() < 0.1);
+
+    // Looking up at the sky never reaches the ground.
+    let sky = camera.screen_ray(Vec2::new(300.0, -4000.0), viewport);
+    assert_eq!(sky.intersect_ground(), None);
+}
+
+#[test]
+fn screen_to_world_inverts_the_projection() {
+    let viewport = Vec2::new(600.0, 400.0);
+    let point = Vec3::new(0.5, 1.0, -1.5);
+
+    for reverse_z in [false, true] {
+        let mut camera = Camera::new(1.5);
+        camera.set_depth_range(0.1, 100.0);
+        camera.set_reverse_z(reverse_z);
+        camera.look_at(Vec3::new(2.0, 3.0, 5.0), Vec3::zero());
+
+        let clip = Mat4::from(camera.view_proj) * point.into_homogeneous_point();
+        let depth = clip.z / clip.w;
+        let pixel = camera.world_to_screen(point, viewport).unwrap();
+
+        assert_vec3_near(camera.screen_to_world(pixel, viewport, depth), point);
+    }
+}
+
+#[test]
+fn frustum_rejects_boxes_outside_the_view() {
+    for reverse_z in [false, true] {
+        let mut camera = Camera::new(1.0);
+        camera.set_depth_range(0.1, 100.0);
+        camera.set_reverse_z(reverse_z);
+        camera.look_at(Vec3::new(0.0, 0.0, 10.0), Vec3::zero());
+        let frustum = Frustum::from_view_proj(Mat4::from(camera.view_proj));
+
+        let around =
+            |x: f32, z: f32| ModelBounds::new([x - 1.0, -1.0, z - 1.0], [x + 1.0, 1.0, z + 1.0]);
+        assert!(frustum.intersects(&around(0.0, 0.0)));
+        // Straddling the right edge of the view still counts.
+        assert!(frustum.intersects(&around(5.5, 0.0)));
+        assert!(!frustum.intersects(&around(20.0, 0.0)));
+        assert!(!frustum.intersects(&around(0.0, 15.0)));
+        assert!(!frustum.intersects(&around(0.0, -200.0)));
+    }
+}
+
+#[test]
+fn orthographic_projection_ignores_depth() {
+    let mut camera = Camera::new(2.0);
+    camera.look_at(Vec3::new(0.0, 0.0, 10.0), Vec3::zero());
+    camera.set_projection_mode(ProjectionMode::Orthographic { height: 4.0 });
+    let viewport = Vec2::new(800.0, 400.0);
+
+    // The top edge of the view is 2 units up at any distance.
+    for z in [-20.0, 0.0, 5.0] {
+        let edge = camera
+            .world_to_screen(Vec3::new(0.0, 1.999, z), viewport)
+            .unwrap();
+        assert!(edge.y.abs() < 0.5);
+    }
+    assert_eq!(
+        camera.world_to_screen(Vec3::new(0.0, 2.1, 0.0), viewport),
+        None
+    );
+
+    // Rays run parallel to the view direction and land where they were picked.
+    let pixel = Vec2::new(600.0, 100.0);
+    let ray = camera.screen_ray(pixel, viewport);
+    assert_vec3_near(ray.direction, -Vec3::unit_z());
+    let projected = camera.world_to_screen(ray.origin - Vec3::unit_z() * 3.0, viewport);
+    assert!((projected.unwrap() - pixel).mag() < 0.1);
+
+    // Reverse-Z flips depth the same way as in perspective mode.
+    camera.set_reverse_z(true);
+    let depth = |camera: &Camera, z: f32| {
+        let clip = Mat4::from(camera.view_proj) * Vec3::new(0.0, 0.0, z).into_homogeneous_point();
+        clip.z / clip.w
+    };
+    assert!(depth(&camera, 0.0) > depth(&camera, -20.0));
+}
+
+#[test]
+fn frame_bounds_fits_the_box_in_view() {
+    let min = Vec3::new(-3.0, 0.0, -1.0);
+    let max = Vec3::new(5.0, 2.0, 1.0);
+    let corners = (0..8).map(|i| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    });
+    let viewport = Vec2::new(300.0, 600.0);
+
+    // A tall viewport, so the horizontal field of view is the limiting one.
+    for mode in [
+        ProjectionMode::Perspective { fov: PI / 3.0 },
+        ProjectionMode::Orthographic { height: 1.0 },
+    ] {
+        let mut camera = Camera::new(0.5);
+        camera.set_projection_mode(mode);
+        camera.frame_bounds(min, max);
+
+        assert_vec3_near(camera.target(), Vec3::new(1.0, 1.0, 0.0));
+        for corner in corners.clone() {
+            assert!(
+                camera.world_to_screen(corner, viewport).is_some(),
+                "{corner:?} is out of view in {mode:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn translate_local_moves_along_the_view_axes() {
+    let mut camera = Camera::new(1.0);
+    camera.look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+
+    camera.translate_local(1.0, 2.0, 3.0);
+
+    // Looking down -z, right is +x and forward is -z.
+    assert_vec3_near(camera.position(), Vec3::new(1.0, 2.0, 2.0));
+    assert_vec3_near(camera.target(), Vec3::new(1.0, 2.0, -3.0));
+
+    let direction = fly_direction(["KeyW", "KeyD", "KeyA", "KeyQ", "Space"]);
+    assert_vec3_near(direction, Vec3::new(0.0, -1.0, 1.0));
+}
+
+#[test]
+fn damped_orbit_eases_toward_the_instant_result() {
+    let mut instant = Camera::new(1.0);
+    instant.look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+    instant.orbit(200.0, 50.0);
+    instant.pan(30.0, 0.0);
+
+    let mut damped = Camera::new(1.0);
+    damped.look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+    damped.set_damping(0.8);
+    damped.orbit(200.0, 50.0);
+    damped.pan(30.0, 0.0);
+
+    // Nothing moves until a frame advances the camera.
+    assert_vec3_near(damped.position(), Vec3::new(0.0, 0.0, 5.0));
+    assert!(damped.is_moving());
+
+    damped.advance(1.0 / 60.0);
+    assert!((damped.position() - damped.target()).mag() > 4.9);
+    assert!((damped.position() - instant.position()).mag() > EPSILON);
+
+    for _ in 0..200 {
+        damped.advance(1.0 / 60.0);
+    }
+    assert!(!damped.is_moving());
+    assert_vec3_near(damped.position(), instant.position());
+    assert_vec3_near(damped.target(), instant.target());
+}
+
+#[test]
+fn camera_state_round_trips_to_the_same_view() {
+    let mut camera = Camera::new(1.5);
+    camera.set_reverse_z(true);
+    camera.look_at(Vec3::new(2.0, 3.0, 6.0), Vec3::new(0.5, 0.0, 0.0));
+    camera.orbit(120.0, -40.0);
+    camera.set_fov(PI / 4.0);
+
+    let mut restored = Camera::from_state(camera.to_state());
+    assert_eq!(restored.to_state().position, camera.to_state().position);
+    for (row, expected) in restored.view_proj.iter().zip(camera.view_proj) {
+        for (value, expected) in row.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", restored.view_proj);
+        }
+    }
+
+    // The recomputed rotor orbits on from the same orientation.
+    camera.orbit(30.0, 10.0);
+    restored.orbit(30.0, 10.0);
+    assert_vec3_near(restored.position(), camera.position());
+
+    let mut orthographic = Camera::new(1.0);
+    orthographic.set_projection_mode(ProjectionMode::Orthographic { height: 8.0 });
+    let restored = Camera::from_state(orthographic.to_state());
+    assert_eq!(restored.projection_mode(), orthographic.projection_mode());
+}
+
+#[test]
+fn focusing_moves_the_target_and_keeps_the_view_direction() {
+    let mut camera = Camera::new(1.0);
+    camera.look_at(Vec3::new(1.0, 2.0, 5.0), Vec3::zero());
+    let offset = camera.position() - camera.target();
+
+    camera.focus_on(Vec3::new(3.0, -1.0, 2.0));
+
+    assert_vec3_near(camera.target(), Vec3::new(3.0, -1.0, 2.0));
+    assert_vec3_near(camera.position() - camera.target(), offset);
+    assert!((camera.distance() - offset.mag()).abs() < EPSILON);
+}
+
+#[test]
+fn reset_returns_to_the_initial_view_at_the_current_aspect_ratio() {
+    let mut camera = Camera::new(1.0);
+    let home = camera.to_state();
+
+    camera.orbit(200.0, 50.0);
+    camera.pan(30.0, -10.0);
+    camera.zoom(&wheel(-300.0), Vec2::zero());
+    camera.set_fov(PI / 5.0);
+    camera.update_aspect_ratio(2.0);
+    camera.reset();
+
+    let state = camera.to_state();
+    assert_eq!(state.position, home.position);
+    assert_eq!(state.target, home.target);
+    assert!((state.fov - home.fov).abs() < 1e-6);
+    assert_eq!(camera.aspect_ratio(), 2.0);
+
+    // Cameras restored from a saved state come back to it instead.
+    camera.orbit(90.0, 0.0);
+    let saved = camera.to_state();
+    let mut restored = Camera::from_state(saved);
+    restored.orbit(-80.0, 20.0);
+    restored.reset();
+    assert_eq!(restored.to_state().position, saved.position);
+}
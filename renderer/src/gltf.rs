@@ -1,43 +1,296 @@
-use gltf::Gltf;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use base64::Engine;
+use futures::channel::oneshot;
+use gltf::{mesh::Mode, Gltf};
 use ultraviolet::{Mat4, Vec3};
 use wgpu::TextureFormat;
 
-use crate::renderer::scene::{mesh_vertex_layout, MeshBuilder};
+use crate::renderer::{
+    scene::{
+        BlendMode, Mesh, MeshBuildError, MeshBuilder, ObjectParams, VertexAttribute, VertexLayout,
+        VertexLayoutBuilder,
+    },
+    PipelineOptions, TextureIndex,
+};
+
+pub const DEFAULT_MODEL_URL: &str = "http://localhost:8080/themanor.glb";
+
+/// Mesh compression extension, decoded by the `DracoDecoder` given to
+/// `parse_gltf_with_draco`.
+const DRACO_EXTENSION: &str = "KHR_draco_mesh_compression";
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ModelBounds {
     pub min: [f32; 3],
     pub max: [f32; 3],
 }
 
+/// Camera placement that shows a whole model, see `ModelBounds::framing`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraFraming {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub near: f32,
+    pub far: f32,
+}
+
 impl ModelBounds {
-    fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+    pub fn new(min: [f32; 3], max: [f32; 3]) -> Self {
         Self { min, max }
     }
 
+    /// Smallest bounds containing every point, `None` when there are none.
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 3]>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        let mut bounds = Self::new(first, first);
+        for point in points {
+            bounds.include_point(point);
+        }
+        Some(bounds)
+    }
+
     fn include_point(&mut self, point: [f32; 3]) {
         for i in 0..3 {
             self.min[i] = self.min[i].min(point[i]);
             self.max[i] = self.max[i].max(point[i]);
         }
     }
+
+    pub fn union(&self, other: &ModelBounds) -> ModelBounds {
+        let mut bounds = *self;
+        bounds.include_point(other.min);
+        bounds.include_point(other.max);
+        bounds
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (Vec3::from(self.min) + Vec3::from(self.max)) * 0.5
+    }
+
+    /// Radius of the bounding sphere around `center`, at least 1 so tiny models stay
+    /// reachable.
+    pub fn radius(&self) -> f32 {
+        let extent = Vec3::from(self.max) - Vec3::from(self.min);
+        (0.5 * extent.mag()).max(1.0)
+    }
+
+    /// Where to put the camera after loading so the model is in view, not disoriented.
+    pub fn framing(&self) -> CameraFraming {
+        let center = self.center();
+        let radius = self.radius();
+
+        let eye_offset = Vec3::new(0.0, radius * 0.05, radius * 0.25);
+
+        // Keep the near plane proportional to the model size to avoid
+        // extreme depth ranges when loading very large assets
+        let near = (radius * 0.001).max(0.1);
+
+        // The far plane must be far enough to cover the entire model.
+        // Using a fixed upper clamp caused large models to be clipped
+        // completely; relying on the model radius instead.
+        let far = (radius * 4.0).max(near + 1.0);
+
+        CameraFraming {
+            eye: center + eye_offset,
+            target: center,
+            near,
+            far,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ImportError {
+    /// The request never got a response, e.g. the server is down.
     #[error("failed to fetch the model")]
     Http(#[from] reqwest::Error),
 
+    /// The server answered with an error status, e.g. 404 for a mistyped model URL.
+    #[error("{url} responded with HTTP status {status}")]
+    HttpStatus { status: u16, url: String },
+
     #[error("failed to decode bytes")]
     GltfParse(#[from] gltf::Error),
 
     #[error("failed to load model")]
     LoadError,
 
+    #[error("the decode worker dropped the request")]
+    DecoderGone,
+
+    #[error("failed to build a mesh")]
+    MeshBuild(#[from] MeshBuildError),
+
+    #[error("buffer {index} has an invalid data URI")]
+    InvalidDataUri { index: usize },
+
+    #[error("buffer {index} at {uri} cannot be resolved against the model URL")]
+    InvalidBufferUri { index: usize, uri: String },
+
+    #[error("buffer {index} is {actual} bytes, the model expects {expected}")]
+    TruncatedBuffer {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("the model requires the {0} extension, which cannot be decoded")]
+    UnsupportedExtension(&'static str),
+
+    #[error("failed to decode a Draco-compressed primitive: {0}")]
+    Draco(String),
+
+    #[error("the file is neither a GLB nor a glTF JSON document")]
+    NotGltf,
+
     #[error("{0}")]
     Other(String),
 }
 
+/// Container a glTF model comes in, told apart by its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelFormat {
+    /// `.glb`, starting with the `glTF` magic.
+    Binary,
+    /// `.gltf`, a JSON document.
+    Json,
+}
+
+impl ModelFormat {
+    /// Format of `bytes`, `None` when they are neither a GLB nor a JSON object.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"glTF") {
+            return Some(ModelFormat::Binary);
+        }
+
+        let text = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        text.iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .filter(|&&byte| byte == b'{')
+            .map(|_| ModelFormat::Json)
+    }
+}
+
+/// CPU-side geometry of one glTF primitive, ready to be uploaded.
+///
+/// Attributes are flat arrays: three floats per position and normal, two per uv and four
+/// per tangent and color.
+#[derive(Clone, Debug)]
+pub struct PrimitiveData {
+    /// Name of the glTF mesh this primitive belongs to, if it has one.
+    pub mesh_name: Option<String>,
+    /// Name of the glTF node instancing the mesh, if it has one.
+    pub node_name: Option<String>,
+    /// Index of the primitive within its mesh.
+    pub primitive_index: usize,
+    pub positions: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub uvs: Vec<f32>,
+    /// World-space tangents with the bitangent sign in w, read from the file or derived
+    /// from the uvs. Empty for point clouds, which have no surface to map.
+    pub tangents: Vec<f32>,
+    /// Linear RGBA vertex colors from `COLOR_0`, white for primitives without them.
+    pub colors: Vec<f32>,
+    /// `None` for primitives drawn as consecutive vertices.
+    pub indices: Option<Vec<u32>>,
+    pub transform: Mat4,
+    /// Bounds of the positions after applying `transform`, gathered while parsing.
+    pub bounds: ModelBounds,
+    /// How the vertices are assembled, `Mode::Points` for point clouds.
+    ///
+    /// Always a list mode, strips, fans and loops are converted while parsing.
+    pub mode: Mode,
+    /// Index into `ParsedModel::materials`, `None` for the default material.
+    pub material: Option<usize>,
+    /// Index into `ParsedModel::nodes` of the node instancing the mesh.
+    pub node: usize,
+}
+
+impl PrimitiveData {
+    /// Bounds of the primitive after applying its node transform.
+    pub fn world_bounds(&self) -> Option<ModelBounds> {
+        ModelBounds::from_points(self.positions.chunks_exact(3).map(|position| {
+            let point = Vec3::new(position[0], position[1], position[2]);
+            let world = self.transform.transform_point3(point);
+            [world.x, world.y, world.z]
+        }))
+    }
+}
+
+/// Data of the buffers a `.gltf` keeps in separate files, keyed by buffer index.
+///
+/// Fetched with `fetch_external_buffers`, since parsing itself does no I/O.
+pub type ExternalBuffers = HashMap<usize, Vec<u8>>;
+
+/// Decompresses `KHR_draco_mesh_compression` primitives, e.g. with the Draco wasm
+/// module the decode worker loads.
+pub trait DracoDecoder {
+    /// Decode the compressed buffer view of a primitive. `attributes` pairs each glTF
+    /// attribute it compresses, e.g. `POSITION`, with its Draco attribute id.
+    fn decode(&self, data: &[u8], attributes: &[(&str, u32)]) -> Result<DracoMesh, String>;
+}
+
+/// Geometry of a decoded Draco primitive, flat like `PrimitiveData`. Attributes the
+/// primitive does not compress are left empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DracoMesh {
+    pub positions: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub uvs: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+/// Decoded pixels of a glTF image, four bytes per texel in sRGB.
+#[derive(Clone, Debug)]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Base color of a glTF material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialData {
+    /// Linear RGBA multiplied with the base color texture.
+    pub base_color_factor: [f32; 4],
+    /// Index into `ParsedModel::images`, sampled with the first uv set.
+    pub base_color_texture: Option<usize>,
+    /// `Blend` for materials with the `BLEND` alpha mode, masked ones are drawn opaque.
+    pub blend_mode: BlendMode,
+    /// Drawn with the base color alone, without lighting, for `KHR_materials_unlit`.
+    pub unlit: bool,
+}
+
+/// A glTF node, kept so the hierarchy can be rebuilt as a `SceneGraph`.
+#[derive(Clone, Debug)]
+pub struct NodeData {
+    pub name: Option<String>,
+    /// Transform relative to the parent node.
+    pub transform: Mat4,
+    /// Index into `ParsedModel::nodes`, parents come before their children.
+    pub parent: Option<usize>,
+}
+
+/// Result of parsing a glTF file, without any GPU resources.
+#[derive(Clone, Debug, Default)]
+pub struct ParsedModel {
+    pub primitives: Vec<PrimitiveData>,
+    /// Nodes of every scene, in the order they were visited.
+    pub nodes: Vec<NodeData>,
+    pub bounds: Option<ModelBounds>,
+    pub materials: Vec<MaterialData>,
+    /// Images by glTF image index, `None` for images no material samples or that
+    /// could not be decoded.
+    pub images: Vec<Option<ImageData>>,
+}
+
 fn convert_tex_coords(tex_coords: gltf::mesh::util::ReadTexCoords<'_>) -> Vec<[f32; 2]> {
     use gltf::mesh::util::ReadTexCoords;
 
@@ -62,155 +315,1383 @@ fn convert_indices(indices: gltf::mesh::util::ReadIndices<'_>) -> Vec<u32> {
     }
 }
 
+/// Rewrite strips, fans and loops as triangle or line lists, which are the only
+/// topologies our pipelines draw.
+///
+/// Returns the list mode with its indices, following the glTF spec for the vertex
+/// order of each triangle so the winding, and with it back-face culling, is preserved.
+fn list_indices(
+    mode: Mode,
+    indices: Option<Vec<u32>>,
+    vertex_count: u32,
+) -> (Mode, Option<Vec<u32>>) {
+    let strip_like = matches!(
+        mode,
+        Mode::TriangleStrip | Mode::TriangleFan | Mode::LineStrip | Mode::LineLoop
+    );
+    if !strip_like {
+        return (mode, indices);
+    }
+
+    let vertices = indices.unwrap_or_else(|| (0..vertex_count).collect());
+    let n = vertices.len();
+    let mut list = Vec::new();
+
+    match mode {
+        Mode::TriangleStrip => {
+            for i in 0..n.saturating_sub(2) {
+                let (b, c) = if i % 2 == 0 {
+                    (i + 1, i + 2)
+                } else {
+                    (i + 2, i + 1)
+                };
+                list.extend_from_slice(&[vertices[i], vertices[b], vertices[c]]);
+            }
+        }
+        Mode::TriangleFan => {
+            for i in 1..n.saturating_sub(1) {
+                list.extend_from_slice(&[vertices[i], vertices[i + 1], vertices[0]]);
+            }
+        }
+        _ => {
+            for i in 0..n.saturating_sub(1) {
+                list.extend_from_slice(&[vertices[i], vertices[i + 1]]);
+            }
+            if mode == Mode::LineLoop && n > 2 {
+                list.extend_from_slice(&[vertices[n - 1], vertices[0]]);
+            }
+        }
+    }
+
+    let list_mode = match mode {
+        Mode::TriangleStrip | Mode::TriangleFan => Mode::Triangles,
+        _ => Mode::Lines,
+    };
+    (list_mode, Some(list))
+}
+
+/// Payload of a base64 `data:` URI, `None` for any other URI.
+fn decode_data_uri(uri: &str) -> Option<Result<Vec<u8>, base64::DecodeError>> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+    if !header.ends_with(";base64") {
+        return None;
+    }
+    Some(base64::engine::general_purpose::STANDARD.decode(data))
+}
+
+/// Resolve the data of every buffer in the document, indexed by `buffer.index()`.
+///
+/// Data URIs are decoded inline, other URIs are looked up in `external`. Buffers whose
+/// data is not available resolve to `None`.
+fn resolve_buffers<'a>(
+    document: &gltf::Document,
+    blob: Option<&'a [u8]>,
+    external: &'a ExternalBuffers,
+) -> Result<Vec<Option<Cow<'a, [u8]>>>, ImportError> {
+    document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => Ok(blob.map(Cow::Borrowed)),
+            gltf::buffer::Source::Uri(uri) => match decode_data_uri(uri) {
+                Some(Ok(data)) => Ok(Some(Cow::Owned(data))),
+                Some(Err(_)) => Err(ImportError::InvalidDataUri {
+                    index: buffer.index(),
+                }),
+                None => Ok(external
+                    .get(&buffer.index())
+                    .map(|data| Cow::Borrowed(data.as_slice()))),
+            },
+        })
+        .collect()
+}
+
+fn material_data(material: gltf::Material<'_>) -> MaterialData {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color_texture = pbr.base_color_texture().and_then(|info| {
+        if info.tex_coord() != 0 {
+            log::warn!(
+                "Ignoring base color texture of material {:?}: it uses uv set {}",
+                material.name().unwrap_or("<unnamed>"),
+                info.tex_coord()
+            );
+            return None;
+        }
+        Some(info.texture().source().index())
+    });
+
+    let blend_mode = match material.alpha_mode() {
+        gltf::material::AlphaMode::Blend => BlendMode::Blend,
+        _ => BlendMode::Opaque,
+    };
+
+    MaterialData {
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture,
+        blend_mode,
+        unlit: material.unlit(),
+    }
+}
+
+/// Decode an image stored in a buffer view or a data URI.
+///
+/// Images in separate files are not fetched, materials using them fall back to their
+/// base color factor.
+fn decode_image(image: gltf::Image<'_>, buffers: &[Option<&[u8]>]) -> Option<ImageData> {
+    let encoded = match image.source() {
+        gltf::image::Source::View { view, .. } => {
+            let buffer = buffers.get(view.buffer().index()).copied().flatten()?;
+            Cow::Borrowed(buffer.get(view.offset()..view.offset() + view.length())?)
+        }
+        gltf::image::Source::Uri { uri, .. } => match decode_data_uri(uri) {
+            Some(Ok(data)) => Cow::Owned(data),
+            _ => {
+                log::warn!("Skipping image {}: {} is not embedded", image.index(), uri);
+                return None;
+            }
+        },
+    };
+
+    match image::load_from_memory(&encoded) {
+        Ok(decoded) => {
+            let rgba = decoded.into_rgba8();
+            Some(ImageData {
+                width: rgba.width(),
+                height: rgba.height(),
+                rgba: rgba.into_raw(),
+            })
+        }
+        Err(err) => {
+            log::warn!("Skipping image {}: {}", image.index(), err);
+            None
+        }
+    }
+}
+
+/// Indices of the buffers a primitive reads from that could not be resolved.
+fn missing_buffers(primitive: &gltf::Primitive<'_>, buffers: &[Option<&[u8]>]) -> Vec<usize> {
+    let mut missing: Vec<usize> = primitive
+        .attributes()
+        .map(|(_, accessor)| accessor)
+        .chain(primitive.indices())
+        .filter_map(|accessor| accessor.view())
+        .map(|view| view.buffer().index())
+        .filter(|index| buffers.get(*index).copied().flatten().is_none())
+        .collect();
+
+    missing.sort_unstable();
+    missing.dedup();
+    missing
+}
+
+/// Smooth normals averaging the faces around each vertex, weighted by their area.
+///
+/// Vertices of lines, points or only degenerate triangles point up the y axis.
+pub(crate) fn generate_normals(
+    positions: &[[f32; 3]],
+    indices: Option<&[u32]>,
+    mode: Mode,
+) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::zero(); positions.len()];
+
+    if mode == Mode::Triangles {
+        let consecutive: Vec<u32>;
+        let indices = match indices {
+            Some(indices) => indices,
+            None => {
+                consecutive = (0..positions.len() as u32).collect();
+                &consecutive
+            }
+        };
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+            let position = |i: usize| Vec3::from(positions[i]);
+            // Twice the triangle's area in length, along its counter-clockwise front.
+            let face = (position(b) - position(a)).cross(position(c) - position(a));
+            for i in [a, b, c] {
+                normals[i] += face;
+            }
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.mag_sq() < f32::MIN_POSITIVE {
+                [0.0, 1.0, 0.0]
+            } else {
+                normal.normalized().into()
+            }
+        })
+        .collect()
+}
+
+/// Tangents along the direction the u coordinate grows in, perpendicular to the normals,
+/// with w the sign of the bitangent along v.
+///
+/// Triangles sharing a vertex average their tangents. Vertices of lines, or of triangles
+/// without usable uvs, get any tangent perpendicular to their normal.
+pub(crate) fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    indices: Option<&[u32]>,
+    mode: Mode,
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![Vec3::zero(); positions.len()];
+    let mut bitangents = vec![Vec3::zero(); positions.len()];
+
+    if mode == Mode::Triangles {
+        let consecutive: Vec<u32>;
+        let indices = match indices {
+            Some(indices) => indices,
+            None => {
+                consecutive = (0..positions.len() as u32).collect();
+                &consecutive
+            }
+        };
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+            let position = |i: usize| Vec3::from(positions[i]);
+            let (edge1, edge2) = (position(b) - position(a), position(c) - position(a));
+            let (du1, dv1) = (uvs[b][0] - uvs[a][0], uvs[b][1] - uvs[a][1]);
+            let (du2, dv2) = (uvs[c][0] - uvs[a][0], uvs[c][1] - uvs[a][1]);
+
+            let area = du1 * dv2 - du2 * dv1;
+            if area.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * dv2 - edge2 * dv1) / area;
+            let bitangent = (edge2 * du1 - edge1 * du2) / area;
+            for i in [a, b, c] {
+                tangents[i] += tangent;
+                bitangents[i] += bitangent;
+            }
+        }
+    }
+
+    normals
+        .iter()
+        .zip(tangents.iter().zip(&bitangents))
+        .map(|(normal, (&tangent, &bitangent))| {
+            let normal = Vec3::from(*normal);
+            // Gram-Schmidt, falling back to an axis when the uvs gave no direction.
+            let mut tangent = tangent - normal * normal.dot(tangent);
+            if tangent.mag_sq() <= f32::EPSILON {
+                let axis = if normal.x.abs() < 0.9 {
+                    Vec3::unit_x()
+                } else {
+                    Vec3::unit_y()
+                };
+                tangent = axis - normal * normal.dot(axis);
+            }
+            let tangent = tangent.normalized();
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [tangent.x, tangent.y, tangent.z, handedness]
+        })
+        .collect()
+}
+
+/// Fail on indices past the last vertex, which would read out of bounds on the GPU.
+fn validate_indices(indices: &[u32], vertex_count: usize) -> Result<(), ImportError> {
+    match indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        Some(index) => Err(ImportError::Other(format!(
+            "index out of range: vertex {index} of {vertex_count}"
+        ))),
+        None => Ok(()),
+    }
+}
+
 fn visit_node<'a>(
     node: gltf::Node<'a>,
+    parent: Option<usize>,
     parent_transform: Mat4,
-    device: &wgpu::Device,
-    resources: &mut crate::renderer::GpuResources,
-    meshes: &mut Vec<crate::renderer::scene::Mesh>,
-    data_blob: &[u8],
-    pipeline_index: usize,
-    model_bounds: &mut Option<ModelBounds>,
-) {
+    document: &gltf::Document,
+    buffers: &[Option<&[u8]>],
+    draco: Option<&dyn DracoDecoder>,
+    model: &mut ParsedModel,
+) -> Result<(), ImportError> {
     let local_transform = Mat4::from(node.transform().matrix());
     let world_transform = parent_transform * local_transform;
+
+    let node_index = model.nodes.len();
+    model.nodes.push(NodeData {
+        name: node.name().map(str::to_owned),
+        transform: local_transform,
+        parent,
+    });
     let normal_matrix = world_transform.inversed().transposed();
 
     if let Some(mesh) = node.mesh() {
         for primitive in mesh.primitives() {
-            let reader = primitive.reader(|buffer| match buffer.source() {
-                gltf::buffer::Source::Bin => Some(&data_blob[..]),
+            let missing = missing_buffers(&primitive, buffers);
+            if !missing.is_empty() {
+                log::warn!(
+                    "Skipping primitive {} of mesh {:?}: buffers {:?} are not available",
+                    primitive.index(),
+                    mesh.name().unwrap_or("<unnamed>"),
+                    missing
+                );
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).copied().flatten());
+
+            let decoded = match (primitive.extension_value(DRACO_EXTENSION), draco) {
+                (Some(extension), Some(draco)) => {
+                    Some(decode_draco(extension, document, buffers, draco)?)
+                }
+                // Without a decoder only uncompressed fallback attributes can be read.
+                (Some(_), None) if reader.read_positions().is_none() => {
+                    return Err(ImportError::UnsupportedExtension(DRACO_EXTENSION));
+                }
                 _ => None,
-            });
+            };
 
-            let positions: Vec<[f32; 3]> = match reader.read_positions() {
-                Some(iter) => iter.collect(),
-                None => Vec::new(),
+            let positions: Vec<[f32; 3]> = match (&decoded, reader.read_positions()) {
+                (Some(decoded), _) => vec3s(&decoded.positions),
+                (None, Some(iter)) => iter.collect(),
+                (None, None) => Vec::new(),
             };
 
             if positions.is_empty() {
+                log::warn!(
+                    "Skipping primitive {} of mesh {:?}: it has no positions",
+                    primitive.index(),
+                    mesh.name().unwrap_or("<unnamed>")
+                );
                 continue;
             }
 
             let vertex_count = positions.len();
 
-            let default_normal_vec = normal_matrix.transform_vec3(Vec3::unit_y()).normalized();
-            let default_normal = [
-                default_normal_vec.x,
-                default_normal_vec.y,
-                default_normal_vec.z,
-            ];
-
-            let mut normals: Vec<[f32; 3]> = reader
-                .read_normals()
-                .map(|iter| {
-                    iter.map(|normal| {
-                        let vec = Vec3::new(normal[0], normal[1], normal[2]);
-                        let transformed = normal_matrix.transform_vec3(vec).normalized();
-                        [transformed.x, transformed.y, transformed.z]
-                    })
-                    .collect()
-                })
-                .unwrap_or_else(|| vec![default_normal; vertex_count]);
-
-            if normals.len() != vertex_count {
-                normals.resize(vertex_count, default_normal);
-            }
-
-            let mut uvs: Vec<[f32; 2]> = reader
-                .read_tex_coords(0)
-                .map(convert_tex_coords)
-                .unwrap_or_else(|| vec![[0.0, 0.0]; vertex_count]);
+            let mut uvs: Vec<[f32; 2]> = match &decoded {
+                Some(decoded) if !decoded.uvs.is_empty() => decoded
+                    .uvs
+                    .chunks_exact(2)
+                    .map(|uv| [uv[0], uv[1]])
+                    .collect(),
+                _ => reader
+                    .read_tex_coords(0)
+                    .map(convert_tex_coords)
+                    .unwrap_or_else(|| vec![[0.0, 0.0]; vertex_count]),
+            };
 
             if uvs.len() != vertex_count {
                 uvs.resize(vertex_count, [0.0, 0.0]);
             }
 
-            for position in &positions {
-                let vec = Vec3::new(position[0], position[1], position[2]);
-                let transformed = world_transform.transform_point3(vec);
-                let world_point = [transformed.x, transformed.y, transformed.z];
-                if let Some(bounds) = model_bounds.as_mut() {
-                    bounds.include_point(world_point);
-                } else {
-                    *model_bounds = Some(ModelBounds::new(world_point, world_point));
-                }
+            let mut colors: Vec<[f32; 4]> = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgba_f32().collect())
+                .unwrap_or_else(|| vec![[1.0; 4]; vertex_count]);
+
+            if colors.len() != vertex_count {
+                colors.resize(vertex_count, [1.0; 4]);
             }
 
-            let indices: Vec<u32> = reader
-                .read_indices()
-                .map(convert_indices)
-                .unwrap_or_else(|| (0..vertex_count as u32).collect());
+            let world_positions: Vec<[f32; 3]> = positions
+                .iter()
+                .map(|position| {
+                    let vec = Vec3::new(position[0], position[1], position[2]);
+                    let transformed = world_transform.transform_point3(vec);
+                    [transformed.x, transformed.y, transformed.z]
+                })
+                .collect();
+            let bounds = ModelBounds::from_points(world_positions.iter().copied())
+                .expect("primitives without positions were skipped");
+            model.bounds = Some(match model.bounds {
+                Some(model_bounds) => model_bounds.union(&bounds),
+                None => bounds,
+            });
+
+            let indices = match &decoded {
+                Some(decoded) => Some(decoded.indices.clone()),
+                None => reader.read_indices().map(convert_indices),
+            };
+            if let Some(indices) = &indices {
+                validate_indices(indices, vertex_count)?;
+            }
+            let (mode, indices) = list_indices(primitive.mode(), indices, vertex_count as u32);
 
-            if indices.is_empty() {
+            if indices.as_ref().is_some_and(Vec::is_empty) {
                 continue;
             }
 
-            let mesh = MeshBuilder::default()
-                .with_vertices(device, resources, &positions, &normals, &uvs)
-                .with_indices(device, resources, &indices)
-                .with_pipeline(pipeline_index)
-                .with_model_matrix(device, resources, world_transform)
-                .build();
+            let to_world = |normal: [f32; 3]| {
+                let transformed = normal_matrix
+                    .transform_vec3(Vec3::from(normal))
+                    .normalized();
+                [transformed.x, transformed.y, transformed.z]
+            };
+            let read_normals = match &decoded {
+                Some(decoded) if !decoded.normals.is_empty() => Some(vec3s(&decoded.normals)),
+                Some(_) => None,
+                None => reader.read_normals().map(Iterator::collect),
+            };
+            let mut normals: Vec<[f32; 3]> = match read_normals {
+                Some(normals) => normals.into_iter().map(to_world).collect(),
+                None => generate_normals(&positions, indices.as_deref(), mode)
+                    .into_iter()
+                    .map(to_world)
+                    .collect(),
+            };
+
+            if normals.len() != vertex_count {
+                normals.resize(vertex_count, to_world([0.0, 1.0, 0.0]));
+            }
+
+            let tangents: Vec<[f32; 4]> = match (mode, reader.read_tangents()) {
+                (Mode::Points, _) => Vec::new(),
+                (_, Some(iter)) if iter.len() == vertex_count => iter
+                    .map(|tangent| {
+                        let vec = Vec3::new(tangent[0], tangent[1], tangent[2]);
+                        let transformed = world_transform.transform_vec3(vec).normalized();
+                        [transformed.x, transformed.y, transformed.z, tangent[3]]
+                    })
+                    .collect(),
+                _ => generate_tangents(&world_positions, &normals, &uvs, indices.as_deref(), mode),
+            };
 
-            meshes.push(mesh);
+            model.primitives.push(PrimitiveData {
+                mesh_name: mesh.name().map(str::to_owned),
+                node_name: node.name().map(str::to_owned),
+                primitive_index: primitive.index(),
+                positions: positions.into_flattened(),
+                normals: normals.into_flattened(),
+                uvs: uvs.into_flattened(),
+                tangents: tangents.into_flattened(),
+                colors: colors.into_flattened(),
+                indices,
+                transform: world_transform,
+                bounds,
+                mode,
+                material: primitive.material().index(),
+                node: node_index,
+            });
         }
     }
 
     for child in node.children() {
         visit_node(
             child,
+            Some(node_index),
             world_transform,
+            document,
+            buffers,
+            draco,
+            model,
+        )?;
+    }
+    Ok(())
+}
+
+/// Triples of a flat array of three floats per vertex.
+fn vec3s(values: &[f32]) -> Vec<[f32; 3]> {
+    values
+        .chunks_exact(3)
+        .map(|value| [value[0], value[1], value[2]])
+        .collect()
+}
+
+/// Decompress a primitive from its `KHR_draco_mesh_compression` extension object.
+fn decode_draco(
+    extension: &gltf::json::Value,
+    document: &gltf::Document,
+    buffers: &[Option<&[u8]>],
+    draco: &dyn DracoDecoder,
+) -> Result<DracoMesh, ImportError> {
+    let invalid = || ImportError::Draco("invalid extension object".to_owned());
+    let view = extension
+        .get("bufferView")
+        .and_then(gltf::json::Value::as_u64)
+        .and_then(|index| document.views().nth(index as usize))
+        .ok_or_else(invalid)?;
+    let attributes = extension
+        .get("attributes")
+        .and_then(gltf::json::Value::as_object)
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|(name, id)| Some((name.as_str(), u32::try_from(id.as_u64()?).ok()?)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+
+    let data = buffers
+        .get(view.buffer().index())
+        .copied()
+        .flatten()
+        .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()))
+        .ok_or_else(|| {
+            ImportError::Draco(format!("buffer view {} is not available", view.index()))
+        })?;
+
+    let mesh = draco
+        .decode(data, &attributes)
+        .map_err(ImportError::Draco)?;
+    if mesh.positions.is_empty() || mesh.positions.len() % 3 != 0 {
+        return Err(ImportError::Draco("no positions were decoded".to_owned()));
+    }
+    Ok(mesh)
+}
+
+/// Extract geometry and bounds from a `.glb`/`.gltf` file.
+///
+/// This is the CPU-heavy part of an import and touches no GPU state, so it can run on
+/// the decode worker. Primitives in buffers stored in separate files are skipped, use
+/// `parse_gltf_with_buffers` to provide them.
+pub fn parse_gltf(bytes: &[u8]) -> Result<ParsedModel, ImportError> {
+    parse_gltf_with_buffers(bytes, &ExternalBuffers::new())
+}
+
+/// Like `parse_gltf`, reading buffers stored in separate files from `external`.
+pub fn parse_gltf_with_buffers(
+    bytes: &[u8],
+    external: &ExternalBuffers,
+) -> Result<ParsedModel, ImportError> {
+    parse(bytes, external, None)
+}
+
+/// Like `parse_gltf_with_buffers`, decompressing Draco primitives with `draco`.
+pub fn parse_gltf_with_draco(
+    bytes: &[u8],
+    external: &ExternalBuffers,
+    draco: &dyn DracoDecoder,
+) -> Result<ParsedModel, ImportError> {
+    parse(bytes, external, Some(draco))
+}
+
+fn parse(
+    bytes: &[u8],
+    external: &ExternalBuffers,
+    draco: Option<&dyn DracoDecoder>,
+) -> Result<ParsedModel, ImportError> {
+    let gltf = open_gltf(bytes, draco.is_some())?;
+    let resolved = resolve_buffers(&gltf.document, gltf.blob.as_deref(), external)?;
+    let buffers: Vec<Option<&[u8]>> = resolved.iter().map(Option::as_deref).collect();
+
+    let mut model = ParsedModel::default();
+
+    for scene in gltf.scenes() {
+        for node in scene.nodes() {
+            visit_node(
+                node,
+                None,
+                Mat4::identity(),
+                &gltf.document,
+                &buffers,
+                draco,
+                &mut model,
+            )?;
+        }
+    }
+
+    model.materials = gltf.materials().map(material_data).collect();
+    model.images = gltf
+        .images()
+        .map(|image| {
+            let sampled = model
+                .materials
+                .iter()
+                .any(|material| material.base_color_texture == Some(image.index()));
+            sampled.then(|| decode_image(image, &buffers)).flatten()
+        })
+        .collect();
+
+    Ok(model)
+}
+
+/// Parse and validate the glTF document.
+///
+/// The `gltf` crate rejects files using Draco, which it cannot decode. Such files are
+/// accepted when Draco is all that fails validation, except that requiring Draco
+/// without a decoder fails with `ImportError::UnsupportedExtension`.
+fn open_gltf(bytes: &[u8], decodes_draco: bool) -> Result<Gltf, ImportError> {
+    let err = match Gltf::from_slice(bytes) {
+        Ok(gltf) => return Ok(gltf),
+        Err(err) => err,
+    };
+    let gltf::Error::Validation(errors) = &err else {
+        return Err(err.into());
+    };
+    // The crate does not know the extension, and it rejects the Draco accessors that
+    // have no buffer view, so a file failing only on those is still usable.
+    let Some(gltf) = Gltf::from_slice_without_validation(bytes)
+        .ok()
+        .filter(|gltf| {
+            gltf.extensions_used()
+                .chain(gltf.extensions_required())
+                .any(|name| name == DRACO_EXTENSION)
+        })
+        .filter(|_| {
+            errors
+                .iter()
+                .all(|(path, error)| is_draco_validation_error(path.as_str(), error))
+        })
+    else {
+        return Err(err.into());
+    };
+    if !decodes_draco
+        && gltf
+            .extensions_required()
+            .any(|name| name == DRACO_EXTENSION)
+    {
+        return Err(ImportError::UnsupportedExtension(DRACO_EXTENSION));
+    }
+    Ok(gltf)
+}
+
+fn is_draco_validation_error(path: &str, error: &gltf::json::validation::Error) -> bool {
+    use gltf::json::validation::Error;
+
+    match error {
+        Error::Unsupported => {
+            path.starts_with("extensionsRequired[") && path.contains(DRACO_EXTENSION)
+        }
+        Error::Missing => path.starts_with("accessors[") && path.ends_with("].bufferView"),
+        _ => false,
+    }
+}
+
+/// Position, normal, uv, tangent and color, the attributes `gltf.wgsl` reads. The
+/// instance matrix follows at locations 5 to 8.
+pub fn gltf_vertex_layout() -> VertexLayout {
+    VertexLayoutBuilder::new()
+        .with(VertexAttribute::Position)
+        .with(VertexAttribute::Normal)
+        .with(VertexAttribute::Uv)
+        .with(VertexAttribute::Tangent)
+        .with(VertexAttribute::Color)
+        .build()
+}
+
+/// Position, normal, quad corner and color, the attributes `points.wgsl` reads.
+fn point_vertex_layout() -> VertexLayout {
+    VertexAttribute::STANDARD
+        .into_iter()
+        .fold(VertexLayoutBuilder::new(), VertexLayoutBuilder::with)
+        .with(VertexAttribute::Color)
+        .build()
+}
+
+/// Scene bind groups the glTF shaders read: the frame metadata and light, then the
+/// camera. Other groups a scene has are left unbound.
+const GLTF_BIND_GROUPS: &[usize] = &[0, 1];
+
+/// Get or create the pipeline `load_gltf_model` draws primitives with.
+///
+/// Meshes drawn with it need a material from `GpuResources::add_material`, and tangents.
+pub fn gltf_pipeline(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    surface_format: TextureFormat,
+) -> usize {
+    resources.get_or_create_pipeline_with_layout(
+        device,
+        "gltf_standard",
+        &gltf_vertex_layout(),
+        include_str!("./gltf.wgsl"),
+        surface_format,
+        PipelineOptions {
+            textured: true,
+            bind_groups: Some(GLTF_BIND_GROUPS),
+            world_space_normals: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Get or create the pipeline drawing point cloud primitives as round screen-space
+/// points, sized by `Scene::set_point_size`.
+pub fn point_cloud_pipeline(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    surface_format: TextureFormat,
+) -> usize {
+    // Quads always face the camera and their anti-aliased edges are blended.
+    resources.get_or_create_pipeline_with_layout(
+        device,
+        "gltf_points",
+        &point_vertex_layout(),
+        include_str!("./points.wgsl"),
+        surface_format,
+        PipelineOptions {
+            cull_mode: None,
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            bind_groups: Some(GLTF_BIND_GROUPS),
+            world_space_normals: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Get or create the pipeline drawing line primitives, one pixel wide and shaded like
+/// triangles.
+pub fn line_pipeline(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    surface_format: TextureFormat,
+) -> usize {
+    resources.get_or_create_pipeline_with_layout(
+        device,
+        "gltf_lines",
+        &gltf_vertex_layout(),
+        include_str!("./gltf.wgsl"),
+        surface_format,
+        PipelineOptions {
+            topology: wgpu::PrimitiveTopology::LineList,
+            cull_mode: None,
+            textured: true,
+            bind_groups: Some(GLTF_BIND_GROUPS),
+            world_space_normals: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Get or create the pipeline drawing primitives of `KHR_materials_unlit` materials,
+/// with their base color and no lighting. Lines get their own one.
+pub fn unlit_pipeline(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    surface_format: TextureFormat,
+    topology: wgpu::PrimitiveTopology,
+) -> usize {
+    let (name, cull_mode) = match topology {
+        wgpu::PrimitiveTopology::LineList => ("gltf_lines_unlit", None),
+        _ => ("gltf_unlit", Some(wgpu::Face::Back)),
+    };
+
+    resources.get_or_create_pipeline_with_layout(
+        device,
+        name,
+        &gltf_vertex_layout(),
+        include_str!("./gltf.wgsl"),
+        surface_format,
+        PipelineOptions {
+            topology,
+            cull_mode,
+            textured: true,
+            bind_groups: Some(GLTF_BIND_GROUPS),
+            world_space_normals: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Pipeline suited to the primitive's mode, and its material being `unlit`.
+pub fn primitive_pipeline(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    surface_format: TextureFormat,
+    primitive: &PrimitiveData,
+    unlit: bool,
+) -> usize {
+    match (primitive.mode, unlit) {
+        (Mode::Points, _) => point_cloud_pipeline(device, resources, surface_format),
+        (Mode::Lines, true) => unlit_pipeline(
             device,
             resources,
-            meshes,
-            data_blob,
-            pipeline_index,
-            model_bounds,
+            surface_format,
+            wgpu::PrimitiveTopology::LineList,
+        ),
+        (Mode::Lines, false) => line_pipeline(device, resources, surface_format),
+        (_, true) => unlit_pipeline(
+            device,
+            resources,
+            surface_format,
+            wgpu::PrimitiveTopology::TriangleList,
+        ),
+        (_, false) => gltf_pipeline(device, resources, surface_format),
+    }
+}
+
+/// Expand every point into a quad with its corner, in `[-1, 1]`, stored as the uv so
+/// the points shader can push it out to the point size in screen space.
+fn expand_points(primitive: &PrimitiveData) -> PrimitiveData {
+    let vertex_count = (primitive.positions.len() / 3) as u32;
+    let points: Vec<u32> = match &primitive.indices {
+        Some(indices) => indices.clone(),
+        None => (0..vertex_count).collect(),
+    };
+
+    let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+    let mut positions = Vec::with_capacity(points.len() * 12);
+    let mut normals = Vec::with_capacity(points.len() * 12);
+    let mut uvs = Vec::with_capacity(points.len() * 8);
+    let mut colors = Vec::with_capacity(points.len() * 16);
+    let mut indices = Vec::with_capacity(points.len() * 6);
+
+    for (i, point) in points.iter().enumerate() {
+        let point = *point as usize;
+        for corner in corners {
+            positions.extend_from_slice(&primitive.positions[point * 3..point * 3 + 3]);
+            normals.extend_from_slice(&primitive.normals[point * 3..point * 3 + 3]);
+            uvs.extend_from_slice(&corner);
+            colors.extend_from_slice(&primitive.colors[point * 4..point * 4 + 4]);
+        }
+
+        let base = i as u32 * 4;
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    PrimitiveData {
+        mesh_name: primitive.mesh_name.clone(),
+        node_name: primitive.node_name.clone(),
+        primitive_index: primitive.primitive_index,
+        positions,
+        normals,
+        uvs,
+        tangents: Vec::new(),
+        colors,
+        indices: Some(indices),
+        transform: primitive.transform,
+        bounds: primitive.bounds,
+        mode: Mode::Triangles,
+        material: primitive.material,
+        node: primitive.node,
+    }
+}
+
+/// Create the GPU buffers for one primitive, drawn with `pipeline_index`.
+///
+/// Point primitives are expanded into quads, they need a pipeline from
+/// `point_cloud_pipeline`.
+pub fn upload_primitive(
+    device: &wgpu::Device,
+    resources: &mut crate::renderer::GpuResources,
+    primitive: &PrimitiveData,
+    pipeline_index: usize,
+) -> Result<Mesh, MeshBuildError> {
+    let expanded;
+    let primitive = if primitive.mode == Mode::Points {
+        expanded = expand_points(primitive);
+        &expanded
+    } else {
+        primitive
+    };
+
+    let mut builder = MeshBuilder::default().with_vertices(
+        device,
+        resources,
+        bytemuck::cast_slice(&primitive.positions),
+        bytemuck::cast_slice(&primitive.normals),
+        bytemuck::cast_slice(&primitive.uvs),
+    )?;
+    if !primitive.tangents.is_empty() {
+        builder = builder.with_attribute(
+            device,
+            resources,
+            VertexAttribute::Tangent,
+            bytemuck::cast_slice(&primitive.tangents),
+        )?;
+    }
+    let builder = builder.with_attribute(
+        device,
+        resources,
+        VertexAttribute::Color,
+        bytemuck::cast_slice(&primitive.colors),
+    )?;
+    let builder = builder.with_pipeline(pipeline_index).with_model_matrix(
+        device,
+        resources,
+        primitive.transform,
+    );
+
+    // Most primitives index fewer than 65536 vertices, which fit in half the memory.
+    let narrow = |indices: &[u32]| -> Option<Vec<u16>> {
+        indices.iter().map(|&i| u16::try_from(i).ok()).collect()
+    };
+
+    let mut mesh = match &primitive.indices {
+        Some(indices) => match narrow(indices) {
+            Some(indices) => builder
+                .with_indices_u16(device, resources, &indices)
+                .build(),
+            None => builder.with_indices(device, resources, indices).build(),
+        },
+        None => builder.build(),
+    };
+    mesh.bounds = Some(primitive.bounds);
+    // Nodes name what is in the scene, several nodes may share a named mesh.
+    mesh.name = primitive
+        .node_name
+        .clone()
+        .or_else(|| primitive.mesh_name.clone());
+
+    Ok(mesh)
+}
+
+/// Hand every primitive of a parsed model to `visitor`, keeping the meshes it returns.
+///
+/// Returning `None` skips the primitive, e.g. for collision meshes.
+pub fn visit_model(
+    model: ParsedModel,
+    visitor: &mut impl FnMut(PrimitiveData) -> Option<Mesh>,
+) -> Vec<Mesh> {
+    model.primitives.into_iter().filter_map(visitor).collect()
+}
+
+/// Parse a glTF file and let `visitor` decide which mesh, if any, each primitive becomes.
+///
+/// The bounds cover every primitive in the file, including skipped ones.
+pub fn visit_gltf(
+    bytes: &[u8],
+    visitor: &mut impl FnMut(PrimitiveData) -> Option<Mesh>,
+) -> Result<(Vec<Mesh>, Option<ModelBounds>), ImportError> {
+    let model = parse_gltf(bytes)?;
+    let bounds = model.bounds;
+
+    Ok((visit_model(model, visitor), bounds))
+}
+
+/// Materials and base color textures uploaded for one model by `upload_materials`.
+#[derive(Debug, Default)]
+pub struct ModelMaterials {
+    params: Vec<usize>,
+    textures: Vec<TextureIndex>,
+}
+
+impl ModelMaterials {
+    /// Params index of each glTF material followed by the default material, see
+    /// `GpuResources::add_material`.
+    pub fn params(&self) -> &[usize] {
+        &self.params
+    }
+
+    /// Free the materials and textures, once the meshes of the model are gone.
+    pub fn remove(self, resources: &mut crate::renderer::GpuResources) {
+        for index in self.params {
+            resources.remove_object_params(index);
+        }
+        for texture in self.textures {
+            resources.free_texture(texture);
+        }
+    }
+}
+
+/// Upload the base color textures of a model and create a material for each glTF
+/// material, followed by the default material.
+pub fn upload_materials(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    resources: &mut crate::renderer::GpuResources,
+    model: &ParsedModel,
+) -> ModelMaterials {
+    let mut textures: HashMap<usize, TextureIndex> = HashMap::new();
+    let mut materials = Vec::with_capacity(model.materials.len() + 1);
+
+    for material in &model.materials {
+        let texture = material.base_color_texture.and_then(|image| {
+            if let Some(texture) = textures.get(&image) {
+                return Some(*texture);
+            }
+            let data = model.images.get(image)?.as_ref()?;
+            let texture =
+                resources.upload_texture_2d(device, queue, &data.rgba, data.width, data.height);
+            textures.insert(image, texture);
+            Some(texture)
+        });
+
+        let params = ObjectParams::with_color(material.base_color_factor);
+        materials.push(resources.add_material(device, queue, params, texture.as_ref()));
+    }
+
+    materials.push(resources.add_material(device, queue, ObjectParams::default(), None));
+    ModelMaterials {
+        params: materials,
+        textures: textures.into_values().collect(),
+    }
+}
+
+/// Give a mesh its primitive's material, from `ModelMaterials::params`,
+/// and blend mode when its pipeline binds materials.
+fn assign_material(
+    resources: &crate::renderer::GpuResources,
+    mesh: &mut Mesh,
+    primitive: &PrimitiveData,
+    materials: &[usize],
+    blend_modes: &[BlendMode],
+) {
+    if !resources.pipeline_options(mesh.pipeline_index).textured {
+        return;
+    }
+
+    let material = primitive
+        .material
+        .and_then(|material| materials.get(material))
+        .or(materials.last());
+    mesh.params_index = material.copied();
+    mesh.blend_mode = primitive
+        .material
+        .and_then(|material| blend_modes.get(material).copied())
+        .unwrap_or_default();
+}
+
+fn blend_modes(model: &ParsedModel) -> Vec<BlendMode> {
+    model
+        .materials
+        .iter()
+        .map(|material| material.blend_mode)
+        .collect()
+}
+
+/// Uploads the primitives of a model one at a time once its materials are on the GPU,
+/// so a large model can be spread over several frames.
+pub struct ModelUploader {
+    materials: ModelMaterials,
+    blend_modes: Vec<BlendMode>,
+    // Whether each glTF material is unlit, picking the pipeline of its primitives
+    unlit: Vec<bool>,
+    surface_format: TextureFormat,
+}
+
+impl ModelUploader {
+    /// Upload the materials of `model`, see `upload_materials`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resources: &mut crate::renderer::GpuResources,
+        model: &ParsedModel,
+        surface_format: TextureFormat,
+    ) -> Self {
+        Self {
+            materials: upload_materials(device, queue, resources, model),
+            blend_modes: blend_modes(model),
+            unlit: model
+                .materials
+                .iter()
+                .map(|material| material.unlit)
+                .collect(),
+            surface_format,
+        }
+    }
+
+    /// Create the buffers and mesh of one primitive of the model.
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        resources: &mut crate::renderer::GpuResources,
+        primitive: &PrimitiveData,
+    ) -> Result<Mesh, MeshBuildError> {
+        let unlit = primitive
+            .material
+            .and_then(|material| self.unlit.get(material).copied())
+            .unwrap_or(false);
+        let pipeline_index =
+            primitive_pipeline(device, resources, self.surface_format, primitive, unlit);
+        let mut mesh = upload_primitive(device, resources, primitive, pipeline_index)?;
+        assign_material(
+            resources,
+            &mut mesh,
+            primitive,
+            self.materials.params(),
+            &self.blend_modes,
         );
+        Ok(mesh)
+    }
+
+    /// Materials of the model, to free with `ModelMaterials::remove` once its meshes
+    /// are gone.
+    pub fn into_materials(self) -> ModelMaterials {
+        self.materials
     }
 }
 
-pub async fn load_gltf_model(
+/// Reports how far loading a model got as a single fraction from 0 to 1, e.g. for a
+/// loading bar.
+///
+/// Downloading fills the first `DOWNLOAD_SHARE` by bytes received, uploading the rest
+/// by primitives uploaded.
+pub struct LoadProgress<'a> {
+    on_progress: Box<dyn FnMut(f32) + 'a>,
+}
+
+impl<'a> LoadProgress<'a> {
+    pub const DOWNLOAD_SHARE: f32 = 0.5;
+
+    pub fn new(on_progress: impl FnMut(f32) + 'a) -> Self {
+        Self {
+            on_progress: Box::new(on_progress),
+        }
+    }
+
+    /// Progress that goes unreported.
+    pub fn ignored() -> Self {
+        Self::new(|_| {})
+    }
+
+    pub fn downloaded(&mut self, received: usize, total: usize) {
+        (self.on_progress)(Self::DOWNLOAD_SHARE * done_fraction(received, total));
+    }
+
+    pub fn uploaded(&mut self, primitives: usize, total: usize) {
+        let fraction = done_fraction(primitives, total);
+        (self.on_progress)(Self::DOWNLOAD_SHARE + (1.0 - Self::DOWNLOAD_SHARE) * fraction);
+    }
+}
+
+fn done_fraction(done: usize, total: usize) -> f32 {
+    if total == 0 {
+        return 1.0;
+    }
+    (done as f32 / total as f32).min(1.0)
+}
+
+/// Create the GPU buffers and materials for a parsed model and append one mesh per
+/// primitive. Returns the materials, to free once the meshes are gone.
+pub fn upload_model(
     device: &wgpu::Device,
+    queue: &wgpu::Queue,
     resources: &mut crate::renderer::GpuResources,
-    meshes: &mut Vec<crate::renderer::scene::Mesh>,
+    meshes: &mut Vec<Mesh>,
+    model: &ParsedModel,
     surface_format: TextureFormat,
-) -> Result<Option<ModelBounds>, ImportError> {
-    let glb_data = reqwest::get("http://localhost:8080/themanor.glb")
-        .await?
-        .bytes()
-        .await?;
+) -> Result<ModelMaterials, MeshBuildError> {
+    let uploader = ModelUploader::new(device, queue, resources, model, surface_format);
+    for primitive in &model.primitives {
+        meshes.push(uploader.upload(device, resources, primitive)?);
+    }
+
+    Ok(uploader.into_materials())
+}
 
-    let model = Gltf::from_slice(&glb_data)?;
-    let data_blob = model.blob.as_ref().ok_or(ImportError::LoadError)?;
+/// What `load_gltf_model` and `load_gltf_from_bytes` loaded besides the meshes.
+#[derive(Debug)]
+pub struct LoadedModel {
+    /// Bounds to frame the camera on, `None` when the model has no positions.
+    pub bounds: Option<ModelBounds>,
+    /// Materials of the appended meshes, to free with `ModelMaterials::remove` once the
+    /// meshes are gone.
+    pub materials: ModelMaterials,
+}
+
+/// `GET` `url`, failing with `ImportError::HttpStatus` unless the response is a success.
+async fn get(url: impl reqwest::IntoUrl) -> Result<reqwest::Response, ImportError> {
+    let response = reqwest::get(url).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ImportError::HttpStatus {
+            status: status.as_u16(),
+            url: response.url().to_string(),
+        });
+    }
+    Ok(response)
+}
+
+pub async fn fetch_model_bytes() -> Result<Vec<u8>, ImportError> {
+    let bytes = get(DEFAULT_MODEL_URL).await?.bytes().await?;
+    Ok(bytes.to_vec())
+}
 
-    let vertex_layout = mesh_vertex_layout();
+/// Fetch the buffers of the `.gltf` in `bytes` that are stored in separate files, e.g.
+/// the `model.bin` next to a `model.gltf` exported by Blender.
+///
+/// Relative URIs are resolved against `model_url`, the URL `bytes` were fetched from.
+/// `.glb` files and data URIs need nothing fetched and give an empty map.
+///
+/// Download progress counts `bytes` as received and is reported after each buffer.
+pub async fn fetch_external_buffers(
+    bytes: &[u8],
+    model_url: &str,
+    progress: &mut LoadProgress<'_>,
+) -> Result<ExternalBuffers, ImportError> {
+    let gltf = Gltf::from_slice_without_validation(bytes)?;
+    let mut external = ExternalBuffers::new();
 
-    let pipeline_index = resources.get_or_create_pipeline(
+    let external_buffers: Vec<_> = gltf
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => Some((buffer, uri)),
+            _ => None,
+        })
+        .collect();
+    let mut received = bytes.len();
+    let total = received
+        + external_buffers
+            .iter()
+            .map(|(buffer, _)| buffer.length())
+            .sum::<usize>();
+    progress.downloaded(received, total);
+
+    for (buffer, uri) in external_buffers {
+        let index = buffer.index();
+        let url = reqwest::Url::parse(model_url)
+            .and_then(|base| base.join(uri))
+            .map_err(|_| ImportError::InvalidBufferUri {
+                index,
+                uri: uri.to_owned(),
+            })?;
+
+        let data = get(url).await?.bytes().await?;
+        if data.len() < buffer.length() {
+            return Err(ImportError::TruncatedBuffer {
+                index,
+                expected: buffer.length(),
+                actual: data.len(),
+            });
+        }
+        received += buffer.length();
+        external.insert(index, data.to_vec());
+        progress.downloaded(received, total);
+    }
+
+    Ok(external)
+}
+
+/// Fetch a `.glb`/`.gltf` file along with the buffers it keeps in separate files.
+pub async fn fetch_model(
+    url: &str,
+    progress: &mut LoadProgress<'_>,
+) -> Result<(Vec<u8>, ExternalBuffers), ImportError> {
+    progress.downloaded(0, 1);
+    let bytes = get(url).await?.bytes().await?.to_vec();
+    let external = fetch_external_buffers(&bytes, url, progress).await?;
+    Ok((bytes, external))
+}
+
+/// Fetch the default model and append one mesh per primitive, see `load_gltf_from_bytes`.
+pub async fn load_gltf_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    resources: &mut crate::renderer::GpuResources,
+    meshes: &mut Vec<Mesh>,
+    surface_format: TextureFormat,
+    progress: &mut LoadProgress<'_>,
+) -> Result<LoadedModel, ImportError> {
+    let (bytes, external) = fetch_model(DEFAULT_MODEL_URL, progress).await?;
+    let model = parse_gltf_with_buffers(&bytes, &external)?;
+    Ok(upload_skipping_failures(
         device,
-        "gltf_standard",
-        &vertex_layout,
-        include_str!("./gltf.wgsl"),
+        queue,
+        resources,
+        meshes,
         surface_format,
-    );
+        model,
+        progress,
+    ))
+}
 
-    let mut model_bounds: Option<ModelBounds> = None;
+/// Parse a `.glb`/`.gltf` file already in memory and append one mesh per primitive,
+/// returning the bounds to frame the camera on and the materials.
+///
+/// Primitives that fail to upload are logged and skipped.
+pub fn load_gltf_from_bytes(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    resources: &mut crate::renderer::GpuResources,
+    meshes: &mut Vec<Mesh>,
+    surface_format: TextureFormat,
+    glb_data: &[u8],
+) -> Result<LoadedModel, ImportError> {
+    let model = parse_gltf(glb_data)?;
+    Ok(upload_skipping_failures(
+        device,
+        queue,
+        resources,
+        meshes,
+        surface_format,
+        model,
+        &mut LoadProgress::ignored(),
+    ))
+}
 
-    for scene in model.scenes() {
-        for node in scene.nodes() {
-            visit_node(
-                node,
-                Mat4::identity(),
-                device,
-                resources,
-                meshes,
-                data_blob,
-                pipeline_index,
-                &mut model_bounds,
-            );
+/// Append one mesh per primitive of `model`, logging and skipping primitives that fail
+/// to upload.
+fn upload_skipping_failures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    resources: &mut crate::renderer::GpuResources,
+    meshes: &mut Vec<Mesh>,
+    surface_format: TextureFormat,
+    model: ParsedModel,
+    progress: &mut LoadProgress<'_>,
+) -> LoadedModel {
+    let bounds = model.bounds;
+    let uploader = ModelUploader::new(device, queue, resources, &model, surface_format);
+    let total = model.primitives.len();
+    let mut uploaded = 0;
+    meshes.extend(visit_model(model, &mut |primitive| {
+        let mesh = uploader.upload(device, resources, &primitive);
+        uploaded += 1;
+        progress.uploaded(uploaded, total);
+        mesh.map_err(|err| log::error!("Skipping glTF primitive: {}", err))
+            .ok()
+    }));
+
+    LoadedModel {
+        bounds,
+        materials: uploader.into_materials(),
+    }
+}
+
+/// A glTF parse job sent to the decode worker.
+pub struct DecodeRequest {
+    bytes: Vec<u8>,
+    external: ExternalBuffers,
+    reply: oneshot::Sender<Result<ParsedModel, ImportError>>,
+}
+
+/// Handle used by the render worker to hand glTF parsing off to the decode worker.
+#[derive(Clone, Debug)]
+pub struct ModelDecoder {
+    requests: Sender<DecodeRequest>,
+}
+
+impl ModelDecoder {
+    /// Create a decoder and the receiving end to pass to `run_decode_loop`.
+    pub fn channel() -> (Self, Receiver<DecodeRequest>) {
+        let (requests, receiver) = mpsc::channel();
+        (Self { requests }, receiver)
+    }
+
+    /// Parse `bytes` on the decode worker, see `parse_gltf_with_buffers`.
+    ///
+    /// Falls back to parsing on the calling thread when the decode worker is gone.
+    pub async fn decode(
+        &self,
+        bytes: Vec<u8>,
+        external: ExternalBuffers,
+    ) -> Result<ParsedModel, ImportError> {
+        let (reply, response) = oneshot::channel();
+        let request = DecodeRequest {
+            bytes,
+            external,
+            reply,
+        };
+
+        if let Err(mpsc::SendError(request)) = self.requests.send(request) {
+            log::warn!("Decode worker is not running, parsing on the current thread");
+            return parse_gltf_with_buffers(&request.bytes, &request.external);
         }
+
+        response.await.map_err(|_| ImportError::DecoderGone)?
     }
+}
 
-    Ok(model_bounds)
+/// Serve decode requests until every `ModelDecoder` is dropped, decompressing Draco
+/// primitives with `draco` when the worker has a decoder.
+///
+/// Blocks the calling thread, so it must run on a dedicated worker.
+pub fn run_decode_loop(requests: Receiver<DecodeRequest>, draco: Option<&dyn DracoDecoder>) {
+    while let Ok(DecodeRequest {
+        bytes,
+        external,
+        reply,
+    }) = requests.recv()
+    {
+        // The requester may have given up on the load, nothing to do then.
+        let _ = reply.send(parse(&bytes, &external, draco));
+    }
 }
+
+#[cfg(test)]
+#[path = "gltf_tests.rs"]
+pub(crate) mod tests;
@@ -3,27 +3,55 @@ struct UniformData {
     mouse_click: vec2<f32>,
     resolution: vec2<f32>,
     time: f32,
-    _padding0: f32,
+    point_size: f32,
     camera_position: vec4<f32>,
+    user_params: vec4<f32>,
 }
 
 @group(0) @binding(0) var<uniform> uni: UniformData;
+
+struct Light {
+    // Direction the light travels in
+    direction: vec4<f32>,
+    color: vec4<f32>,
+}
+
+@group(0) @binding(1) var<uniform> light: Light;
 @group(1) @binding(0) var<uniform> view_proj: mat4x4<f32>;
 
+struct ObjectParams {
+    color: vec4<f32>,
+    params: vec4<f32>,
+}
+
+// Object params follow the frame and camera groups, see `GLTF_BIND_GROUPS`.
+@group(2) @binding(0) var<uniform> object_params: ObjectParams;
+// Material texture, white for materials without one.
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+
 struct VertexInput {
     @location(0) pos: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
-    @location(3) model_col0: vec4<f32>,
-    @location(4) model_col1: vec4<f32>,
-    @location(5) model_col2: vec4<f32>,
-    @location(6) model_col3: vec4<f32>,
+    // World-space like the normal, with the bitangent sign in w.
+    @location(3) tangent: vec4<f32>,
+    // Linear vertex color, white when the primitive has none.
+    @location(4) color: vec4<f32>,
+    @location(5) model_col0: vec4<f32>,
+    @location(6) model_col1: vec4<f32>,
+    @location(7) model_col2: vec4<f32>,
+    @location(8) model_col3: vec4<f32>,
 }
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // Invariant so the depth prepass and the color pass compute identical depths.
+    @invariant @builtin(position) clip_position: vec4<f32>,
     @location(0) world_pos: vec3<f32>,
-    @location(1) normal: vec3<f32>
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) color: vec4<f32>,
 }
 
 
@@ -39,16 +67,23 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     let world_position = model * vec4<f32>(in.pos, 1.0);
     out.clip_position = view_proj * world_position;
     out.world_pos = world_position.xyz;
+    // Normals are imported in world space, already transformed by the inverse transpose
+    // of the node transform. The model matrix would skew them under non-uniform scale.
     out.normal = normalize(in.normal);
+    out.uv = in.uv;
+    out.tangent = vec4<f32>(normalize(in.tangent.xyz), in.tangent.w);
+    out.color = in.color;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let light_direction = normalize(vec3<f32>(0.35, 1.0, 0.45));
-    let light_color = vec3<f32>(1.0, 0.95, 0.85);
-    let base_color = vec3<f32>(0.2, 0.2, 0.2);
+    let light_direction = -light.direction.xyz;
+    let light_color = light.color.rgb;
+    let texel = textureSample(base_color_texture, base_color_sampler, in.uv);
+    let base_color = object_params.color.rgb * texel.rgb * in.color.rgb;
 
+    // Interpolation shortens normals between vertices, renormalize per fragment.
     let normal = normalize(in.normal);
     let view_dir = normalize(uni.camera_position.xyz - in.world_pos);
 
@@ -61,8 +96,17 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         specular = pow(max(dot(normal, halfway_dir), 0.0), 32.0);
     }
 
-    let lighting = min(base_color * (ambient + diffuse_strength) + light_color * specular, vec3<f32>(1.0));
+    let diffuse = light_color * diffuse_strength;
+    let lighting = min(base_color * (ambient + diffuse) + light_color * specular, vec3<f32>(1.0));
     let x = select(0.0, 0.3, distance(in.clip_position.xy, uni.mouse_move) < 25.0);
     let y = select(0.0, 0.3, distance(in.clip_position.xy, uni.mouse_click) < 25.0);
-    return vec4<f32>(lighting + x - y, 1.0);
+    // Opaque meshes replace the target color, the alpha only matters once blended.
+    return vec4<f32>(lighting + x - y, object_params.color.a * texel.a * in.color.a);
+}
+
+// Base color only, for `KHR_materials_unlit` materials.
+@fragment
+fn fs_unlit(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(base_color_texture, base_color_sampler, in.uv);
+    return object_params.color * texel * in.color;
 }
@@ -0,0 +1,645 @@
+use super::*;
+
+const EPSILON: f32 = 1e-4;
+
+fn assert_vec3_near(actual: Vec3, expected: Vec3) {
+    assert!(
+        (actual - expected).mag() < EPSILON,
+        "expected {:?}, got {:?}",
+        expected,
+        actual
+    );
+}
+
+/// A `.glb` with one triangle mesh used by two nodes, one of them nested under a
+/// translated parent:
+///
+/// - node 0 at (0, 2, 0) with child node 1 at (1, 0, 0), drawing the triangle
+/// - node 2 at (-3, 0, 0), drawing the triangle
+///
+/// The triangle spans (0, 0, 0), (1, 0, 0) and (0, 1, 0) in mesh space.
+pub(crate) fn two_triangle_glb() -> Vec<u8> {
+    let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    let indices: [u16; 3] = [0, 1, 2];
+
+    let mut bin: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&indices));
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0, 2] }}],
+            "nodes": [
+                {{ "translation": [0.0, 2.0, 0.0], "children": [1] }},
+                {{ "name": "child", "translation": [1.0, 0.0, 0.0], "mesh": 0 }},
+                {{ "translation": [-3.0, 0.0, 0.0], "mesh": 0 }}
+            ],
+            "meshes": [{{
+                "name": "triangle",
+                "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}]
+            }}],
+            "accessors": [
+                {{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+            ],
+            "buffers": [{{ "byteLength": {} }}]
+        }}"#,
+        bin.len()
+    );
+
+    glb(json, bin)
+}
+
+/// A `.glb` with one slanted triangle under a node scaled by (4, 1, 1).
+///
+/// The triangle lies in the plane x + y = 1 with its normal (1, 1, 0) / √2 stored per
+/// vertex, so only the inverse transpose of the scale keeps it perpendicular.
+fn scaled_slope_glb() -> Vec<u8> {
+    let positions: [f32; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0];
+    let normal = std::f32::consts::FRAC_1_SQRT_2;
+    let normals: [f32; 9] = [
+        normal, normal, 0.0, normal, normal, 0.0, normal, normal, 0.0,
+    ];
+
+    let mut bin: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&normals));
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "scale": [4.0, 1.0, 1.0], "mesh": 0 }}],
+            "meshes": [{{
+                "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }} }}]
+            }}],
+            "accessors": [
+                {{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 1.0]
+                }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }}
+            ],
+            "buffers": [{{ "byteLength": {} }}]
+        }}"#,
+        bin.len()
+    );
+
+    glb(json, bin)
+}
+
+/// A `.glb` with one triangle whose material samples a 2x1 PNG, red then blue, tinted
+/// by a half-transparent base color factor.
+pub(crate) fn textured_triangle_glb() -> Vec<u8> {
+    textured_triangle("")
+}
+
+/// `textured_triangle_glb` with a `KHR_materials_unlit` material.
+pub(crate) fn unlit_triangle_glb() -> Vec<u8> {
+    textured_triangle(r#""extensions": { "KHR_materials_unlit": {} },"#)
+}
+
+/// Textured triangle with `material_fields` added to its material.
+fn textured_triangle(material_fields: &str) -> Vec<u8> {
+    let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    let uvs: [f32; 6] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+
+    let pixels = image::RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+    let mut png = std::io::Cursor::new(Vec::new());
+    pixels.write_to(&mut png, image::ImageFormat::Png).unwrap();
+    let png = png.into_inner();
+
+    let mut bin: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+    bin.extend_from_slice(bytemuck::cast_slice(&uvs));
+    bin.extend_from_slice(&png);
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }},
+                    "material": 0
+                }}]
+            }}],
+            "materials": [{{
+                {}
+                "alphaMode": "BLEND",
+                "pbrMetallicRoughness": {{
+                    "baseColorFactor": [1.0, 1.0, 1.0, 0.5],
+                    "baseColorTexture": {{ "index": 0 }}
+                }}
+            }}],
+            "textures": [{{ "source": 0 }}],
+            "images": [{{ "bufferView": 2, "mimeType": "image/png" }}],
+            "accessors": [
+                {{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+                {{ "buffer": 0, "byteOffset": 60, "byteLength": {} }}
+            ],
+            "buffers": [{{ "byteLength": {} }}]
+        }}"#,
+        material_fields,
+        png.len(),
+        bin.len()
+    );
+
+    glb(json, bin)
+}
+
+/// Pack a glTF JSON document and its binary buffer into a `.glb` container.
+fn glb(json: String, bin: Vec<u8>) -> Vec<u8> {
+    let mut json = json.into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+
+    let total_length = 12 + 8 + json.len() + 8 + bin.len();
+
+    let mut glb = Vec::with_capacity(total_length);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"BIN\0");
+    glb.extend_from_slice(&bin);
+    glb
+}
+
+#[test]
+fn parse_applies_node_hierarchy_to_world_bounds() {
+    let model = parse_gltf(&two_triangle_glb()).unwrap();
+
+    assert_eq!(model.primitives.len(), 2);
+    assert_eq!(
+        model.bounds,
+        Some(ModelBounds::new([-3.0, 0.0, 0.0], [2.0, 3.0, 0.0]))
+    );
+
+    let nested = model.primitives[0].world_bounds().unwrap();
+    assert_eq!(nested, ModelBounds::new([1.0, 2.0, 0.0], [2.0, 3.0, 0.0]));
+    assert_eq!(model.primitives[0].bounds, nested);
+
+    let combined = model
+        .primitives
+        .iter()
+        .filter_map(PrimitiveData::world_bounds)
+        .reduce(|a, b| a.union(&b));
+    assert_eq!(combined, model.bounds);
+
+    // The hierarchy is kept too, parents first.
+    let parents: Vec<_> = model.nodes.iter().map(|node| node.parent).collect();
+    assert_eq!(parents, [None, Some(0), None]);
+    assert_eq!(model.primitives[0].node, 1);
+    assert_eq!(model.primitives[1].node, 2);
+}
+
+#[test]
+fn parse_fills_missing_attributes() {
+    let model = parse_gltf(&two_triangle_glb()).unwrap();
+    let primitive = &model.primitives[0];
+
+    assert_eq!(primitive.mesh_name.as_deref(), Some("triangle"));
+    assert_eq!(primitive.node_name.as_deref(), Some("child"));
+    assert_eq!(primitive.indices, Some(vec![0, 1, 2]));
+    // The triangle has no normals, they are derived from its counter-clockwise face.
+    assert_eq!(primitive.normals, [0.0, 0.0, 1.0].repeat(3));
+    assert_eq!(primitive.uvs.len(), primitive.positions.len() / 3 * 2);
+    assert_eq!(primitive.tangents.len(), primitive.positions.len() / 3 * 4);
+    assert_eq!(
+        primitive.colors,
+        vec![1.0; primitive.positions.len() / 3 * 4]
+    );
+}
+
+#[test]
+fn parse_reads_normalized_vertex_colors() {
+    let mut bin = triangle_positions();
+    bin.extend_from_slice(&[255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 0]);
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "COLOR_0": 1 } }] }],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            },
+            {
+                "bufferView": 1, "componentType": 5121, "normalized": true,
+                "count": 3, "type": "VEC4"
+            }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 12 }
+        ],
+        "buffers": [{ "byteLength": 48 }]
+    }"#;
+
+    let model = parse_gltf(&glb(json.to_owned(), bin)).unwrap();
+    assert_eq!(
+        model.primitives[0].colors,
+        [1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]
+    );
+}
+
+#[test]
+fn generated_normals_average_the_faces_around_each_vertex() {
+    // Two faces of a cube folded along the edge from vertex 0 to vertex 1, the top
+    // facing up and the front facing +z.
+    let positions = [
+        [0.0, 1.0, 1.0],
+        [1.0, 1.0, 1.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 1.0],
+    ];
+    let indices = [0, 1, 2, 3, 4, 1, 3, 1, 0];
+    let normals = generate_normals(&positions, Some(&indices), Mode::Triangles);
+
+    assert_vec3_near(Vec3::from(normals[2]), Vec3::unit_y());
+    assert_vec3_near(Vec3::from(normals[3]), Vec3::unit_z());
+    // Vertex 1 touches the top once and the front twice, weighted by area.
+    let shared = (Vec3::unit_y() * 0.5 + Vec3::unit_z()).normalized();
+    assert_vec3_near(Vec3::from(normals[1]), shared);
+
+    // Lines and degenerate triangles have no face to take a normal from.
+    let flat = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]];
+    let up = vec![[0.0, 1.0, 0.0]; 3];
+    assert_eq!(generate_normals(&flat, None, Mode::Triangles), up);
+    assert_eq!(generate_normals(&positions[..3], None, Mode::Lines), up);
+}
+
+#[test]
+fn generated_tangents_follow_u_and_flip_with_mirrored_v() {
+    // A quad in the xy plane facing +z, u along +x.
+    let positions = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    let normals = [[0.0, 0.0, 1.0]; 4];
+    let indices = [0, 1, 2, 0, 2, 3];
+
+    let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    let tangents = generate_tangents(&positions, &normals, &uvs, Some(&indices), Mode::Triangles);
+    assert!(tangents
+        .iter()
+        .all(|&tangent| tangent == [1.0, 0.0, 0.0, 1.0]));
+
+    let mirrored = uvs.map(|[u, v]| [u, 1.0 - v]);
+    let tangents = generate_tangents(
+        &positions,
+        &normals,
+        &mirrored,
+        Some(&indices),
+        Mode::Triangles,
+    );
+    assert!(tangents
+        .iter()
+        .all(|&tangent| tangent == [1.0, 0.0, 0.0, -1.0]));
+
+    // Lines have no surface, any tangent perpendicular to the normal does.
+    let tangents = generate_tangents(&positions, &normals, &uvs, None, Mode::Lines);
+    for tangent in tangents {
+        let tangent = Vec3::new(tangent[0], tangent[1], tangent[2]);
+        assert!(tangent.dot(Vec3::unit_z()).abs() < EPSILON);
+        assert!((tangent.mag() - 1.0).abs() < EPSILON);
+    }
+}
+
+#[test]
+fn normals_stay_perpendicular_under_non_uniform_scale() {
+    let model = parse_gltf(&scaled_slope_glb()).unwrap();
+    let primitive = &model.primitives[0];
+
+    let world_position = |vertex: usize| {
+        let p = &primitive.positions[vertex * 3..vertex * 3 + 3];
+        primitive
+            .transform
+            .transform_point3(Vec3::new(p[0], p[1], p[2]))
+    };
+    let (a, b, c) = (world_position(0), world_position(1), world_position(2));
+    let surface_normal = (b - a).cross(c - a).normalized();
+    assert_vec3_near(surface_normal, Vec3::new(1.0, 4.0, 0.0).normalized());
+
+    for normal in primitive.normals.chunks_exact(3) {
+        let normal = Vec3::new(normal[0], normal[1], normal[2]);
+        assert!((normal.mag() - 1.0).abs() < EPSILON);
+        assert_vec3_near(normal, surface_normal);
+        // Outward, away from the origin the slope faces away from.
+        assert!(normal.dot(a) > 0.0);
+    }
+}
+
+#[test]
+fn parse_rejects_required_draco_compression() {
+    let json = r#"{
+        "asset": {"version": "2.0"},
+        "extensionsUsed": ["KHR_draco_mesh_compression"],
+        "extensionsRequired": ["KHR_draco_mesh_compression"],
+        "buffers": [{"byteLength": 4}]
+    }"#;
+
+    let result = parse_gltf(&glb(json.to_owned(), vec![0; 4]));
+
+    assert!(matches!(
+        result,
+        Err(ImportError::UnsupportedExtension(
+            "KHR_draco_mesh_compression"
+        ))
+    ));
+}
+
+/// A `.glb` with one Draco-compressed triangle and no uncompressed fallback, listing
+/// the extension in `extensions` ("extensionsUsed" and, optionally, "extensionsRequired").
+fn draco_triangle_glb(extensions: &str) -> Vec<u8> {
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            {extensions}
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0 }},
+                    "indices": 1,
+                    "extensions": {{
+                        "KHR_draco_mesh_compression": {{
+                            "bufferView": 0,
+                            "attributes": {{ "POSITION": 7 }}
+                        }}
+                    }}
+                }}]
+            }}],
+            "accessors": [
+                {{
+                    "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                }},
+                {{ "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [{{ "buffer": 0, "byteOffset": 0, "byteLength": 4 }}],
+            "buffers": [{{ "byteLength": 4 }}]
+        }}"#
+    );
+    glb(json, vec![1, 2, 3, 4])
+}
+
+/// Decodes the compressed view of `draco_triangle_glb` into its triangle.
+struct TriangleDraco;
+
+impl DracoDecoder for TriangleDraco {
+    fn decode(&self, data: &[u8], attributes: &[(&str, u32)]) -> Result<DracoMesh, String> {
+        assert_eq!(data, [1, 2, 3, 4]);
+        assert_eq!(attributes, [("POSITION", 7)]);
+        Ok(DracoMesh {
+            positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn parse_decodes_draco_primitives_with_a_decoder() {
+    let bytes = draco_triangle_glb(
+        r#""extensionsUsed": ["KHR_draco_mesh_compression"],
+        "extensionsRequired": ["KHR_draco_mesh_compression"],"#,
+    );
+
+    let model = parse_gltf_with_draco(&bytes, &ExternalBuffers::new(), &TriangleDraco).unwrap();
+
+    let primitive = &model.primitives[0];
+    assert_eq!(primitive.positions[3..6], [1.0, 0.0, 0.0]);
+    assert_eq!(primitive.indices, Some(vec![0, 1, 2]));
+    // Normals are derived like for any primitive without them.
+    let normal = &primitive.normals[..3];
+    assert_vec3_near(Vec3::new(normal[0], normal[1], normal[2]), Vec3::unit_z());
+    assert_eq!(
+        model.bounds,
+        Some(ModelBounds::new([0.0, 0.0, 0.0], [1.0, 1.0, 0.0]))
+    );
+}
+
+#[test]
+fn parse_rejects_draco_primitives_without_fallback_when_only_used() {
+    let bytes = draco_triangle_glb(r#""extensionsUsed": ["KHR_draco_mesh_compression"],"#);
+
+    assert!(matches!(
+        parse_gltf(&bytes),
+        Err(ImportError::UnsupportedExtension(
+            "KHR_draco_mesh_compression"
+        ))
+    ));
+}
+
+#[test]
+fn parse_rejects_indices_past_the_last_vertex() {
+    let mut bin = triangle_positions();
+    bin.extend_from_slice(bytemuck::cast_slice(&[0u16, 1, 3, 0]));
+    let json = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "buffers": [{ "byteLength": 44 }]
+    }"#;
+
+    match parse_gltf(&glb(json.to_owned(), bin)) {
+        Err(ImportError::Other(message)) => assert!(message.starts_with("index out of range")),
+        other => panic!("expected an index error, got {:?}", other.map(|_| ())),
+    }
+}
+
+/// A `.gltf` drawing one triangle from a single buffer stored at `uri`.
+fn triangle_gltf(uri: &str) -> Vec<u8> {
+    format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+            "accessors": [{{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+            }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+            "buffers": [{{ "uri": "{uri}", "byteLength": 36 }}]
+        }}"#
+    )
+    .into_bytes()
+}
+
+fn triangle_positions() -> Vec<u8> {
+    let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    bytemuck::cast_slice(&positions).to_vec()
+}
+
+#[test]
+fn parse_reads_data_uri_and_external_buffers() {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(triangle_positions());
+    let inline = triangle_gltf(&format!("data:application/octet-stream;base64,{encoded}"));
+    let model = parse_gltf(&inline).unwrap();
+    assert_eq!(model.primitives.len(), 1);
+    assert_eq!(model.primitives[0].positions[3], 1.0);
+
+    // A separate file is only read once it has been fetched.
+    let separate = triangle_gltf("model.bin");
+    assert!(parse_gltf(&separate).unwrap().primitives.is_empty());
+
+    let external = ExternalBuffers::from([(0, triangle_positions())]);
+    let model = parse_gltf_with_buffers(&separate, &external).unwrap();
+    assert_eq!(model.primitives.len(), 1);
+
+    let broken = triangle_gltf("data:application/octet-stream;base64,not base64!");
+    assert!(matches!(
+        parse_gltf(&broken),
+        Err(ImportError::InvalidDataUri { index: 0 })
+    ));
+}
+
+#[test]
+fn sniff_tells_glb_from_json() {
+    assert_eq!(
+        ModelFormat::sniff(&two_triangle_glb()),
+        Some(ModelFormat::Binary)
+    );
+    assert_eq!(
+        ModelFormat::sniff(b"\xEF\xBB\xBF\n  {\"asset\": {}}"),
+        Some(ModelFormat::Json)
+    );
+    assert_eq!(ModelFormat::sniff(b"\x89PNG\r\n"), None);
+    assert_eq!(ModelFormat::sniff(b""), None);
+}
+
+#[test]
+fn parse_decodes_base_color_textures() {
+    let model = parse_gltf(&textured_triangle_glb()).unwrap();
+
+    assert_eq!(model.primitives[0].material, Some(0));
+    assert_eq!(
+        model.materials,
+        [MaterialData {
+            base_color_factor: [1.0, 1.0, 1.0, 0.5],
+            base_color_texture: Some(0),
+            blend_mode: BlendMode::Blend,
+            unlit: false,
+        }]
+    );
+    let unlit = parse_gltf(&unlit_triangle_glb()).unwrap();
+    assert!(unlit.materials[0].unlit);
+
+    let image = model.images[0].as_ref().unwrap();
+    assert_eq!((image.width, image.height), (2, 1));
+    assert_eq!(image.rgba, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+    // Models without materials need no images decoded.
+    let plain = parse_gltf(&two_triangle_glb()).unwrap();
+    assert!(plain.materials.is_empty() && plain.images.is_empty());
+    assert_eq!(plain.primitives[0].material, None);
+}
+
+#[test]
+fn strips_and_fans_become_lists_with_the_same_winding() {
+    let (mode, strip) = list_indices(Mode::TriangleStrip, None, 5);
+    assert_eq!(mode, Mode::Triangles);
+    assert_eq!(strip.unwrap(), [0, 1, 2, 1, 3, 2, 2, 3, 4]);
+
+    let (mode, fan) = list_indices(Mode::TriangleFan, Some(vec![7, 8, 9, 6]), 10);
+    assert_eq!(mode, Mode::Triangles);
+    assert_eq!(fan.unwrap(), [8, 9, 7, 9, 6, 7]);
+}
+
+#[test]
+fn line_loops_close_back_to_the_first_vertex() {
+    let (mode, strip) = list_indices(Mode::LineStrip, None, 3);
+    assert_eq!(mode, Mode::Lines);
+    assert_eq!(strip.unwrap(), [0, 1, 1, 2]);
+
+    let (mode, lines) = list_indices(Mode::LineLoop, None, 3);
+    assert_eq!(mode, Mode::Lines);
+    assert_eq!(lines.unwrap(), [0, 1, 1, 2, 2, 0]);
+}
+
+#[test]
+fn framing_looks_at_center_and_covers_model() {
+    let bounds = ModelBounds::new([-1.0, -1.0, -1.0], [3.0, 1.0, 1.0]);
+    let framing = bounds.framing();
+    let radius = bounds.radius();
+
+    assert_vec3_near(framing.target, Vec3::new(1.0, 0.0, 0.0));
+    assert!((radius - 6.0f32.sqrt()).abs() < EPSILON);
+    assert_vec3_near(framing.eye, Vec3::new(1.0, radius * 0.05, radius * 0.25));
+    assert!(framing.far >= (framing.eye - framing.target).mag() + radius);
+    assert!(framing.near > 0.0 && framing.near < framing.far);
+}
+
+#[test]
+fn framing_keeps_tiny_models_reachable() {
+    let framing = ModelBounds::new([0.0; 3], [0.01; 3]).framing();
+
+    assert_eq!(framing.near, 0.1);
+    assert_eq!(framing.far, 4.0);
+}
+
+#[test]
+fn load_progress_fills_download_then_upload_share() {
+    let mut reported = Vec::new();
+    {
+        let mut progress = LoadProgress::new(|fraction| reported.push(fraction));
+        progress.downloaded(0, 1);
+        progress.downloaded(300, 600);
+        progress.downloaded(600, 600);
+        progress.uploaded(1, 4);
+        progress.uploaded(4, 4);
+        // Models without primitives are done as soon as they are downloaded.
+        progress.uploaded(0, 0);
+    }
+
+    assert_eq!(reported, [0.0, 0.25, 0.5, 0.625, 1.0, 1.0]);
+}
@@ -1,14 +1,37 @@
 use core::fmt;
+use std::collections::HashMap;
 use std::sync::mpsc::TryRecvError;
 use std::cell::BorrowMutError;
 
+use js_sys::Reflect;
+use wasm_bindgen::{JsCast, JsValue};
+
 #[derive(Debug)]
 pub enum WindowEvent {
     Resize(ResizeMessage),
     PointerMove(MouseMessage),
     PointerClick(MouseMessage),
+    /// Second click of a double click, sent after its `PointerClick`, see `ClickTracker`.
+    PointerDoubleClick(MouseMessage),
     PointerWheel(WheelMessage),
     Keyboard(KeyboardMessage),
+    /// A key was released, sent so held keys can be tracked.
+    KeyboardUp(KeyboardMessage),
+    /// The window lost focus or the page was hidden, so held keys get no keyup.
+    FocusLost,
+    /// Replace the scene with the glTF or GLB file in the bytes, e.g. a dropped file.
+    LoadModel(Vec<u8>),
+    /// Render the current frame to PNG, posted back as `WorkerMessage::FrameCaptured`.
+    CaptureFrame,
+    /// Replace the WGSL of a registered pipeline, see `GpuResources::reload_pipeline`.
+    ReloadShader {
+        name: String,
+        source: String,
+    },
+    /// Stop or resume drawing frames, see `Renderer::set_paused`.
+    SetPaused(bool),
+    /// Only draw frames when something changed, see `Renderer::set_render_on_demand`.
+    SetRenderOnDemand(bool),
 }
 
 // Display for WindowEvent
@@ -18,8 +41,18 @@ impl fmt::Display for WindowEvent {
             WindowEvent::Resize(msg) => write!(f, "Resize: {:?}", msg),
             WindowEvent::PointerMove(msg) => write!(f, "PointerMove: {:?}", msg),
             WindowEvent::PointerClick(msg) => write!(f, "PointerClick: {:?}", msg),
+            WindowEvent::PointerDoubleClick(msg) => write!(f, "PointerDoubleClick: {:?}", msg),
             WindowEvent::PointerWheel(msg) => write!(f, "PointerWheel: {:?}", msg),
             WindowEvent::Keyboard(msg) => write!(f, "Keyboard: {:?}", msg),
+            WindowEvent::KeyboardUp(msg) => write!(f, "KeyboardUp: {:?}", msg),
+            WindowEvent::FocusLost => write!(f, "FocusLost"),
+            WindowEvent::LoadModel(bytes) => write!(f, "LoadModel: {} bytes", bytes.len()),
+            WindowEvent::CaptureFrame => write!(f, "CaptureFrame"),
+            WindowEvent::ReloadShader { name, .. } => write!(f, "ReloadShader: {}", name),
+            WindowEvent::SetPaused(paused) => write!(f, "SetPaused: {}", paused),
+            WindowEvent::SetRenderOnDemand(on_demand) => {
+                write!(f, "SetRenderOnDemand: {}", on_demand)
+            }
         }
     }
 }
@@ -42,6 +75,7 @@ pub struct MouseMessage {
     pub movement_y: f64,
     pub offset_x: f64,
     pub offset_y: f64,
+    pub shift_key: bool,
 }
 
 impl MouseMessage {
@@ -57,6 +91,7 @@ impl MouseMessage {
             movement_y: event.movement_y() as f64,
             offset_x: event.offset_x() as f64,
             offset_y: event.offset_y() as f64,
+            shift_key: event.shift_key(),
         }
     }
 }
@@ -70,6 +105,10 @@ pub struct WheelMessage {
     pub delta_mode: u32,
     pub client_x: f64,
     pub client_y: f64,
+    pub alt_key: bool,
+    pub ctrl_key: bool,
+    pub meta_key: bool,
+    pub shift_key: bool,
 }
 
 impl WheelMessage {
@@ -83,6 +122,125 @@ impl WheelMessage {
             delta_mode: event.delta_mode(),
             client_x: event.client_x() as f64,
             client_y: event.client_y() as f64,
+            alt_key: event.alt_key(),
+            ctrl_key: event.ctrl_key(),
+            meta_key: event.meta_key(),
+            shift_key: event.shift_key(),
+        }
+    }
+
+    /// Vertical scroll in pixels, whatever the event's delta mode.
+    pub fn pixel_delta_y(&self) -> f64 {
+        match self.delta_mode {
+            1 => self.delta_y * 16.0,
+            2 => self.delta_y * 800.0,
+            _ => self.delta_y,
+        }
+    }
+
+    /// Fold a later event into this one, so a burst of events can be applied as one step.
+    ///
+    /// The result is expressed in pixels and keeps the position and modifiers of `other`.
+    pub fn accumulate(&mut self, other: &WheelMessage) {
+        let delta_y = self.pixel_delta_y() + other.pixel_delta_y();
+        *self = WheelMessage {
+            delta_y,
+            delta_mode: 0,
+            ..other.clone()
+        };
+    }
+}
+
+/// Longest time between the two clicks of a double click, in milliseconds.
+const DOUBLE_CLICK_INTERVAL: f64 = 400.0;
+/// Farthest the pointer may move between the two clicks of a double click, in CSS pixels.
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+
+/// Tells double clicks apart by the time and place of the previous click.
+#[derive(Debug, Default)]
+pub struct ClickTracker {
+    // Time stamp and client position of the last click not part of a double click
+    last: Option<(f64, f64, f64)>,
+}
+
+impl ClickTracker {
+    /// Record a click at `time_stamp` milliseconds and client position (`x`, `y`),
+    /// returning whether it is the second click of a double click. A third click starts
+    /// over.
+    pub fn click(&mut self, time_stamp: f64, x: f64, y: f64) -> bool {
+        let double = self.last.is_some_and(|(last_time, last_x, last_y)| {
+            time_stamp - last_time <= DOUBLE_CLICK_INTERVAL
+                && (x - last_x).hypot(y - last_y) <= DOUBLE_CLICK_DISTANCE
+        });
+        self.last = if double {
+            None
+        } else {
+            Some((time_stamp, x, y))
+        };
+        double
+    }
+}
+
+/// Turns touches into the pointer events the renderer already handles: dragging one
+/// finger orbits like a middle button drag, pinching two fingers zooms like the wheel.
+#[derive(Debug, Default)]
+pub struct TouchTracker {
+    // Last client position of each active touch, by identifier
+    touches: HashMap<i32, (f64, f64)>,
+}
+
+impl TouchTracker {
+    /// Start over from the active touches, as `(identifier, client_x, client_y)`, when
+    /// fingers are added or lifted so the gesture does not jump.
+    pub fn reset(&mut self, touches: &[(i32, f64, f64)]) {
+        self.touches = touches.iter().map(|&(id, x, y)| (id, (x, y))).collect();
+    }
+
+    /// Follow the active touches and return the event their movement amounts to.
+    ///
+    /// Gestures with three or more fingers are ignored.
+    pub fn moved(&mut self, touches: &[(i32, f64, f64)], scale_factor: f64) -> Option<WindowEvent> {
+        let previous = touches
+            .iter()
+            .map(|(id, _, _)| self.touches.get(id).copied())
+            .collect::<Option<Vec<_>>>();
+        let same_touches = previous.is_some() && touches.len() == self.touches.len();
+        self.reset(touches);
+        let previous = previous.filter(|_| same_touches)?;
+
+        match (touches, previous.as_slice()) {
+            (&[(_, x, y)], &[(last_x, last_y)]) => Some(WindowEvent::PointerMove(MouseMessage {
+                scale_factor,
+                button: 1.0,
+                buttons: 0x04,
+                client_x: x,
+                client_y: y,
+                movement_x: x - last_x,
+                movement_y: y - last_y,
+                offset_x: x,
+                offset_y: y,
+                shift_key: false,
+            })),
+            (&[(_, x0, y0), (_, x1, y1)], &[(last_x0, last_y0), (last_x1, last_y1)]) => {
+                let spread = (x1 - x0).hypot(y1 - y0);
+                let last_spread = (last_x1 - last_x0).hypot(last_y1 - last_y0);
+
+                // Spreading the fingers zooms in, like scrolling up.
+                Some(WindowEvent::PointerWheel(WheelMessage {
+                    scale_factor,
+                    delta_x: 0.0,
+                    delta_y: last_spread - spread,
+                    delta_z: 0.0,
+                    delta_mode: 0,
+                    client_x: (x0 + x1) / 2.0,
+                    client_y: (y0 + y1) / 2.0,
+                    alt_key: false,
+                    ctrl_key: false,
+                    meta_key: false,
+                    shift_key: false,
+                }))
+            }
+            _ => None,
         }
     }
 }
@@ -114,6 +272,111 @@ impl KeyboardMessage {
     }
 }
 
+/// Adapter the renderer ended up on, reported once it is ready.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RendererInfo {
+    pub adapter: String,
+    pub backend: String,
+    pub device_type: String,
+}
+
+impl RendererInfo {
+    pub fn from_adapter_info(info: &wgpu::AdapterInfo) -> Self {
+        Self {
+            adapter: info.name.clone(),
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+        }
+    }
+}
+
+/// Messages posted from the render worker back to the main thread.
+#[derive(Debug, Clone)]
+pub enum WorkerMessage {
+    /// The renderer is set up and about to draw its first frame.
+    RendererReady(RendererInfo),
+    /// The renderer could not be created, typically because WebGPU is not supported.
+    RendererInitFailed { kind: String, message: String },
+    /// PNG bytes of a frame captured with `WindowEvent::CaptureFrame`.
+    FrameCaptured(Vec<u8>),
+    /// How far a model being loaded got, from 0 to 1, see `LoadProgress`.
+    ModelLoadProgress(f32),
+    /// A model finished loading into the given number of meshes.
+    ModelLoaded { meshes: usize },
+}
+
+impl WorkerMessage {
+    const RENDERER_READY: &'static str = "renderer-ready";
+    const RENDERER_INIT_FAILED: &'static str = "renderer-init-failed";
+    const FRAME_CAPTURED: &'static str = "frame-captured";
+    const MODEL_LOAD_PROGRESS: &'static str = "model-load-progress";
+    const MODEL_LOADED: &'static str = "model-loaded";
+
+    /// Encode as a plain JS object for `postMessage`, `{ type, adapter, backend,
+    /// deviceType }`, `{ type, kind, message }`, `{ type, png }` with the PNG in a
+    /// `Uint8Array`, `{ type, fraction }` or `{ type, meshes }`.
+    pub fn to_js(&self) -> JsValue {
+        let object = js_sys::Object::new();
+        match self {
+            WorkerMessage::RendererReady(info) => {
+                let _ = Reflect::set(&object, &"type".into(), &Self::RENDERER_READY.into());
+                let _ = Reflect::set(&object, &"adapter".into(), &(&info.adapter).into());
+                let _ = Reflect::set(&object, &"backend".into(), &(&info.backend).into());
+                let _ = Reflect::set(&object, &"deviceType".into(), &(&info.device_type).into());
+            }
+            WorkerMessage::RendererInitFailed { kind, message } => {
+                let _ = Reflect::set(&object, &"type".into(), &Self::RENDERER_INIT_FAILED.into());
+                let _ = Reflect::set(&object, &"kind".into(), &kind.into());
+                let _ = Reflect::set(&object, &"message".into(), &message.into());
+            }
+            WorkerMessage::FrameCaptured(png) => {
+                let _ = Reflect::set(&object, &"type".into(), &Self::FRAME_CAPTURED.into());
+                let bytes = js_sys::Uint8Array::from(png.as_slice());
+                let _ = Reflect::set(&object, &"png".into(), &bytes);
+            }
+            WorkerMessage::ModelLoadProgress(fraction) => {
+                let _ = Reflect::set(&object, &"type".into(), &Self::MODEL_LOAD_PROGRESS.into());
+                let _ = Reflect::set(&object, &"fraction".into(), &(*fraction).into());
+            }
+            WorkerMessage::ModelLoaded { meshes } => {
+                let _ = Reflect::set(&object, &"type".into(), &Self::MODEL_LOADED.into());
+                let _ = Reflect::set(&object, &"meshes".into(), &(*meshes as u32).into());
+            }
+        }
+        object.into()
+    }
+
+    /// Decode a message produced by `to_js`, `None` for anything else.
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        let field = |name: &str| Reflect::get(value, &name.into()).ok()?.as_string();
+        let number = |name: &str| Reflect::get(value, &name.into()).ok()?.as_f64();
+
+        match field("type")?.as_str() {
+            Self::RENDERER_READY => Some(WorkerMessage::RendererReady(RendererInfo {
+                adapter: field("adapter")?,
+                backend: field("backend")?,
+                device_type: field("deviceType")?,
+            })),
+            Self::RENDERER_INIT_FAILED => Some(WorkerMessage::RendererInitFailed {
+                kind: field("kind")?,
+                message: field("message")?,
+            }),
+            Self::FRAME_CAPTURED => {
+                let png = Reflect::get(value, &"png".into()).ok()?;
+                let png = png.dyn_into::<js_sys::Uint8Array>().ok()?;
+                Some(WorkerMessage::FrameCaptured(png.to_vec()))
+            }
+            Self::MODEL_LOAD_PROGRESS => {
+                Some(WorkerMessage::ModelLoadProgress(number("fraction")? as f32))
+            }
+            Self::MODEL_LOADED => Some(WorkerMessage::ModelLoaded {
+                meshes: number("meshes")? as usize,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DrainEventError {
     BorrowError(BorrowMutError),
@@ -2,4 +2,4 @@
 pub mod web;
 
 #[cfg(not(target_arch = "wasm32"))]
-mod native;
+pub mod native;
@@ -1 +1,29 @@
+/// Device and queue from a headless adapter, to build scenes without a canvas.
+///
+/// Returns `None` when the machine has no usable adapter, so callers such as tests can
+/// skip GPU work instead of failing.
+pub fn test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
 
+    futures::executor::block_on(async {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: None,
+                force_fallback_adapter: false,
+                ..Default::default()
+            })
+            .await
+            .ok()?;
+
+        adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("test device"),
+                required_features: adapter.features() & wgpu::Features::POLYGON_MODE_LINE,
+                required_limits: wgpu::Limits::downlevel_defaults(),
+                memory_hints: wgpu::MemoryHints::default(),
+                trace: wgpu::Trace::default(),
+            })
+            .await
+            .ok()
+    })
+}
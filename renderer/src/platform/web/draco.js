@@ -0,0 +1,79 @@
+// Google's Draco decoder for the decode worker, loaded the way three.js' DRACOLoader
+// does it: the wrapper script is not an ES module, so it is evaluated from its text.
+let draco = null;
+
+export async function loadDracoDecoder(baseUrl) {
+  const [wrapper, wasmBinary] = await Promise.all([
+    fetchOk(`${baseUrl}draco_wasm_wrapper.js`).then((response) => response.text()),
+    fetchOk(`${baseUrl}draco_decoder.wasm`).then((response) => response.arrayBuffer()),
+  ]);
+  const DracoDecoderModule = new Function(`${wrapper}; return DracoDecoderModule;`)();
+
+  // The module is a thenable that never settles on its own, so it is wrapped in an
+  // object before resolving.
+  const loaded = await new Promise((resolve) => {
+    DracoDecoderModule({
+      wasmBinary,
+      onModuleLoaded: (module) => resolve({ module }),
+    });
+  });
+  draco = loaded.module;
+}
+
+async function fetchOk(url) {
+  const response = await fetch(url);
+  if (!response.ok) {
+    throw new Error(`${url} responded with HTTP status ${response.status}`);
+  }
+  return response;
+}
+
+// Decode a compressed primitive into its triangle indices and one Float32Array per
+// attribute, keyed by the glTF attribute name in `names`.
+export function decodeDraco(data, names, ids) {
+  if (draco === null) {
+    throw new Error("the Draco decoder is not loaded");
+  }
+
+  const decoder = new draco.Decoder();
+  const mesh = new draco.Mesh();
+  try {
+    const bytes = new Int8Array(data.buffer, data.byteOffset, data.byteLength);
+    const status = decoder.DecodeArrayToMesh(bytes, bytes.byteLength, mesh);
+    if (!status.ok() || mesh.ptr === 0) {
+      throw new Error(status.error_msg());
+    }
+
+    const result = { indices: readIndices(decoder, mesh) };
+    names.forEach((name, i) => {
+      const attribute = decoder.GetAttributeByUniqueId(mesh, ids[i]);
+      result[name] = readFloats(decoder, mesh, attribute);
+    });
+    return result;
+  } finally {
+    draco.destroy(mesh);
+    draco.destroy(decoder);
+  }
+}
+
+function readIndices(decoder, mesh) {
+  const count = mesh.num_faces() * 3;
+  const ptr = draco._malloc(count * 4);
+  try {
+    decoder.GetTrianglesUInt32Array(mesh, count * 4, ptr);
+    return new Uint32Array(draco.HEAPU32.buffer, ptr, count).slice();
+  } finally {
+    draco._free(ptr);
+  }
+}
+
+function readFloats(decoder, mesh, attribute) {
+  const count = mesh.num_points() * attribute.num_components();
+  const ptr = draco._malloc(count * 4);
+  try {
+    decoder.GetAttributeDataArrayForAllPoints(mesh, attribute, draco.DT_FLOAT32, count * 4, ptr);
+    return new Float32Array(draco.HEAPF32.buffer, ptr, count).slice();
+  } finally {
+    draco._free(ptr);
+  }
+}
@@ -0,0 +1,69 @@
+use js_sys::{Array, Float32Array, Reflect, Uint32Array, Uint8Array};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::gltf::{DracoDecoder, DracoMesh};
+
+/// Where the decode worker loads `draco_wasm_wrapper.js` and `draco_decoder.wasm` from,
+/// e.g. copied from the `draco3d` package into `static/draco/`.
+pub const DRACO_DECODER_PATH: &str = "/draco/";
+
+/// Binds JS.
+#[wasm_bindgen(module = "/src/platform/web/draco.js")]
+extern "C" {
+    #[wasm_bindgen(js_name = "loadDracoDecoder", catch)]
+    async fn load_draco_decoder(base_url: &str) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_name = "decodeDraco", catch)]
+    fn decode_draco(data: &Uint8Array, names: &Array, ids: &Array) -> Result<JsValue, JsValue>;
+}
+
+/// Google's Draco wasm module, loaded into the worker that decodes models.
+pub struct WebDracoDecoder(());
+
+impl WebDracoDecoder {
+    /// Load the decoder from `base_url`. `None` when it is not served there, Draco
+    /// models then fail with `ImportError::UnsupportedExtension`.
+    pub async fn load(base_url: &str) -> Option<Self> {
+        match load_draco_decoder(base_url).await {
+            Ok(_) => Some(Self(())),
+            Err(err) => {
+                log::warn!("No Draco decoder at {}: {:?}", base_url, err);
+                None
+            }
+        }
+    }
+}
+
+impl DracoDecoder for WebDracoDecoder {
+    fn decode(&self, data: &[u8], attributes: &[(&str, u32)]) -> Result<DracoMesh, String> {
+        let names: Array = attributes
+            .iter()
+            .map(|&(name, _)| JsValue::from_str(name))
+            .collect();
+        let ids: Array = attributes
+            .iter()
+            .map(|&(_, id)| JsValue::from(id))
+            .collect();
+        let decoded = decode_draco(&Uint8Array::from(data), &names, &ids)
+            .map_err(|err| format!("{:?}", err))?;
+
+        let floats = |name: &str| {
+            Reflect::get(&decoded, &name.into())
+                .ok()
+                .filter(|value| !value.is_undefined())
+                .map(|value| value.unchecked_into::<Float32Array>().to_vec())
+                .unwrap_or_default()
+        };
+        let indices = Reflect::get(&decoded, &"indices".into())
+            .map_err(|err| format!("{:?}", err))?
+            .unchecked_into::<Uint32Array>()
+            .to_vec();
+
+        Ok(DracoMesh {
+            positions: floats("POSITION"),
+            normals: floats("NORMAL"),
+            uvs: floats("TEXCOORD_0"),
+            indices,
+        })
+    }
+}
@@ -1,5 +1,6 @@
 use wasm_bindgen::JsCast;
 
+pub mod draco;
 pub mod worker;
 
 pub fn get_canvas_element(selectors: &str) -> web_sys::HtmlCanvasElement {
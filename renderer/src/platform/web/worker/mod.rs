@@ -1,4 +1,6 @@
-use crate::message::WindowEvent;
+use crate::gltf::ModelDecoder;
+use crate::message::{RendererInfo, WindowEvent, WorkerMessage};
+use crate::renderer::RendererConfig;
 use log::info;
 use std::sync::mpsc::Receiver;
 use std::{cell::RefCell, fmt::Debug, ops::Deref, rc::Rc};
@@ -24,9 +26,58 @@ extern "C" {
     fn attachMain();
 }
 
+/// Event dispatched on `window` for every `WorkerMessage`, with the message object
+/// (see `WorkerMessage::to_js`) as `detail`.
+pub const WORKER_MESSAGE_EVENT: &str = "renderer-message";
+
+/// Renderer status reported by the worker, and the callbacks still waiting for it.
+#[derive(Default)]
+struct Lifecycle {
+    status: Option<WorkerMessage>,
+    on_ready: Vec<Box<dyn FnOnce(&RendererInfo)>>,
+    on_init_failed: Vec<Box<dyn FnOnce(&str, &str)>>,
+}
+
+impl Lifecycle {
+    /// Run the callbacks matching `status`. They are taken out first, so a callback
+    /// may register further ones.
+    fn notify(lifecycle: &RefCell<Self>, status: &WorkerMessage) {
+        // Captured frames and loaded models come and go without changing the renderer
+        // status.
+        if matches!(
+            status,
+            WorkerMessage::FrameCaptured(_)
+                | WorkerMessage::ModelLoadProgress(_)
+                | WorkerMessage::ModelLoaded { .. }
+        ) {
+            return;
+        }
+
+        let (on_ready, on_init_failed) = {
+            let mut lifecycle = lifecycle.borrow_mut();
+            lifecycle.status = Some(status.clone());
+            (
+                std::mem::take(&mut lifecycle.on_ready),
+                std::mem::take(&mut lifecycle.on_init_failed),
+            )
+        };
+
+        match status {
+            WorkerMessage::RendererReady(info) => on_ready.into_iter().for_each(|f| f(info)),
+            WorkerMessage::RendererInitFailed { kind, message } => {
+                on_init_failed.into_iter().for_each(|f| f(kind, message))
+            }
+            WorkerMessage::FrameCaptured(_)
+            | WorkerMessage::ModelLoadProgress(_)
+            | WorkerMessage::ModelLoaded { .. } => {}
+        }
+    }
+}
+
 pub struct MainWorker {
     handle: web_sys::Worker,
     name: String,
+    lifecycle: Rc<RefCell<Lifecycle>>,
     _callback: Closure<dyn FnMut(web_sys::Event)>,
 }
 
@@ -61,9 +112,18 @@ impl MainWorker {
         // an undefined layout (although I think in practice its a pointer and a length?).
         let ptr = Box::into_raw(Box::new(Box::new(f) as Box<dyn FnOnce()>));
 
-        // Sets default callback.
-        let callback = Closure::new(|_ev| {
-            info!("got a message..canvas?");
+        // Forwards worker messages to the page as DOM events.
+        let lifecycle = Rc::new(RefCell::new(Lifecycle::default()));
+        let callback_lifecycle = lifecycle.clone();
+        let callback = Closure::new(move |event: web_sys::Event| {
+            let data = event.unchecked_into::<MessageEvent>().data();
+            match WorkerMessage::from_js(&data) {
+                Some(message) => {
+                    Lifecycle::notify(&callback_lifecycle, &message);
+                    dispatch_worker_message(&message, &data);
+                }
+                None => info!("got a message..canvas?"),
+            }
         });
         handle.set_onmessage(Some(callback.as_ref().unchecked_ref()));
 
@@ -82,10 +142,38 @@ impl MainWorker {
         Ok(Self {
             handle,
             name: name.to_owned(),
+            lifecycle,
             _callback: callback,
         })
     }
 
+    /// Call `f` once the renderer in this worker is ready to draw, e.g. to hide a
+    /// loading spinner. Runs right away if it already is.
+    pub fn on_ready(&self, f: impl FnOnce(&RendererInfo) + 'static) {
+        let status = self.lifecycle.borrow().status.clone();
+        match status {
+            Some(WorkerMessage::RendererReady(info)) => f(&info),
+            Some(_) => {}
+            None => self.lifecycle.borrow_mut().on_ready.push(Box::new(f)),
+        }
+    }
+
+    /// Call `f` with the error kind and message if the renderer fails to initialize.
+    /// Runs right away if it already has.
+    pub fn on_init_failed(&self, f: impl FnOnce(&str, &str) + 'static) {
+        let status = self.lifecycle.borrow().status.clone();
+        match status {
+            Some(WorkerMessage::RendererInitFailed { kind, message }) => f(&kind, &message),
+            Some(_) => {}
+            None => self.lifecycle.borrow_mut().on_init_failed.push(Box::new(f)),
+        }
+    }
+
+    /// Name the worker was spawned with, shown by the browser's dev tools.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn transfer_ownership(&self, canvas: &web_sys::HtmlCanvasElement) {
         let offscreen_canvas = canvas.transfer_control_to_offscreen().unwrap();
         let transfer_list = js_sys::Array::new();
@@ -99,12 +187,29 @@ impl MainWorker {
 
     pub async fn run_render_loop<T: crate::renderer::scene::Scene + 'static>(
         events_chan: Receiver<WindowEvent>,
+        model_decoder: ModelDecoder,
+        config: RendererConfig,
     ) {
         use crate::renderer::Renderer;
 
         let canvas = wait_for_canvas_transfer().await;
 
-        let renderer = Rc::new(RefCell::new(Renderer::<T>::new(canvas, events_chan).await));
+        let renderer = match Renderer::<T>::new(canvas, events_chan, config).await {
+            Ok(renderer) => Rc::new(RefCell::new(renderer)),
+            Err(err) => {
+                log::error!("Failed to initialize the renderer: {}", err);
+                post_to_main_thread(&WorkerMessage::RendererInitFailed {
+                    kind: err.kind().to_owned(),
+                    message: err.to_string(),
+                });
+                return;
+            }
+        };
+        renderer.borrow_mut().set_model_decoder(model_decoder);
+
+        let info = RendererInfo::from_adapter_info(renderer.borrow().adapter_info());
+        post_to_main_thread(&WorkerMessage::RendererReady(info));
+
         Renderer::run_render_loop(renderer);
     }
 }
@@ -118,6 +223,40 @@ impl Deref for MainWorker {
     }
 }
 
+/// Post a message from inside a worker to the thread that spawned it.
+pub fn post_to_main_thread(message: &WorkerMessage) {
+    let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+    if let Err(err) = global.post_message(&message.to_js()) {
+        log::error!("Failed to post {:?} to the main thread: {:?}", message, err);
+    }
+}
+
+fn dispatch_worker_message(message: &WorkerMessage, data: &JsValue) {
+    match message {
+        WorkerMessage::RendererReady(info) => info!("Renderer ready: {:?}", info),
+        WorkerMessage::RendererInitFailed { .. } => {
+            log::error!("Renderer initialization failed: {:?}", message)
+        }
+        WorkerMessage::FrameCaptured(png) => info!("Frame captured: {} bytes", png.len()),
+        WorkerMessage::ModelLoadProgress(_) => {}
+        WorkerMessage::ModelLoaded { meshes } => info!("Model loaded: {} meshes", meshes),
+    }
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    let init = web_sys::CustomEventInit::new();
+    init.set_detail(data);
+
+    match web_sys::CustomEvent::new_with_event_init_dict(WORKER_MESSAGE_EVENT, &init) {
+        Ok(event) => {
+            let _ = window.dispatch_event(&event);
+        }
+        Err(err) => log::error!("Failed to create {}: {:?}", WORKER_MESSAGE_EVENT, err),
+    }
+}
+
 pub async fn wait_for_canvas_transfer() -> web_sys::OffscreenCanvas {
     let global = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
 
@@ -0,0 +1,74 @@
+struct UniformData {
+    mouse_move: vec2<f32>,
+    mouse_click: vec2<f32>,
+    resolution: vec2<f32>,
+    time: f32,
+    point_size: f32,
+    camera_position: vec4<f32>,
+    user_params: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uni: UniformData;
+@group(1) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+
+struct ObjectParams {
+    color: vec4<f32>,
+    params: vec4<f32>,
+}
+
+// Object params follow the frame and camera groups, see `GLTF_BIND_GROUPS`.
+@group(2) @binding(0) var<uniform> object_params: ObjectParams;
+
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    // Corner of the point's quad in [-1, 1].
+    @location(2) corner: vec2<f32>,
+    // Linear vertex color, white when the point cloud has none.
+    @location(3) color: vec4<f32>,
+    @location(4) model_col0: vec4<f32>,
+    @location(5) model_col1: vec4<f32>,
+    @location(6) model_col2: vec4<f32>,
+    @location(7) model_col3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @invariant @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let model = mat4x4<f32>(
+        in.model_col0,
+        in.model_col1,
+        in.model_col2,
+        in.model_col3,
+    );
+    var clip = view_proj * model * vec4<f32>(in.pos, 1.0);
+
+    // Offset in NDC so the quad is point_size pixels across at any distance.
+    let offset = in.corner * uni.point_size / max(uni.resolution, vec2<f32>(1.0));
+    clip = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+
+    out.clip_position = clip;
+    out.corner = in.corner;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Round mask with a one pixel soft edge.
+    let radius = length(in.corner);
+    let edge = fwidth(radius);
+    let coverage = 1.0 - smoothstep(1.0 - edge, 1.0, radius);
+    if coverage <= 0.0 {
+        discard;
+    }
+
+    let color = vec3<f32>(0.8, 0.8, 0.8) * object_params.color.rgb * in.color.rgb;
+    return vec4<f32>(color, coverage * object_params.color.a * in.color.a);
+}
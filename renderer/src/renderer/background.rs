@@ -0,0 +1,302 @@
+use ultraviolet::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::DEPTH_FORMAT;
+
+/// Handle to a cube map used as a skybox.
+///
+/// The view must be created with `wgpu::TextureViewDimension::Cube`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeHandle {
+    view: wgpu::TextureView,
+}
+
+impl CubeHandle {
+    pub fn new(view: wgpu::TextureView) -> Self {
+        Self { view }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
+/// What is drawn behind the scene geometry.
+///
+/// `Solid` only sets the clear color, the other variants draw a fullscreen pass before
+/// any mesh is rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    Solid(wgpu::Color),
+    /// Gradient from the top color to the bottom color of the viewport.
+    VerticalGradient(wgpu::Color, wgpu::Color),
+    Skybox(CubeHandle),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(wgpu::Color::BLACK)
+    }
+}
+
+impl Background {
+    /// Color the color attachment is cleared to before the background pass.
+    pub fn clear_color(&self) -> wgpu::Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::VerticalGradient(_, bottom) => *bottom,
+            Background::Skybox(_) => wgpu::Color::BLACK,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
+struct BackgroundUniform {
+    top_color: [f32; 4],
+    bottom_color: [f32; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+}
+
+fn color_to_array(color: wgpu::Color) -> [f32; 4] {
+    [
+        color.r as f32,
+        color.g as f32,
+        color.b as f32,
+        color.a as f32,
+    ]
+}
+
+/// Fullscreen pass drawing gradient and skybox backgrounds.
+pub(crate) struct BackgroundPass {
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    uniform_layout: wgpu::BindGroupLayout,
+    cube_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    shader: wgpu::ShaderModule,
+    gradient_pipeline: Option<wgpu::RenderPipeline>,
+    skybox_pipeline: Option<wgpu::RenderPipeline>,
+    skybox_bind_group: Option<(CubeHandle, wgpu::BindGroup)>,
+}
+
+impl BackgroundPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("background uniform buffer"),
+            contents: bytemuck::cast_slice(&[BackgroundUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Background bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Background bind group"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let cube_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Skybox bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Skybox sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("background"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./background.wgsl").into()),
+        });
+
+        Self {
+            uniform_buffer,
+            uniform_bind_group,
+            uniform_layout,
+            cube_layout,
+            sampler,
+            shader,
+            gradient_pipeline: None,
+            skybox_pipeline: None,
+            skybox_bind_group: None,
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        fragment_entry: &str,
+        surface_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            // The background never writes depth so the scene draws over it.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some(fragment_entry),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// Upload the background parameters and make sure the needed pipeline exists.
+    ///
+    /// Returns `false` when the background is a plain clear and nothing needs drawing.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        background: &Background,
+        view_proj: Mat4,
+        camera_position: Vec3,
+    ) -> bool {
+        let mut uniform = BackgroundUniform {
+            inv_view_proj: view_proj.inversed().into(),
+            camera_position: [camera_position.x, camera_position.y, camera_position.z, 1.0],
+            ..Default::default()
+        };
+
+        match background {
+            Background::Solid(_) => return false,
+            Background::VerticalGradient(top, bottom) => {
+                uniform.top_color = color_to_array(*top);
+                uniform.bottom_color = color_to_array(*bottom);
+
+                if self.gradient_pipeline.is_none() {
+                    self.gradient_pipeline = Some(self.create_pipeline(
+                        device,
+                        "background_gradient",
+                        &[&self.uniform_layout],
+                        "fs_gradient",
+                        surface_format,
+                    ));
+                }
+            }
+            Background::Skybox(cube) => {
+                if self.skybox_pipeline.is_none() {
+                    self.skybox_pipeline = Some(self.create_pipeline(
+                        device,
+                        "background_skybox",
+                        &[&self.uniform_layout, &self.cube_layout],
+                        "fs_skybox",
+                        surface_format,
+                    ));
+                }
+
+                let stale = match &self.skybox_bind_group {
+                    Some((bound, _)) => bound != cube,
+                    None => true,
+                };
+                if stale {
+                    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("Skybox bind group"),
+                        layout: &self.cube_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(cube.view()),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&self.sampler),
+                            },
+                        ],
+                    });
+                    self.skybox_bind_group = Some((cube.clone(), bind_group));
+                }
+            }
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        true
+    }
+
+    /// Draw the background prepared by the last `prepare` call.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, background: &Background) {
+        let pipeline = match background {
+            Background::Solid(_) => return,
+            Background::VerticalGradient(..) => self.gradient_pipeline.as_ref(),
+            Background::Skybox(_) => {
+                if let Some((_, bind_group)) = &self.skybox_bind_group {
+                    render_pass.set_bind_group(1, bind_group, &[]);
+                }
+                self.skybox_pipeline.as_ref()
+            }
+        };
+
+        if let Some(pipeline) = pipeline {
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+struct BackgroundUniform {
+    top_color: vec4<f32>,
+    bottom_color: vec4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    camera_position: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> background: BackgroundUniform;
+
+@group(1) @binding(0) var skybox_texture: texture_cube<f32>;
+@group(1) @binding(1) var skybox_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// A single triangle covering the whole viewport.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_gradient(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = in.ndc.y * 0.5 + 0.5;
+    return mix(background.bottom_color, background.top_color, t);
+}
+
+@fragment
+fn fs_skybox(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far_point = background.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = normalize(far_point.xyz / far_point.w - background.camera_position.xyz);
+    return textureSample(skybox_texture, skybox_sampler, direction);
+}
@@ -0,0 +1,187 @@
+use futures::channel::oneshot;
+
+use super::RendererContext;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Frames in {0:?} can't be saved as PNG")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("Failed to read back the captured frame: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+    #[error("Readback cancelled before the GPU finished")]
+    Cancelled,
+    #[error("Failed to encode the captured frame: {0}")]
+    Encode(#[from] image::ImageError),
+}
+
+/// Offscreen color target a frame is rendered into instead of the surface texture.
+pub(crate) struct CaptureTarget {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl CaptureTarget {
+    /// Target matching the surface format and size, so the scene pipelines can draw to it
+    /// and the depth buffer fits.
+    pub fn new(context: &RendererContext) -> Self {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("frame capture texture"),
+            size: wgpu::Extent3d {
+                width: context.surface_config.width,
+                height: context.surface_config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+
+        Self { texture, view }
+    }
+
+    /// Copy the target after the frame recorded in `encoder`, submit it, and start
+    /// mapping the copy.
+    pub fn read(
+        self,
+        context: &RendererContext,
+        mut encoder: wgpu::CommandEncoder,
+    ) -> CaptureReadback {
+        let width = self.texture.width();
+        let height = self.texture.height();
+        let format = self.texture.format();
+        let bytes_per_row = padded_bytes_per_row(width);
+
+        let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame capture read buffer"),
+            size: bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        let (tx, mapped) = oneshot::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+
+        CaptureReadback {
+            buffer,
+            mapped,
+            width,
+            height,
+            bytes_per_row,
+            format,
+        }
+    }
+}
+
+/// Captured frame copied for reading, encoded once the GPU is done.
+pub(crate) struct CaptureReadback {
+    buffer: wgpu::Buffer,
+    mapped: oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl CaptureReadback {
+    /// The frame as PNG bytes.
+    pub async fn resolve(self) -> Result<Vec<u8>, CaptureError> {
+        let (width, height) = (self.width, self.height);
+        let pixels = self.resolve_rgba().await?;
+        encode_rgba_png(pixels, width, height)
+    }
+
+    /// The frame as tightly packed RGBA8 rows, top row first.
+    pub async fn resolve_rgba(self) -> Result<Vec<u8>, CaptureError> {
+        self.mapped.await.map_err(|_| CaptureError::Cancelled)??;
+
+        let pixels = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            rgba_pixels(
+                &data,
+                self.width,
+                self.height,
+                self.bytes_per_row,
+                self.format,
+            )
+        };
+        self.buffer.unmap();
+
+        pixels
+    }
+}
+
+/// Rows of 4 byte texels, rounded up to the alignment buffer copies need.
+pub(crate) fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+/// Convert `height` rows of `bytes_per_row` each, with texels in `format`, to RGBA8.
+///
+/// The padding at the end of each row is dropped.
+fn rgba_pixels(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>, CaptureError> {
+    let swap_red_blue = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(CaptureError::UnsupportedFormat(format)),
+    };
+
+    let row_len = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in data.chunks(bytes_per_row as usize).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    if swap_red_blue {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|texel| texel.swap(0, 2));
+    }
+
+    Ok(pixels)
+}
+
+fn encode_rgba_png(pixels: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>, CaptureError> {
+    let image = image::RgbaImage::from_raw(width, height, pixels)
+        .expect("captured rows cover the whole image");
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+
+    Ok(png.into_inner())
+}
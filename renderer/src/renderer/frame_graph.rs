@@ -0,0 +1,304 @@
+//! Minimal frame graph: passes declare the textures they read and write, the graph
+//! orders them, skips passes nothing depends on and allocates transient textures for
+//! just the passes that use them.
+//!
+//! Ordering rules:
+//!
+//! - a pass reading a texture runs after every pass writing it,
+//! - passes writing the same texture run in registration order, so later passes draw
+//!   over earlier ones, e.g. an overlay over the main pass.
+//!
+//! Passes are kept when they write an imported texture, such as the surface, or a
+//! texture read by a kept pass.
+
+use std::collections::BTreeSet;
+
+/// Texture used by frame graph passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+/// Texture allocated by the graph for the passes of one frame, e.g. a shadow map or an
+/// intermediate color target.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TransientTexture {
+    pub label: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TransientTexture {
+    fn create(&self, device: &wgpu::Device) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(self.label),
+            size: wgpu::Extent3d {
+                width: self.width.max(1),
+                height: self.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: self.usage,
+            view_formats: &[],
+        })
+    }
+}
+
+/// Transient textures kept between frames, so the graph does not allocate every frame.
+///
+/// Textures not used during a frame are freed at its end, e.g. after a resize.
+#[derive(Default)]
+pub struct TexturePool {
+    free: Vec<(TransientTexture, wgpu::Texture)>,
+    stale: Vec<(TransientTexture, wgpu::Texture)>,
+}
+
+impl TexturePool {
+    fn begin_frame(&mut self) {
+        self.stale.append(&mut self.free);
+    }
+
+    fn acquire(&mut self, device: &wgpu::Device, desc: &TransientTexture) -> wgpu::Texture {
+        for list in [&mut self.free, &mut self.stale] {
+            if let Some(index) = list.iter().position(|(pooled, _)| pooled == desc) {
+                return list.swap_remove(index).1;
+            }
+        }
+
+        desc.create(device)
+    }
+
+    fn release(&mut self, desc: TransientTexture, texture: wgpu::Texture) {
+        self.free.push((desc, texture));
+    }
+
+    fn end_frame(&mut self) {
+        self.stale.clear();
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FrameGraphError {
+    #[error("frame graph passes depend on each other in a cycle: {0:?}")]
+    Cycle(Vec<&'static str>),
+}
+
+/// Views of the textures declared by the graph, handed to each pass.
+pub struct PassTextures {
+    views: Vec<Option<wgpu::TextureView>>,
+}
+
+impl PassTextures {
+    /// View of `handle`.
+    ///
+    /// Panics if the texture is not alive during the current pass, i.e. the pass did
+    /// not declare it.
+    pub fn view(&self, handle: TextureHandle) -> &wgpu::TextureView {
+        self.views[handle.0]
+            .as_ref()
+            .expect("texture was not declared by the pass")
+    }
+}
+
+enum GraphTexture {
+    Imported(wgpu::TextureView),
+    Transient(TransientTexture),
+}
+
+type PassFn<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder, &PassTextures) + 'a>;
+
+struct PassNode<'a> {
+    name: &'static str,
+    reads: Vec<TextureHandle>,
+    writes: Vec<TextureHandle>,
+    execute: PassFn<'a>,
+}
+
+impl PassNode<'_> {
+    fn uses(&self, texture: TextureHandle) -> bool {
+        self.reads.contains(&texture) || self.writes.contains(&texture)
+    }
+}
+
+/// Passes of one frame, built up and executed into a single command encoder.
+#[derive(Default)]
+pub struct FrameGraph<'a> {
+    textures: Vec<GraphTexture>,
+    passes: Vec<PassNode<'a>>,
+}
+
+impl<'a> FrameGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Texture owned outside the graph, such as the surface or the depth buffer.
+    pub fn import_texture(&mut self, view: &wgpu::TextureView) -> TextureHandle {
+        self.textures.push(GraphTexture::Imported(view.clone()));
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// Texture allocated by the graph, alive from the first to the last pass using it.
+    pub fn create_texture(&mut self, desc: TransientTexture) -> TextureHandle {
+        self.textures.push(GraphTexture::Transient(desc));
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[TextureHandle],
+        writes: &[TextureHandle],
+        execute: impl FnOnce(&mut wgpu::CommandEncoder, &PassTextures) + 'a,
+    ) {
+        self.passes.push(PassNode {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            execute: Box::new(execute),
+        });
+    }
+
+    /// Passes each pass has to wait for, by index.
+    fn dependencies(&self) -> Vec<BTreeSet<usize>> {
+        self.passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let mut dependencies = BTreeSet::new();
+                for (other_index, other) in self.passes.iter().enumerate() {
+                    if other_index == index {
+                        continue;
+                    }
+
+                    let feeds_read = pass.reads.iter().any(|t| other.writes.contains(t));
+                    let earlier_write =
+                        other_index < index && pass.writes.iter().any(|t| other.writes.contains(t));
+                    if feeds_read || earlier_write {
+                        dependencies.insert(other_index);
+                    }
+                }
+                dependencies
+            })
+            .collect()
+    }
+
+    /// Execution order of the passes that contribute to an imported texture.
+    pub fn compile(&self) -> Result<Vec<usize>, FrameGraphError> {
+        let dependencies = self.dependencies();
+
+        let mut live: Vec<bool> = self
+            .passes
+            .iter()
+            .map(|pass| {
+                pass.writes
+                    .iter()
+                    .any(|t| matches!(self.textures[t.0], GraphTexture::Imported(_)))
+            })
+            .collect();
+        let mut pending: Vec<usize> = (0..self.passes.len()).filter(|&i| live[i]).collect();
+        while let Some(index) = pending.pop() {
+            for &dependency in &dependencies[index] {
+                if !live[dependency] {
+                    live[dependency] = true;
+                    pending.push(dependency);
+                }
+            }
+        }
+
+        // Kahn's algorithm, preferring registration order among ready passes.
+        let mut order = Vec::new();
+        let mut done = vec![false; self.passes.len()];
+        let mut remaining: BTreeSet<usize> = (0..self.passes.len()).filter(|&i| live[i]).collect();
+        while let Some(&next) = remaining
+            .iter()
+            .find(|&&i| dependencies[i].iter().all(|&d| done[d] || !live[d]))
+        {
+            remaining.remove(&next);
+            done[next] = true;
+            order.push(next);
+        }
+
+        if !remaining.is_empty() {
+            let names = remaining.iter().map(|&i| self.passes[i].name).collect();
+            return Err(FrameGraphError::Cycle(names));
+        }
+
+        Ok(order)
+    }
+
+    /// Record every live pass into `encoder`.
+    pub fn execute(
+        self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TexturePool,
+    ) -> Result<(), FrameGraphError> {
+        let order = self.compile()?;
+
+        // Position in `order` of the first and last pass using each transient texture.
+        let lifetimes: Vec<Option<(usize, usize)>> = (0..self.textures.len())
+            .map(|texture| {
+                let mut positions = order
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &pass)| self.passes[pass].uses(TextureHandle(texture)))
+                    .map(|(position, _)| position);
+                let first = positions.next()?;
+                Some((first, positions.next_back().unwrap_or(first)))
+            })
+            .collect();
+
+        let mut textures = PassTextures {
+            views: self
+                .textures
+                .iter()
+                .map(|texture| match texture {
+                    GraphTexture::Imported(view) => Some(view.clone()),
+                    GraphTexture::Transient(_) => None,
+                })
+                .collect(),
+        };
+        let mut allocated: Vec<Option<wgpu::Texture>> = vec![None; self.textures.len()];
+        let mut passes: Vec<Option<PassNode>> = self.passes.into_iter().map(Some).collect();
+
+        pool.begin_frame();
+        for (position, &pass_index) in order.iter().enumerate() {
+            for (texture, desc) in self.textures.iter().enumerate() {
+                let GraphTexture::Transient(desc) = desc else {
+                    continue;
+                };
+                if lifetimes[texture].is_some_and(|(first, _)| first == position) {
+                    let allocation = pool.acquire(device, desc);
+                    textures.views[texture] =
+                        Some(allocation.create_view(&wgpu::TextureViewDescriptor::default()));
+                    allocated[texture] = Some(allocation);
+                }
+            }
+
+            if let Some(pass) = passes[pass_index].take() {
+                (pass.execute)(encoder, &textures);
+            }
+
+            // Hand textures back once their last pass is recorded, so later passes can
+            // reuse them. wgpu keeps them alive until the commands have run.
+            for (texture, desc) in self.textures.iter().enumerate() {
+                let GraphTexture::Transient(desc) = desc else {
+                    continue;
+                };
+                if lifetimes[texture].is_some_and(|(_, last)| last == position) {
+                    textures.views[texture] = None;
+                    if let Some(allocation) = allocated[texture].take() {
+                        pool.release(desc.clone(), allocation);
+                    }
+                }
+            }
+        }
+        pool.end_frame();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use ultraviolet::Mat4;
+
+use crate::gltf::ModelBounds;
+
+use super::{
+    scene::{Mesh, MeshBuilder, VertexAttribute, VertexLayoutBuilder},
+    GpuResources, PipelineOptions,
+};
+
+pub const GRID_PIPELINE: &str = "grid";
+
+const LINE_COLOR: [f32; 4] = [0.4, 0.4, 0.4, 1.0];
+const X_AXIS_COLOR: [f32; 4] = [0.8, 0.25, 0.25, 1.0];
+const Z_AXIS_COLOR: [f32; 4] = [0.25, 0.4, 0.8, 1.0];
+
+/// Grid lines across the model, per side of the origin, see `Grid::size_for`.
+const LINES_PER_SIDE: f32 = 10.0;
+
+/// Reference grid of lines on the `y = 0` plane, with the x and z axes highlighted.
+///
+/// It is drawn before the scene meshes but kept apart from them, so loading a model or
+/// picking leaves it alone.
+#[derive(Default)]
+pub struct Grid {
+    // `None` until the grid is built with `Scene::add_grid`
+    mesh: Option<Mesh>,
+    extent: f32,
+    spacing: f32,
+    hidden: bool,
+}
+
+impl Grid {
+    /// Replace the lines with ones reaching `extent` from the origin along x and z,
+    /// `spacing` apart.
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        surface_format: wgpu::TextureFormat,
+        extent: f32,
+        spacing: f32,
+    ) -> Result<(), String> {
+        let pipeline = match resources.get_pipeline(GRID_PIPELINE) {
+            Some(index) => index,
+            None => grid_pipeline(device, resources, surface_format)?,
+        };
+
+        let (positions, colors) = grid_lines(extent, spacing);
+        let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+        let uvs = vec![[0.0, 0.0]; positions.len()];
+
+        let mesh = MeshBuilder::default()
+            .with_vertices(device, resources, &positions, &normals, &uvs)
+            .and_then(|builder| {
+                builder.with_attribute(
+                    device,
+                    resources,
+                    VertexAttribute::Color,
+                    bytemuck::cast_slice(&colors),
+                )
+            })
+            .map_err(|err| err.to_string())?
+            .with_pipeline(pipeline)
+            .with_model_matrix(device, resources, Mat4::identity())
+            .build();
+
+        if let Some(old) = self.mesh.replace(mesh) {
+            resources.remove_mesh_buffers(&old);
+        }
+        self.extent = extent;
+        self.spacing = spacing;
+        Ok(())
+    }
+
+    /// Mesh to draw, `None` while hidden or not built yet.
+    pub fn mesh(&self) -> Option<&Mesh> {
+        self.mesh.as_ref().filter(|_| !self.hidden)
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.mesh.is_some()
+    }
+
+    pub fn is_visible(&self) -> bool {
+        !self.hidden
+    }
+
+    /// Hide the grid without dropping its lines, showing it again is free.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.hidden = !visible;
+    }
+
+    pub fn extent(&self) -> f32 {
+        self.extent
+    }
+
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    /// Extent and spacing of a grid under a model, a round spacing with about
+    /// `LINES_PER_SIDE` lines on each side of the origin.
+    pub fn size_for(bounds: &ModelBounds) -> (f32, f32) {
+        let reach = [bounds.min[0], bounds.max[0], bounds.min[2], bounds.max[2]]
+            .into_iter()
+            .fold(0.0f32, |reach, value| reach.max(value.abs()));
+        // A flat or empty model still gets a unit grid.
+        let reach = if reach > f32::EPSILON { reach } else { 1.0 };
+
+        let spacing = 10f32.powf((reach / LINES_PER_SIDE).log10().ceil());
+        let extent = (reach / spacing).ceil().max(1.0) * spacing;
+        (extent, spacing)
+    }
+}
+
+/// Positions and colors of the `LineList` vertices of a grid.
+pub(crate) fn grid_lines(extent: f32, spacing: f32) -> (Vec<[f32; 3]>, Vec<[f32; 4]>) {
+    let steps = (extent / spacing).floor() as i32;
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+
+    for step in -steps..=steps {
+        let offset = step as f32 * spacing;
+        let (along_z, along_x) = if step == 0 {
+            (Z_AXIS_COLOR, X_AXIS_COLOR)
+        } else {
+            (LINE_COLOR, LINE_COLOR)
+        };
+
+        positions.extend([[offset, 0.0, -extent], [offset, 0.0, extent]]);
+        colors.extend([along_z; 2]);
+        positions.extend([[-extent, 0.0, offset], [extent, 0.0, offset]]);
+        colors.extend([along_x; 2]);
+    }
+
+    (positions, colors)
+}
+
+/// Create the pipeline grids are drawn with. It only binds the camera, scene bind group
+/// 1 like the other built-in pipelines.
+fn grid_pipeline(
+    device: &wgpu::Device,
+    resources: &mut GpuResources,
+    surface_format: wgpu::TextureFormat,
+) -> Result<usize, String> {
+    let layout = VertexLayoutBuilder::new()
+        .with(VertexAttribute::Position)
+        .with(VertexAttribute::Color)
+        .build();
+
+    resources.create_pipeline_with_layout(
+        device,
+        GRID_PIPELINE,
+        &layout,
+        include_str!("./grid.wgsl"),
+        surface_format,
+        PipelineOptions {
+            topology: wgpu::PrimitiveTopology::LineList,
+            cull_mode: None,
+            bind_groups: Some(&[1]),
+            ..Default::default()
+        },
+    )
+}
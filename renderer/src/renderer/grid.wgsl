@@ -0,0 +1,31 @@
+// Only the camera is bound, the scene's bind group 1.
+@group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) model_col0: vec4<f32>,
+    @location(3) model_col1: vec4<f32>,
+    @location(4) model_col2: vec4<f32>,
+    @location(5) model_col3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let model = mat4x4<f32>(in.model_col0, in.model_col1, in.model_col2, in.model_col3);
+
+    var out: VertexOutput;
+    out.clip_position = view_proj * model * vec4<f32>(in.pos, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
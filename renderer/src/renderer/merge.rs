@@ -0,0 +1,413 @@
+use ultraviolet::Mat4;
+use wgpu::util::DeviceExt;
+
+use super::{
+    scene::{BlendMode, Mesh, MeshPasses, VertexAttribute},
+    GpuResources,
+};
+
+/// Invocations per workgroup of the bake shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Vertices or indices one dispatch can cover, larger meshes are left as they are.
+const MAX_ELEMENTS: u32 = u16::MAX as u32 * WORKGROUP_SIZE;
+
+/// Draw calls of the scene before and after `Scene::merge_static_meshes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub draw_calls_before: usize,
+    pub draw_calls_after: usize,
+    /// Meshes folded into merged ones.
+    pub merged_meshes: usize,
+}
+
+/// Meshes drawn to the screen, one draw call each.
+pub(crate) fn draw_calls(meshes: &[Mesh]) -> usize {
+    meshes
+        .iter()
+        .filter(|mesh| mesh.visible && mesh.passes.color())
+        .count()
+}
+
+/// Whether the merge shader can bake `mesh`: a single opaque, indexed, unskinned instance
+/// whose buffers allow being read from.
+fn is_mergeable(mesh: &Mesh, resources: &GpuResources) -> bool {
+    let usage = |buffer: Option<&wgpu::Buffer>, needed| {
+        buffer.is_some_and(|buffer| buffer.usage().contains(needed))
+    };
+    let storage = wgpu::BufferUsages::STORAGE;
+    let copy = wgpu::BufferUsages::COPY_SRC;
+
+    mesh.visible
+        && mesh.passes == MeshPasses::All
+        && mesh.instance_count == 1
+        && mesh.blend_mode == BlendMode::Opaque
+        && mesh.index_count > 0
+        && (1..=MAX_ELEMENTS).contains(&mesh.vertex_count)
+        && mesh.index_count <= MAX_ELEMENTS
+        && !mesh.attribute_buffers.iter().any(|(attribute, _)| {
+            matches!(
+                attribute,
+                VertexAttribute::Joints | VertexAttribute::Weights
+            )
+        })
+        && mesh
+            .index_buffer_index
+            .is_some_and(|index| usage(resources.try_get_buffer(&index), storage))
+        && usage(
+            resources.try_get_buffer(&mesh.position_buffer_index),
+            storage,
+        )
+        && usage(
+            resources.try_get_buffer(&mesh.normal_buffer_index),
+            storage | copy,
+        )
+        && usage(resources.try_get_buffer(&mesh.model_buffer_index), storage)
+        && usage(resources.try_get_buffer(&mesh.uv_buffer_index), copy)
+        && mesh
+            .attribute_buffers
+            .iter()
+            .all(|(_, buffer)| usage(resources.try_get_buffer(buffer), copy))
+}
+
+/// Attributes beyond position, normal and uv, in a stable order to compare meshes by.
+fn extra_attributes(mesh: &Mesh) -> Vec<VertexAttribute> {
+    let mut attributes: Vec<VertexAttribute> = mesh
+        .attribute_buffers
+        .iter()
+        .map(|&(attribute, _)| attribute)
+        .collect();
+    attributes.sort_by_key(|&attribute| attribute as u8);
+    attributes
+}
+
+/// Indices into `meshes` of the meshes to merge together, grouped by pipeline, object
+/// params and vertex attributes. Groups stay within `limits`, only groups of two or more
+/// meshes are returned.
+pub(crate) fn merge_groups(
+    meshes: &[Mesh],
+    resources: &GpuResources,
+    limits: &wgpu::Limits,
+) -> Vec<Vec<usize>> {
+    // Float32x4 is the widest attribute, u32 indices take four bytes each.
+    let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    let max_vertices = max_bytes / 16;
+    let max_indices = max_bytes / 4;
+
+    struct Group {
+        key: (usize, Option<usize>, Vec<VertexAttribute>),
+        meshes: Vec<usize>,
+        vertices: u64,
+        indices: u64,
+    }
+    let mut groups: Vec<Group> = Vec::new();
+
+    for (index, mesh) in meshes.iter().enumerate() {
+        if !is_mergeable(mesh, resources) {
+            continue;
+        }
+
+        let key = (
+            mesh.pipeline_index,
+            mesh.params_index,
+            extra_attributes(mesh),
+        );
+        let (vertices, indices) = (mesh.vertex_count as u64, mesh.index_count as u64);
+        let group = groups.iter_mut().rev().find(|group| {
+            group.key == key
+                && group.vertices + vertices <= max_vertices
+                && group.indices + indices <= max_indices
+        });
+        match group {
+            Some(group) => {
+                group.meshes.push(index);
+                group.vertices += vertices;
+                group.indices += indices;
+            }
+            None => groups.push(Group {
+                key,
+                meshes: vec![index],
+                vertices,
+                indices,
+            }),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| group.meshes.len() > 1)
+        .map(|group| group.meshes)
+        .collect()
+}
+
+/// Compute pipelines copying meshes into shared buffers with their model matrices baked
+/// into the positions and normals, see `merge.wgsl`. Normals of pipelines with
+/// `PipelineOptions::world_space_normals` are copied as they are.
+pub(crate) struct MeshMerger {
+    layout: wgpu::BindGroupLayout,
+    bake_positions: wgpu::ComputePipeline,
+    bake_normals: wgpu::ComputePipeline,
+    rebase_indices: wgpu::ComputePipeline,
+}
+
+impl MeshMerger {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mesh merge layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, true),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mesh merge pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mesh merge shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./merge.wgsl").into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            bake_positions: pipeline("bake_positions"),
+            bake_normals: pipeline("bake_normals"),
+            rebase_indices: pipeline("rebase_indices"),
+            layout,
+        }
+    }
+
+    /// One mesh drawing all of `meshes` in world space, with an identity model matrix and
+    /// 32-bit indices. The meshes must come from the same group of `merge_groups`.
+    pub fn merge(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resources: &mut GpuResources,
+        meshes: &[&Mesh],
+    ) -> Mesh {
+        let first = meshes[0];
+        let world_space_normals = resources
+            .pipeline_options(first.pipeline_index)
+            .world_space_normals;
+        let vertex_count: u32 = meshes.iter().map(|mesh| mesh.vertex_count).sum();
+        let index_count: u32 = meshes.iter().map(|mesh| mesh.index_count).sum();
+
+        let vertex_buffer = |label, stride: u64| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: vertex_count as u64 * stride,
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let positions = vertex_buffer("Merged Positions", 12);
+        let normals = vertex_buffer("Merged Normals", 12);
+        let uvs = vertex_buffer("Merged UVs", 8);
+        let attributes: Vec<(VertexAttribute, wgpu::Buffer)> = extra_attributes(first)
+            .into_iter()
+            .map(|attribute| {
+                let buffer = vertex_buffer("Merged Attribute", attribute.format().size());
+                (attribute, buffer)
+            })
+            .collect();
+        let indices = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Merged Indices"),
+            size: index_count as u64 * 4,
+            usage: wgpu::BufferUsages::INDEX
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mesh merge encoder"),
+        });
+        let (mut vertex_offset, mut index_offset) = (0u32, 0u32);
+        for mesh in meshes {
+            let u16_indices = mesh.index_format == wgpu::IndexFormat::Uint16;
+            let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("mesh merge params"),
+                contents: bytemuck::cast_slice(&[
+                    mesh.vertex_count,
+                    mesh.index_count,
+                    vertex_offset,
+                    index_offset,
+                    u16_indices as u32,
+                    0,
+                    0,
+                    0,
+                ]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let model = resources.get_buffer(&mesh.model_buffer_index);
+            let bind_group = |source: &wgpu::Buffer, merged: &wgpu::Buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Mesh merge bind group"),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: params.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: source.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: merged.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: model.as_entire_binding(),
+                        },
+                    ],
+                })
+            };
+
+            let index_buffer = mesh.index_buffer_index.expect("merged meshes have indices");
+            let mut passes = vec![
+                (
+                    &self.bake_positions,
+                    bind_group(
+                        resources.get_buffer(&mesh.position_buffer_index),
+                        &positions,
+                    ),
+                    mesh.vertex_count,
+                ),
+                (
+                    &self.rebase_indices,
+                    bind_group(resources.get_buffer(&index_buffer), &indices),
+                    mesh.index_count,
+                ),
+            ];
+            if !world_space_normals {
+                passes.push((
+                    &self.bake_normals,
+                    bind_group(resources.get_buffer(&mesh.normal_buffer_index), &normals),
+                    mesh.vertex_count,
+                ));
+            }
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Mesh merge pass"),
+                    timestamp_writes: None,
+                });
+                for (pipeline, bind_group, count) in &passes {
+                    pass.set_pipeline(pipeline);
+                    pass.set_bind_group(0, bind_group, &[]);
+                    pass.dispatch_workgroups(count.div_ceil(WORKGROUP_SIZE), 1, 1);
+                }
+            }
+
+            // Uvs and the other attributes do not depend on the transform.
+            let vertex_offset_bytes = |stride: u64| vertex_offset as u64 * stride;
+            if world_space_normals {
+                encoder.copy_buffer_to_buffer(
+                    resources.get_buffer(&mesh.normal_buffer_index),
+                    0,
+                    &normals,
+                    vertex_offset_bytes(12),
+                    mesh.vertex_count as u64 * 12,
+                );
+            }
+            encoder.copy_buffer_to_buffer(
+                resources.get_buffer(&mesh.uv_buffer_index),
+                0,
+                &uvs,
+                vertex_offset_bytes(8),
+                mesh.vertex_count as u64 * 8,
+            );
+            for (attribute, merged) in &attributes {
+                let source = mesh
+                    .vertex_buffer_index(*attribute)
+                    .expect("merged meshes share their attributes");
+                let stride = attribute.format().size();
+                encoder.copy_buffer_to_buffer(
+                    resources.get_buffer(&source),
+                    0,
+                    merged,
+                    vertex_offset_bytes(stride),
+                    mesh.vertex_count as u64 * stride,
+                );
+            }
+
+            vertex_offset += mesh.vertex_count;
+            index_offset += mesh.index_count;
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let model = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Merged Model Matrix"),
+            contents: bytemuck::cast_slice(Mat4::identity().as_slice()),
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::STORAGE,
+        });
+        let bounds = meshes
+            .iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(|union, bounds| Some(union?.union(&bounds?)))
+            .flatten();
+
+        Mesh {
+            pipeline_index: first.pipeline_index,
+            position_buffer_index: resources.add_position_buffer(positions),
+            normal_buffer_index: resources.add_normal_buffer(normals),
+            uv_buffer_index: resources.add_uv_buffer(uvs),
+            attribute_buffers: attributes
+                .into_iter()
+                .map(|(attribute, buffer)| (attribute, resources.add_attribute_buffer(buffer)))
+                .collect(),
+            model_buffer_index: resources.add_model_matrix_buffer(model),
+            index_buffer_index: Some(resources.add_index_buffer(indices)),
+            index_format: wgpu::IndexFormat::Uint32,
+            index_count,
+            vertex_count,
+            instance_count: 1,
+            params_index: first.params_index,
+            blend_mode: BlendMode::Opaque,
+            origin: bounds.map_or(first.origin, |bounds| bounds.center()),
+            name: None,
+            visible: true,
+            passes: MeshPasses::All,
+            bounds,
+        }
+    }
+}
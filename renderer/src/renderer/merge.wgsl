@@ -0,0 +1,75 @@
+// Bakes one source mesh into the merged buffers at an offset, one invocation per
+// vertex or index.
+struct Params {
+    vertex_count: u32,
+    index_count: u32,
+    // Where the mesh starts in the merged buffers, in vertices and in indices
+    vertex_offset: u32,
+    index_offset: u32,
+    // Whether the source indices are packed two `u16`s to a word
+    u16_indices: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> source: array<u32>;
+@group(0) @binding(2) var<storage, read_write> merged: array<u32>;
+// The mesh's model matrix, the first instance of its instance buffer
+@group(0) @binding(3) var<storage, read> models: array<mat4x4<f32>>;
+
+fn read_vec3(index: u32) -> vec3<f32> {
+    return vec3<f32>(
+        bitcast<f32>(source[index * 3u]),
+        bitcast<f32>(source[index * 3u + 1u]),
+        bitcast<f32>(source[index * 3u + 2u]),
+    );
+}
+
+fn write_vec3(index: u32, value: vec3<f32>) {
+    let offset = (params.vertex_offset + index) * 3u;
+    merged[offset] = bitcast<u32>(value.x);
+    merged[offset + 1u] = bitcast<u32>(value.y);
+    merged[offset + 2u] = bitcast<u32>(value.z);
+}
+
+@compute @workgroup_size(64)
+fn bake_positions(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.vertex_count {
+        return;
+    }
+    let world = models[0] * vec4<f32>(read_vec3(id.x), 1.0);
+    write_vec3(id.x, world.xyz);
+}
+
+@compute @workgroup_size(64)
+fn bake_normals(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.vertex_count {
+        return;
+    }
+
+    // Cofactors of the upper 3x3, the inverse transpose up to its determinant's scale.
+    let model = models[0];
+    let x = model[0].xyz;
+    let y = model[1].xyz;
+    let z = model[2].xyz;
+    let cofactors = mat3x3<f32>(cross(y, z), cross(z, x), cross(x, y));
+    let flip = select(1.0, -1.0, dot(x, cross(y, z)) < 0.0);
+
+    let normal = cofactors * read_vec3(id.x) * flip;
+    let magnitude = length(normal);
+    write_vec3(id.x, select(normal, normal / magnitude, magnitude > 0.0));
+}
+
+@compute @workgroup_size(64)
+fn rebase_indices(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.index_count {
+        return;
+    }
+
+    var index: u32;
+    if params.u16_indices != 0u {
+        index = (source[id.x / 2u] >> ((id.x % 2u) * 16u)) & 0xffffu;
+    } else {
+        index = source[id.x];
+    }
+    merged[params.index_offset + id.x] = index + params.vertex_offset;
+}
@@ -1,6 +1,7 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    future::Future,
     marker::PhantomData,
     rc::Rc,
     sync::mpsc::Receiver,
@@ -8,32 +9,98 @@ use std::{
 
 use futures::channel::oneshot;
 use log::info;
-use ultraviolet::Vec4;
+use ultraviolet::{Mat4, Vec2, Vec3, Vec4};
 use wasm_bindgen::{prelude::Closure, JsCast};
 use wasm_bindgen_futures::{spawn_local, JsFuture};
 use web_sys::{DedicatedWorkerGlobalScope, File, MessageEvent};
+use wgpu::util::DeviceExt;
 
 use crate::{
-    gltf::{load_gltf_model, ImportError, ModelBounds},
-    message::{DrainEventError, MouseMessage, ResizeMessage, WindowEvent},
+    camera::{fly_direction, Camera, CameraMode, Frustum},
+    gltf::{
+        fetch_model, parse_gltf_with_buffers, ExternalBuffers, ImportError, LoadProgress,
+        ModelDecoder, ModelFormat, ModelMaterials, ModelUploader, DEFAULT_MODEL_URL,
+    },
+    message::{
+        DrainEventError, MouseMessage, ResizeMessage, WheelMessage, WindowEvent, WorkerMessage,
+    },
     renderer::scene::Scene,
 };
 
+pub mod background;
+pub mod capture;
+pub mod frame_graph;
+pub mod grid;
+pub mod merge;
+pub mod overlay;
+pub mod picking;
 pub mod scene;
+pub mod scene_graph;
 
 // Re-export commonly used types
-pub use scene::Mesh;
+pub use background::{Background, CubeHandle};
+pub use capture::CaptureError;
+pub use grid::Grid;
+pub use overlay::{Rect, Sprite, SpriteBatch, SpriteTexture};
+pub use scene::{
+    AttachmentLoad, BlendMode, Light, LightUniform, Mesh, MeshId, MeshPasses, MeshStore,
+    ObjectParams, SceneError, VertexAttribute, VertexLayout, VertexLayoutBuilder,
+};
+pub use scene_graph::{NodeId, SceneGraph, SceneNode};
+
+use background::BackgroundPass;
+use capture::CaptureTarget;
+use frame_graph::{FrameGraph, TexturePool};
+use overlay::{DepthPreview, OverlayPass};
+use picking::{PickReadback, PickingPass};
+
+pub(crate) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Largest zoom applied in one frame, in wheel pixels (about two mouse wheel notches).
+const DEFAULT_MAX_ZOOM_STEP: f64 = 240.0;
+
+/// How early a paced frame may render, in milliseconds, so animation frame timing jitter
+/// does not drop it to the next vsync and halve the frame rate.
+const FRAME_PACING_SLACK_MS: f32 = 2.0;
+
+/// wgpu's cap on bind groups in a pipeline layout, whatever the device limits.
+const MAX_BIND_GROUPS: usize = 8;
+
+/// Fly camera speed in world units per second.
+const DEFAULT_FLY_SPEED: f32 = 5.0;
+
+/// Longest frame a fly or damping step covers in seconds, so the camera does not jump
+/// after the page was in the background.
+const MAX_FLY_STEP: f32 = 0.1;
 
-const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Share of the surface width and height covered by the depth debug preview.
+const DEPTH_PREVIEW_SCALE: f32 = 0.25;
+/// Gap between the depth debug preview and the surface edges, in physical pixels.
+const DEPTH_PREVIEW_MARGIN: f32 = 16.0;
+
+/// Primitives uploaded between two frames while loading a model, so the render loop keeps
+/// drawing during large loads.
+const PRIMITIVES_PER_FRAME: usize = 16;
+
+/// Name of the scene the renderer starts with, see `Renderer::set_active_scene`.
+pub const DEFAULT_SCENE: &str = "main";
+
+/// Animation frame callback of the render loop, shared with itself to request the next frame.
+type FrameCallback = Rc<RefCell<Option<Closure<dyn FnMut(f32)>>>>;
 
 pub struct GpuResources {
-    // Core resources
-    buffers: Vec<wgpu::Buffer>,
+    // Core resources. Removed buffers leave an empty slot, reused by the next buffer
+    // added, with a new generation so stale `BufferIndex`es don't resolve
+    buffers: Vec<BufferSlot>,
+    free_buffer_slots: Vec<u32>,
     pipelines: Vec<wgpu::RenderPipeline>,
-    textures: Vec<wgpu::Texture>,
+    // Freed textures leave an empty slot the same way, reused by the next `add_texture`
+    textures: Vec<TextureSlot>,
+    free_texture_slots: Vec<u32>,
 
-    // Layout management
-    pipeline_layouts: Vec<wgpu::PipelineLayout>,
+    // Layout management, keyed by the scene layouts a pipeline binds and indexed by
+    // whether its object params group carries a material
+    pipeline_layouts: [HashMap<Vec<usize>, wgpu::PipelineLayout>; 2],
     bind_group_layouts: Vec<wgpu::BindGroupLayout>,
 
     // Simple name-based pipeline lookup
@@ -41,69 +108,256 @@ pub struct GpuResources {
 
     // Shader modules cache
     shader_modules: HashMap<String, wgpu::ShaderModule>,
+
+    // What each registered pipeline was built from, so variants can be derived later
+    pipeline_sources: HashMap<usize, PipelineSource>,
+    pipeline_variants: HashMap<(usize, PipelineVariant), usize>,
+    // Depth is cleared to 0 and tested with `GreaterEqual` instead of 1 and `LessEqual`
+    reverse_z: bool,
+
+    // Sampler shared by every texture
+    sampler_config: SamplerConfig,
+    default_sampler: Option<wgpu::Sampler>,
+    anisotropic_filtering: bool,
+    // Whether the device was created with `POLYGON_MODE_LINE`, needed for wireframes
+    polygon_mode_line: bool,
+
+    // Per-object params, bound after the scene's bind groups
+    object_params_layout: Option<wgpu::BindGroupLayout>,
+    default_object_params: Option<wgpu::BindGroup>,
+    // Removed params leave an empty slot, meshes may still hold their index
+    object_params: Vec<Option<(wgpu::Buffer, wgpu::BindGroup)>>,
+
+    // Params with a base color texture, bound instead of object params by textured pipelines
+    material_layout: Option<wgpu::BindGroupLayout>,
+    default_material: Option<wgpu::BindGroup>,
+    white_texture: Option<TextureIndex>,
+    // Base color view of each material in `object_params`, to rebind them with a new
+    // sampler
+    material_views: HashMap<usize, wgpu::TextureView>,
+
+    // Bytes of every buffer and texture added so far, minus removed buffers and freed
+    // textures
+    memory_usage: GpuMemoryUsage,
 }
 
 impl GpuResources {
     pub fn new() -> Self {
         Self {
             buffers: Vec::new(),
+            free_buffer_slots: Vec::new(),
             pipelines: Vec::new(),
             textures: Vec::new(),
-            pipeline_layouts: Vec::new(),
+            free_texture_slots: Vec::new(),
+            pipeline_layouts: Default::default(),
             bind_group_layouts: Vec::new(),
             pipeline_registry: HashMap::new(),
             shader_modules: HashMap::new(),
+            pipeline_sources: HashMap::new(),
+            pipeline_variants: HashMap::new(),
+            reverse_z: false,
+            sampler_config: SamplerConfig::default(),
+            default_sampler: None,
+            anisotropic_filtering: false,
+            polygon_mode_line: false,
+            object_params_layout: None,
+            default_object_params: None,
+            object_params: Vec::new(),
+            material_layout: None,
+            default_material: None,
+            material_views: HashMap::new(),
+            white_texture: None,
+            memory_usage: GpuMemoryUsage::default(),
         }
     }
 
     pub fn add_position_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<Position> {
-        let index = self.buffers.len() as u32;
-        self.buffers.push(buffer);
-        BufferIndex {
-            index,
-            _buffer_type: PhantomData,
-        }
+        self.insert_buffer(buffer, false)
     }
 
     pub fn add_normal_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<Normal> {
-        let index = self.buffers.len() as u32;
-        self.buffers.push(buffer);
-        BufferIndex {
-            index,
-            _buffer_type: PhantomData,
-        }
+        self.insert_buffer(buffer, false)
     }
 
     pub fn add_uv_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<UV> {
-        let index = self.buffers.len() as u32;
-        self.buffers.push(buffer);
-        BufferIndex {
-            index,
-            _buffer_type: PhantomData,
-        }
+        self.insert_buffer(buffer, false)
     }
 
     pub fn add_index_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<Index> {
-        let index = self.buffers.len() as u32;
-        self.buffers.push(buffer);
-        BufferIndex {
-            index,
-            _buffer_type: PhantomData,
-        }
+        self.insert_buffer(buffer, true)
     }
 
     pub fn add_model_matrix_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<ModelMatrix> {
-        let index = self.buffers.len() as u32;
-        self.buffers.push(buffer);
+        self.insert_buffer(buffer, false)
+    }
+
+    pub fn add_attribute_buffer(&mut self, buffer: wgpu::Buffer) -> BufferIndex<AttributeData> {
+        self.insert_buffer(buffer, false)
+    }
+
+    fn insert_buffer<T>(&mut self, buffer: wgpu::Buffer, index_data: bool) -> BufferIndex<T> {
+        if index_data {
+            self.memory_usage.index += buffer.size();
+        } else {
+            self.memory_usage.vertex += buffer.size();
+        }
+
+        let index = match self.free_buffer_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.buffers.push(BufferSlot::default());
+                self.buffers.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.buffers[index as usize];
+        slot.buffer = Some(buffer);
+        slot.index_data = index_data;
+
         BufferIndex {
             index,
+            generation: slot.generation,
             _buffer_type: PhantomData,
         }
     }
 
+    /// Panics if the buffer was removed.
     #[inline(always)]
     pub fn get_buffer<T>(&self, id: &BufferIndex<T>) -> &wgpu::Buffer {
-        &self.buffers[id.index as usize]
+        self.try_get_buffer(id)
+            .unwrap_or_else(|| panic!("buffer {} was removed", id.index))
+    }
+
+    /// The buffer behind `id`, `None` once it was removed, even if its slot was reused.
+    pub fn try_get_buffer<T>(&self, id: &BufferIndex<T>) -> Option<&wgpu::Buffer> {
+        self.buffers
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .buffer
+            .as_ref()
+    }
+
+    /// Remove a buffer, handing it back so the caller decides when it is dropped.
+    ///
+    /// Its slot is reused by later buffers, `id` and its copies then resolve to nothing.
+    pub fn remove_buffer<T>(&mut self, id: BufferIndex<T>) -> Option<wgpu::Buffer> {
+        let slot = self
+            .buffers
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let buffer = slot.buffer.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_buffer_slots.push(id.index);
+
+        if slot.index_data {
+            self.memory_usage.index -= buffer.size();
+        } else {
+            self.memory_usage.vertex -= buffer.size();
+        }
+        Some(buffer)
+    }
+
+    /// Remove every vertex, index and model matrix buffer of `mesh`, once it is out of
+    /// the scene for good.
+    pub fn remove_mesh_buffers(&mut self, mesh: &Mesh) {
+        self.remove_buffer(mesh.position_buffer_index);
+        self.remove_buffer(mesh.normal_buffer_index);
+        self.remove_buffer(mesh.uv_buffer_index);
+        self.remove_buffer(mesh.model_buffer_index);
+        for &(_, buffer) in &mesh.attribute_buffers {
+            self.remove_buffer(buffer);
+        }
+        if let Some(buffer) = mesh.index_buffer_index {
+            self.remove_buffer(buffer);
+        }
+    }
+
+    pub fn add_texture(&mut self, texture: wgpu::Texture) -> TextureIndex {
+        self.memory_usage.texture += texture_byte_size(&texture);
+        let index = match self.free_texture_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.textures.push(TextureSlot::default());
+                self.textures.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.textures[index as usize];
+        slot.texture = Some(texture);
+
+        TextureIndex {
+            index,
+            generation: slot.generation,
+            _texture_type: PhantomData,
+        }
+    }
+
+    /// Create an sRGB color texture from tightly packed RGBA8 pixels and add it.
+    ///
+    /// Panics if `rgba` does not hold exactly `width * height` texels.
+    pub fn upload_texture_2d(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) -> TextureIndex {
+        assert_eq!(
+            rgba.len(),
+            width as usize * height as usize * 4,
+            "{width}x{height} texture data must be RGBA8"
+        );
+
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Texture 2d"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            rgba,
+        );
+        self.add_texture(texture)
+    }
+
+    /// Panics if the texture was freed.
+    #[inline(always)]
+    pub fn get_texture<T>(&self, id: &TextureIndex<T>) -> &wgpu::Texture {
+        self.try_get_texture(id)
+            .unwrap_or_else(|| panic!("texture {} was freed", id.index))
+    }
+
+    /// The texture behind `id`, `None` once it was freed, even if its slot was reused.
+    pub fn try_get_texture<T>(&self, id: &TextureIndex<T>) -> Option<&wgpu::Texture> {
+        self.textures
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .texture
+            .as_ref()
+    }
+
+    /// Remove a texture, handing it back so the caller decides when it is dropped.
+    ///
+    /// Its slot is reused by later textures, `id` and its copies then resolve to nothing.
+    pub fn free_texture<T>(&mut self, id: TextureIndex<T>) -> Option<wgpu::Texture> {
+        let slot = self
+            .textures
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let texture = slot.texture.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_texture_slots.push(id.index);
+        self.memory_usage.texture -= texture_byte_size(&texture);
+        Some(texture)
     }
 
     pub fn create_pipeline(
@@ -113,46 +367,212 @@ impl GpuResources {
         vertex_layout: &[wgpu::VertexBufferLayout],
         shader_source: &str,
         surface_format: wgpu::TextureFormat,
+    ) -> Result<usize, String> {
+        self.create_pipeline_with_options(
+            device,
+            name,
+            vertex_layout,
+            shader_source,
+            surface_format,
+            PipelineOptions::default(),
+        )
+    }
+
+    /// Like `create_pipeline`, with non-default topology, culling or blending.
+    pub fn create_pipeline_with_options(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        vertex_layout: &[wgpu::VertexBufferLayout],
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+        options: PipelineOptions,
+    ) -> Result<usize, String> {
+        self.register_pipeline(
+            device,
+            name,
+            vertex_layout,
+            VertexAttribute::STANDARD.to_vec(),
+            shader_source,
+            surface_format,
+            options,
+        )
+    }
+
+    /// Create a pipeline reading the attributes of `layout`, meshes drawn with it bind
+    /// their buffers for those attributes in the same order.
+    pub fn create_pipeline_with_layout(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        layout: &VertexLayout,
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+        options: PipelineOptions,
+    ) -> Result<usize, String> {
+        self.register_pipeline(
+            device,
+            name,
+            &layout.buffer_layouts(),
+            layout.attributes().to_vec(),
+            shader_source,
+            surface_format,
+            options,
+        )
+    }
+
+    fn register_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        vertex_layout: &[wgpu::VertexBufferLayout],
+        attributes: Vec<VertexAttribute>,
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+        options: PipelineOptions,
     ) -> Result<usize, String> {
         if self.pipeline_registry.contains_key(name) {
             return Err(format!("Pipeline '{}' already exists", name));
         }
+        if let Some(&index) = options.bind_groups.and_then(|groups| {
+            groups
+                .iter()
+                .find(|&&index| index >= self.bind_group_layouts.len())
+        }) {
+            return Err(format!(
+                "Pipeline '{}' uses bind group layout {} but only {} are set",
+                name,
+                index,
+                self.bind_group_layouts.len()
+            ));
+        }
+        let bind_groups = self.bind_groups_of(&options);
+        if bind_groups.len() >= MAX_BIND_GROUPS {
+            return Err(format!(
+                "Pipeline '{}' binds {} scene groups but at most {} fit before its object params",
+                name,
+                bind_groups.len(),
+                MAX_BIND_GROUPS - 1
+            ));
+        }
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(name),
             source: wgpu::ShaderSource::Wgsl(shader_source.into()),
         });
-
-        let layout = self.get_or_create_pipeline_layout(device, name);
+        self.shader_modules.insert(name.to_string(), shader);
 
         // Determine entry points based on pipeline name
         let (vertex_entry, fragment_entry) = match name {
             "triangle_colored" => ("v_main", "f_main"),
+            "gltf_unlit" | "gltf_lines_unlit" => ("vs_main", "fs_unlit"),
             _ => ("vs_main", "fs_main"),
         };
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(name),
+        let source = PipelineSource {
+            name: name.to_string(),
+            vertex_entry,
+            fragment_entry,
+            vertex_buffers: vertex_layout
+                .iter()
+                .map(OwnedVertexBufferLayout::from)
+                .collect(),
+            attributes,
+            surface_format,
+            bind_groups,
+            options,
+        };
+
+        let pipeline = self.build_pipeline(device, &source, None);
+
+        let index = self.pipelines.len();
+        self.pipelines.push(pipeline);
+        self.pipeline_registry.insert(name.to_string(), index);
+        self.pipeline_sources.insert(index, source);
+
+        Ok(index)
+    }
+
+    fn build_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        source: &PipelineSource,
+        variant: Option<PipelineVariant>,
+    ) -> wgpu::RenderPipeline {
+        let layout = self.get_or_create_pipeline_layout(device, source);
+        let shader = &self.shader_modules[&source.name];
+        let vertex_buffers: Vec<_> = source
+            .vertex_buffers
+            .iter()
+            .map(OwnedVertexBufferLayout::as_layout)
+            .collect();
+
+        let label = match variant {
+            Some(variant) => format!("{}_{:?}", source.name, variant),
+            None => source.name.clone(),
+        };
+
+        let closer = if self.reverse_z {
+            wgpu::CompareFunction::GreaterEqual
+        } else {
+            wgpu::CompareFunction::LessEqual
+        };
+        let (depth_write_enabled, depth_compare) = match variant {
+            Some(PipelineVariant::DepthEqual) => (false, wgpu::CompareFunction::Equal),
+            // Blended meshes must not hide the ones behind them drawn later.
+            Some(PipelineVariant::Blend) => (false, closer),
+            _ => (true, closer),
+        };
+        let blend = match variant {
+            Some(PipelineVariant::Blend) => wgpu::BlendState::ALPHA_BLENDING,
+            _ => source.options.blend,
+        };
+
+        // Wireframes show the back edges too.
+        let (polygon_mode, cull_mode) = match variant {
+            Some(PipelineVariant::Wireframe) => (wgpu::PolygonMode::Line, None),
+            _ => (source.options.polygon_mode, source.options.cull_mode),
+        };
+
+        let color_targets = [Some(wgpu::ColorTargetState {
+            format: source.surface_format,
+            blend: Some(blend),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+
+        // The depth prepass only rasterizes depth, so it skips fragment shading entirely.
+        let fragment = match variant {
+            Some(PipelineVariant::DepthPrepass) => None,
+            _ => Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(source.fragment_entry),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &color_targets,
+            }),
+        };
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&label),
             layout: Some(&layout),
             vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some(vertex_entry),
+                module: shader,
+                entry_point: Some(source.vertex_entry),
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: vertex_layout,
+                buffers: &vertex_buffers,
             },
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: source.options.topology,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                cull_mode,
                 unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
+                polygon_mode,
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
+                depth_write_enabled,
+                depth_compare,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -161,25 +581,187 @@ impl GpuResources {
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some(fragment_entry),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
+            fragment,
             multiview: None,
             cache: None,
-        });
+        })
+    }
 
-        let index = self.pipelines.len();
+    /// Index of `variant` of the pipeline at `index`, building it on first use.
+    pub fn get_or_create_pipeline_variant(
+        &mut self,
+        device: &wgpu::Device,
+        index: usize,
+        variant: PipelineVariant,
+    ) -> usize {
+        if let Some(variant_index) = self.pipeline_variant(index, variant) {
+            return variant_index;
+        }
+
+        let source = self.pipeline_sources[&index].clone();
+        let pipeline = self.build_pipeline(device, &source, Some(variant));
+
+        let variant_index = self.pipelines.len();
         self.pipelines.push(pipeline);
-        self.pipeline_registry.insert(name.to_string(), index);
+        self.pipeline_variants
+            .insert((index, variant), variant_index);
 
-        Ok(index)
+        variant_index
+    }
+
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// Value the depth buffer is cleared to, the farthest depth.
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Switch the depth test of every pipeline to reverse-Z or back, rebuilding the
+    /// pipelines and variants created so far in place so their indices stay valid.
+    pub fn set_reverse_z(&mut self, device: &wgpu::Device, enabled: bool) {
+        if self.reverse_z == enabled {
+            return;
+        }
+        self.reverse_z = enabled;
+
+        let mut builds: Vec<(usize, usize, Option<PipelineVariant>)> = self
+            .pipeline_sources
+            .keys()
+            .map(|&index| (index, index, None))
+            .collect();
+        builds.extend(
+            self.pipeline_variants
+                .iter()
+                .map(|(&(index, variant), &variant_index)| (variant_index, index, Some(variant))),
+        );
+
+        for (target, index, variant) in builds {
+            let source = self.pipeline_sources[&index].clone();
+            self.pipelines[target] = self.build_pipeline(device, &source, variant);
+        }
+    }
+
+    /// Recompile the pipeline registered as `name` and its variants from new WGSL,
+    /// keeping their indices.
+    ///
+    /// The old pipelines stay in place when the shader fails to compile or validate, so a
+    /// broken edit never breaks the frame.
+    pub async fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        shader_source: &str,
+    ) -> Result<(), String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let reload = self.rebuild_pipeline(device, name, shader_source);
+        let error = device.pop_error_scope().await;
+
+        let reload = reload?;
+        match error {
+            Some(error) => Err(format!(
+                "Shader for pipeline '{}' is invalid: {}",
+                name, error
+            )),
+            None => {
+                self.apply_pipeline_reload(reload);
+                Ok(())
+            }
+        }
+    }
+
+    /// Build the pipelines for `reload_pipeline` without putting them in use.
+    ///
+    /// Validation errors are only reported asynchronously, callers that cannot hold
+    /// `self` across an await wrap this in an error scope and apply the result with
+    /// `apply_pipeline_reload` once the scope comes back clean.
+    pub fn rebuild_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        shader_source: &str,
+    ) -> Result<PipelineReload, String> {
+        let index = self
+            .get_pipeline(name)
+            .ok_or_else(|| format!("Pipeline '{}' does not exist", name))?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(name),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        // Pipelines are built from the registered module, so swap the new one in while
+        // building and restore the old one until the reload is applied.
+        let previous = self.shader_modules.insert(name.to_string(), shader);
+
+        let source = self.pipeline_sources[&index].clone();
+        let mut pipelines = vec![(index, self.build_pipeline(device, &source, None))];
+        let variants: Vec<(PipelineVariant, usize)> = self
+            .pipeline_variants
+            .iter()
+            .filter(|((base, _), _)| *base == index)
+            .map(|(&(_, variant), &variant_index)| (variant, variant_index))
+            .collect();
+        for (variant, variant_index) in variants {
+            let pipeline = self.build_pipeline(device, &source, Some(variant));
+            pipelines.push((variant_index, pipeline));
+        }
+
+        let shader = match previous {
+            Some(previous) => self.shader_modules.insert(name.to_string(), previous),
+            None => self.shader_modules.remove(name),
+        };
+
+        Ok(PipelineReload {
+            name: name.to_string(),
+            shader: shader.expect("the new shader module was just inserted"),
+            pipelines,
+        })
+    }
+
+    /// Put pipelines from `rebuild_pipeline` in place of the ones they were built for.
+    pub fn apply_pipeline_reload(&mut self, reload: PipelineReload) {
+        self.shader_modules.insert(reload.name, reload.shader);
+        for (index, pipeline) in reload.pipelines {
+            self.pipelines[index] = pipeline;
+        }
+    }
+
+    /// Mesh attributes the pipeline at `index` reads, in vertex buffer slot order.
+    ///
+    /// Variants read the same attributes as the pipeline they were built from.
+    pub fn pipeline_attributes(&self, index: usize) -> &[VertexAttribute] {
+        self.pipeline_sources
+            .get(&index)
+            .map_or(&VertexAttribute::STANDARD, |source| &source.attributes)
+    }
+
+    /// Index of an already built variant of the pipeline at `index`.
+    pub fn pipeline_variant(&self, index: usize, variant: PipelineVariant) -> Option<usize> {
+        self.pipeline_variants.get(&(index, variant)).copied()
+    }
+
+    /// Options a registered pipeline or one of its variants was created with.
+    pub(crate) fn pipeline_options(&self, index: usize) -> PipelineOptions {
+        self.pipeline_source(index)
+            .map_or(PipelineOptions::default(), |source| source.options)
+    }
+
+    /// Source of a registered pipeline or of the pipeline a variant was built from.
+    fn pipeline_source(&self, index: usize) -> Option<&PipelineSource> {
+        if let Some(source) = self.pipeline_sources.get(&index) {
+            return Some(source);
+        }
+
+        self.pipeline_variants
+            .iter()
+            .find_map(|(&(source, _), &variant)| (variant == index).then_some(source))
+            .and_then(|source| self.pipeline_sources.get(&source))
     }
 
     pub fn get_pipeline(&self, name: &str) -> Option<usize> {
@@ -193,88 +775,897 @@ impl GpuResources {
         vertex_layout: &[wgpu::VertexBufferLayout],
         shader_source: &str,
         surface_format: wgpu::TextureFormat,
+    ) -> usize {
+        self.try_get_or_create_pipeline(device, name, vertex_layout, shader_source, surface_format)
+            .expect(&format!("Failed to create pipeline '{}'", name))
+    }
+
+    /// Like `get_or_create_pipeline`, returning why the pipeline could not be created
+    /// instead of panicking.
+    pub fn try_get_or_create_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        vertex_layout: &[wgpu::VertexBufferLayout],
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+    ) -> Result<usize, String> {
+        match self.get_pipeline(name) {
+            Some(index) => Ok(index),
+            None => {
+                self.create_pipeline(device, name, vertex_layout, shader_source, surface_format)
+            }
+        }
+    }
+
+    /// Like `get_or_create_pipeline`, `options` only apply when the pipeline is created.
+    pub fn get_or_create_pipeline_with_options(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        vertex_layout: &[wgpu::VertexBufferLayout],
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+        options: PipelineOptions,
     ) -> usize {
         if let Some(index) = self.get_pipeline(name) {
             return index;
         }
 
-        self.create_pipeline(device, name, vertex_layout, shader_source, surface_format)
-            .expect(&format!("Failed to create pipeline '{}'", name))
+        self.create_pipeline_with_options(
+            device,
+            name,
+            vertex_layout,
+            shader_source,
+            surface_format,
+            options,
+        )
+        .unwrap_or_else(|err| panic!("Failed to create pipeline '{name}': {err}"))
+    }
+
+    /// Like `get_or_create_pipeline_with_options`, reading the attributes of `layout`.
+    pub fn get_or_create_pipeline_with_layout(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        layout: &VertexLayout,
+        shader_source: &str,
+        surface_format: wgpu::TextureFormat,
+        options: PipelineOptions,
+    ) -> usize {
+        if let Some(index) = self.get_pipeline(name) {
+            return index;
+        }
+
+        self.create_pipeline_with_layout(
+            device,
+            name,
+            layout,
+            shader_source,
+            surface_format,
+            options,
+        )
+        .unwrap_or_else(|err| panic!("Failed to create pipeline '{name}': {err}"))
     }
 
     pub fn get_pipeline_by_index(&self, index: usize) -> &wgpu::RenderPipeline {
         &self.pipelines[index]
     }
 
-    pub fn set_bind_group_layouts(&mut self, layouts: &[wgpu::BindGroupLayout; 2]) {
+    /// Layouts of the scene's bind groups, which pipelines pick from with
+    /// `PipelineOptions::bind_groups`. Pipelines created afterwards use the new layouts.
+    pub fn set_bind_group_layouts(&mut self, layouts: &[wgpu::BindGroupLayout]) {
         self.bind_group_layouts = layouts.to_vec();
+        self.pipeline_layouts = Default::default();
     }
 
-    fn get_or_create_pipeline_layout(
-        &mut self,
-        device: &wgpu::Device,
-        label: &str,
-    ) -> wgpu::PipelineLayout {
-        if self.pipeline_layouts.is_empty() {
-            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some(label),
-                bind_group_layouts: &self.bind_group_layouts.iter().collect::<Vec<_>>(),
-                push_constant_ranges: &[],
-            });
-            self.pipeline_layouts.push(layout);
-        }
-        self.pipeline_layouts[0].clone()
+    /// Change the sampler used for textures and rebind the materials created so far with
+    /// it.
+    pub fn set_default_sampler(&mut self, device: &wgpu::Device, config: SamplerConfig) {
+        self.sampler_config = config;
+        self.default_sampler = None;
+        self.rebind_materials(device);
     }
-}
 
-impl Default for GpuResources {
-    fn default() -> Self {
-        Self::new()
+    pub fn sampler_config(&self) -> SamplerConfig {
+        self.sampler_config
     }
-}
 
-#[repr(transparent)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct BufferIndex<T> {
-    pub index: u32,
-    _buffer_type: PhantomData<T>,
-}
+    /// Whether the adapter can filter anisotropically, checked when the renderer starts.
+    pub fn set_anisotropic_filtering(&mut self, supported: bool) {
+        self.anisotropic_filtering = supported;
+        self.default_sampler = None;
+    }
+
+    /// Whether the device has `POLYGON_MODE_LINE`, checked when the renderer starts.
+    pub fn set_polygon_mode_line(&mut self, supported: bool) {
+        self.polygon_mode_line = supported;
+    }
+
+    /// Whether `PipelineVariant::Wireframe` and `PolygonMode::Line` pipelines can be built.
+    pub fn supports_wireframe(&self) -> bool {
+        self.polygon_mode_line
+    }
+
+    /// Sampler to reuse across textures instead of creating one per texture.
+    pub fn default_sampler(&mut self, device: &wgpu::Device) -> wgpu::Sampler {
+        let config = self.sampler_config;
+        let anisotropic_filtering = self.anisotropic_filtering;
+
+        self.default_sampler
+            .get_or_insert_with(|| {
+                device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Default sampler"),
+                    address_mode_u: config.address_mode,
+                    address_mode_v: config.address_mode,
+                    address_mode_w: config.address_mode,
+                    mag_filter: config.mag_filter,
+                    min_filter: config.min_filter,
+                    mipmap_filter: config.mipmap_filter,
+                    anisotropy_clamp: config.effective_anisotropy(anisotropic_filtering),
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
+
+    /// Estimated bytes of GPU memory held by the buffers and textures added so far.
+    pub fn estimated_gpu_memory(&self) -> u64 {
+        self.memory_usage.total()
+    }
+
+    /// Same estimate as `estimated_gpu_memory`, broken down by kind of resource.
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        self.memory_usage
+    }
+
+    /// Group index `ObjectParams` are bound at, right after the scene's bind groups.
+    pub fn object_params_group(&self) -> u32 {
+        self.bind_group_layouts.len() as u32
+    }
+
+    /// Scene bind group layouts the pipeline at `index` binds, in group order.
+    pub fn pipeline_bind_groups(&self, index: usize) -> &[usize] {
+        self.pipeline_source(index)
+            .map_or(&[], |source| &source.bind_groups)
+    }
+
+    fn bind_groups_of(&self, options: &PipelineOptions) -> Vec<usize> {
+        match options.bind_groups {
+            Some(groups) => groups.to_vec(),
+            None => (0..self.bind_group_layouts.len()).collect(),
+        }
+    }
+
+    /// Upload `params` and return the index to store in `Mesh::params_index`.
+    pub fn add_object_params(&mut self, device: &wgpu::Device, params: ObjectParams) -> usize {
+        let binding = self.create_object_params_binding(device, params);
+        self.memory_usage.uniform += binding.0.size();
+        self.object_params.push(Some(binding));
+        self.object_params.len() - 1
+    }
+
+    pub fn update_object_params(&self, queue: &wgpu::Queue, index: usize, params: ObjectParams) {
+        let (buffer, _) = self.object_params[index]
+            .as_ref()
+            .unwrap_or_else(|| panic!("object params {index} were removed"));
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    /// Remove params from `add_object_params` or a material from `add_material`, once
+    /// no mesh is drawn with them. Returns whether there was anything to remove.
+    ///
+    /// Meshes still holding `index` are drawn with the default params.
+    pub fn remove_object_params(&mut self, index: usize) -> bool {
+        let Some((buffer, _)) = self.object_params.get_mut(index).and_then(Option::take) else {
+            return false;
+        };
+        self.material_views.remove(&index);
+        self.memory_usage.uniform -= buffer.size();
+        true
+    }
+
+    /// Upload `params` along with a base color texture and return the index to store in
+    /// `Mesh::params_index`, for meshes drawn with a `PipelineOptions::textured` pipeline.
+    ///
+    /// `None` binds a 1x1 white texture, so the params color is used as is.
+    pub fn add_material(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        params: ObjectParams,
+        base_color: Option<&TextureIndex>,
+    ) -> usize {
+        let white = self.white_texture(device, queue);
+        if self.default_material.is_none() {
+            let view = self.get_texture(&white).create_view(&Default::default());
+            let (_, bind_group) =
+                self.create_material_binding(device, ObjectParams::default(), &view);
+            self.default_material = Some(bind_group);
+        }
+
+        let view = self
+            .get_texture(base_color.unwrap_or(&white))
+            .create_view(&Default::default());
+        let binding = self.create_material_binding(device, params, &view);
+        self.memory_usage.uniform += binding.0.size();
+        self.object_params.push(Some(binding));
+        self.material_views
+            .insert(self.object_params.len() - 1, view);
+        self.object_params.len() - 1
+    }
+
+    /// Recreate the bind groups of the materials, and of the default one, with the current
+    /// default sampler.
+    fn rebind_materials(&mut self, device: &wgpu::Device) {
+        let Some(layout) = self.material_layout.clone() else {
+            return;
+        };
+        let sampler = self.default_sampler(device);
+
+        for (&index, view) in &self.material_views {
+            if let Some((buffer, bind_group)) = &mut self.object_params[index] {
+                *bind_group = Self::material_bind_group(device, &layout, buffer, view, &sampler);
+            }
+        }
+        if let (Some(_), Some(white)) = (&self.default_material, self.white_texture) {
+            let view = self.get_texture(&white).create_view(&Default::default());
+            let (_, bind_group) =
+                self.create_material_binding(device, ObjectParams::default(), &view);
+            self.default_material = Some(bind_group);
+        }
+    }
+
+    /// Bind group for a mesh's params, falling back to the defaults of untextured or
+    /// textured pipelines when it has none or they were removed.
+    ///
+    /// `None` until the first pipeline, or for textured ones the first material, has
+    /// been created.
+    fn object_params_bind_group(
+        &self,
+        index: Option<usize>,
+        textured: bool,
+    ) -> Option<&wgpu::BindGroup> {
+        let params = index.and_then(|index| self.object_params.get(index)?.as_ref());
+        match params {
+            Some((_, bind_group)) => Some(bind_group),
+            None if textured => self.default_material.as_ref(),
+            None => self.default_object_params.as_ref(),
+        }
+    }
+
+    /// 1x1 white texture bound by materials without a base color texture.
+    fn white_texture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> TextureIndex {
+        if let Some(texture) = self.white_texture {
+            return texture;
+        }
+
+        let texture = self.upload_texture_2d(device, queue, &[255; 4], 1, 1);
+        self.white_texture = Some(texture);
+        texture
+    }
+
+    fn material_layout(&mut self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        self.material_layout
+            .get_or_insert_with(|| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Material bind group layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                })
+            })
+            .clone()
+    }
+
+    fn create_material_binding(
+        &mut self,
+        device: &wgpu::Device,
+        params: ObjectParams,
+        base_color: &wgpu::TextureView,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let layout = self.material_layout(device);
+        let sampler = self.default_sampler(device);
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material params uniform buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::material_bind_group(device, &layout, &buffer, base_color, &sampler);
+
+        (buffer, bind_group)
+    }
+
+    fn material_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params: &wgpu::Buffer,
+        base_color: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(base_color),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    fn object_params_layout(&mut self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        self.object_params_layout
+            .get_or_insert_with(|| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Object params bind group layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                })
+            })
+            .clone()
+    }
+
+    fn create_object_params_binding(
+        &mut self,
+        device: &wgpu::Device,
+        params: ObjectParams,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let layout = self.object_params_layout(device);
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Object params uniform buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Object params bind group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        (buffer, bind_group)
+    }
+
+    fn get_or_create_pipeline_layout(
+        &mut self,
+        device: &wgpu::Device,
+        source: &PipelineSource,
+    ) -> wgpu::PipelineLayout {
+        let groups = &source.bind_groups;
+        let textured = source.options.textured;
+        let layouts = &self.pipeline_layouts[usize::from(textured)];
+        if let Some(layout) = layouts.get(groups.as_slice()) {
+            return layout.clone();
+        }
+
+        if self.default_object_params.is_none() {
+            let (_, bind_group) =
+                self.create_object_params_binding(device, ObjectParams::default());
+            self.default_object_params = Some(bind_group);
+        }
+        let object_params_layout = if textured {
+            self.material_layout(device)
+        } else {
+            self.object_params_layout(device)
+        };
+
+        // The object params group follows the scene groups
+        let mut bind_group_layouts = [&object_params_layout; MAX_BIND_GROUPS];
+        for (slot, &index) in bind_group_layouts.iter_mut().zip(groups) {
+            *slot = &self.bind_group_layouts[index];
+        }
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&source.name),
+            bind_group_layouts: &bind_group_layouts[..=groups.len()],
+            push_constant_ranges: &[],
+        });
+        self.pipeline_layouts[usize::from(textured)].insert(groups.clone(), layout.clone());
+        layout
+    }
+}
+
+/// Alternative configurations of a registered pipeline, built on demand from its source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PipelineVariant {
+    /// Depth only, used to fill the depth buffer before the color pass.
+    DepthPrepass,
+    /// Color pass over a filled depth buffer: `Equal` depth test and no depth writes.
+    DepthEqual,
+    /// Triangle edges only, with `PolygonMode::Line` and no culling. Needs the
+    /// `POLYGON_MODE_LINE` device feature.
+    Wireframe,
+    /// Alpha blended without depth writes, for meshes with `BlendMode::Blend`.
+    Blend,
+}
+
+/// Fixed-function state of a pipeline that differs between kinds of geometry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PipelineOptions {
+    pub topology: wgpu::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::Face>,
+    pub blend: wgpu::BlendState,
+    /// Bind materials from `GpuResources::add_material`, with a base color texture and
+    /// sampler at bindings 1 and 2, instead of plain object params.
+    pub textured: bool,
+    /// Indices into the scene's bind group layouts the shader reads, bound from group 0
+    /// in this order with object params right after. `None` binds every scene group.
+    pub bind_groups: Option<&'static [usize]>,
+    /// Anything but `Fill` needs a device feature, see `GpuResources::supports_wireframe`.
+    pub polygon_mode: wgpu::PolygonMode,
+    /// Normals are already in world space, like glTF import writes them, so
+    /// `Scene::merge_static_meshes` copies them as they are instead of transforming them.
+    pub world_space_normals: bool,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: wgpu::BlendState::REPLACE,
+            textured: false,
+            bind_groups: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            world_space_normals: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PipelineSource {
+    name: String,
+    vertex_entry: &'static str,
+    fragment_entry: &'static str,
+    vertex_buffers: Vec<OwnedVertexBufferLayout>,
+    // Mesh attribute bound to each per-vertex buffer slot
+    attributes: Vec<VertexAttribute>,
+    surface_format: wgpu::TextureFormat,
+    options: PipelineOptions,
+    // Scene bind group layouts it binds, resolved once when it is registered
+    bind_groups: Vec<usize>,
+}
+
+/// `wgpu::VertexBufferLayout` without the borrow, so it can be kept for later variants.
+#[derive(Clone)]
+struct OwnedVertexBufferLayout {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl OwnedVertexBufferLayout {
+    fn as_layout(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+impl From<&wgpu::VertexBufferLayout<'_>> for OwnedVertexBufferLayout {
+    fn from(layout: &wgpu::VertexBufferLayout<'_>) -> Self {
+        Self {
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: layout.attributes.to_vec(),
+        }
+    }
+}
+
+impl Default for GpuResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+struct BufferSlot {
+    buffer: Option<wgpu::Buffer>,
+    generation: u32,
+    // Counted as index memory rather than vertex memory
+    index_data: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct BufferIndex<T> {
+    pub index: u32,
+    // Generation of the slot when the buffer was added
+    generation: u32,
+    _buffer_type: PhantomData<T>,
+}
 
 impl<T> BufferIndex<T> {
     pub fn new(index: u32) -> Self {
         Self {
             index,
+            generation: 0,
+            _buffer_type: PhantomData,
+        }
+    }
+
+    /// The same buffer, looked up without its kind.
+    pub(crate) fn untyped(self) -> BufferIndex<AttributeData> {
+        BufferIndex {
+            index: self.index,
+            generation: self.generation,
             _buffer_type: PhantomData,
         }
     }
 }
 
+// Handles are copied around whatever their kind, which derives would tie to `T`
+impl<T> Clone for BufferIndex<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BufferIndex<T> {}
+
 // Kinds of buffers supported
 pub struct Position;
 pub struct Normal;
 pub struct UV;
 pub struct Index;
 pub struct ModelMatrix;
+/// Vertex data of a `VertexAttribute` other than position, normal and uv.
+pub struct AttributeData;
+
+#[derive(Default)]
+struct TextureSlot {
+    texture: Option<wgpu::Texture>,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureIndex<T = Texture2d> {
+    pub index: u32,
+    // Generation of the slot when the texture was added
+    generation: u32,
+    _texture_type: PhantomData<T>,
+}
+
+// Kinds of textures supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Texture2d;
+
+/// Pipeline and variants rebuilt from new shader source by
+/// `GpuResources::rebuild_pipeline`, not in use yet.
+pub struct PipelineReload {
+    name: String,
+    shader: wgpu::ShaderModule,
+    pipelines: Vec<(usize, wgpu::RenderPipeline)>,
+}
+
+/// Bytes of GPU memory held by `GpuResources`, per kind of resource.
+///
+/// Estimated from buffer sizes and texture extents, drivers may pad or compress both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GpuMemoryUsage {
+    /// Per-vertex attributes and per-instance model matrices.
+    pub vertex: u64,
+    pub index: u64,
+    pub uniform: u64,
+    pub texture: u64,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.vertex + self.index + self.uniform + self.texture
+    }
+}
+
+impl std::fmt::Display for GpuMemoryUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        write!(
+            f,
+            "{:.1} MiB (vertex {:.1}, index {:.1}, uniform {:.1}, texture {:.1})",
+            mib(self.total()),
+            mib(self.vertex),
+            mib(self.index),
+            mib(self.uniform),
+            mib(self.texture)
+        )
+    }
+}
+
+/// Bytes taken by every mip level and layer of `texture`.
+fn texture_byte_size(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    // Combined depth-stencil formats have no single copy size, count them as 4 bytes.
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    let size = texture.size();
+
+    let layer_size: u64 = (0..texture.mip_level_count())
+        .map(|level| {
+            let width = (size.width >> level).max(1).div_ceil(block_width) as u64;
+            let height = (size.height >> level).max(1).div_ceil(block_height) as u64;
+            width * height * block_size
+        })
+        .sum();
+
+    layer_size * size.depth_or_array_layers as u64 * texture.sample_count() as u64
+}
+
+/// Filtering and addressing of the shared texture sampler.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerConfig {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Maximum anisotropy, 1 disables anisotropic filtering.
+    pub anisotropy_clamp: u16,
+    pub address_mode: wgpu::AddressMode,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 16,
+            address_mode: wgpu::AddressMode::Repeat,
+        }
+    }
+}
+
+impl SamplerConfig {
+    /// Anisotropy the sampler can actually use.
+    ///
+    /// wgpu only accepts anisotropy above 1 when the adapter supports it and every filter
+    /// is linear, otherwise this falls back to 1.
+    fn effective_anisotropy(&self, supported: bool) -> u16 {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+
+        if supported && all_linear {
+            self.anisotropy_clamp.clamp(1, 16)
+        } else {
+            1
+        }
+    }
+}
+
+/// Rectangle of the surface the scene is drawn into, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Largest viewport of the given aspect ratio centered inside a `width` x `height`
+    /// surface, leaving bars on the sides or top and bottom.
+    pub fn letterboxed(width: f32, height: f32, aspect_ratio: f32) -> Self {
+        if width / height > aspect_ratio {
+            let inner_width = height * aspect_ratio;
+            Self::new((width - inner_width) * 0.5, 0.0, inner_width, height)
+        } else {
+            let inner_height = width / aspect_ratio;
+            Self::new(0.0, (height - inner_height) * 0.5, width, inner_height)
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width / self.height.max(1.0)
+    }
+
+    /// Clamp the rectangle so it lies within a `width` x `height` surface.
+    fn clamped_to(self, width: f32, height: f32) -> Self {
+        let x = self.x.clamp(0.0, width);
+        let y = self.y.clamp(0.0, height);
+        Self::new(
+            x,
+            y,
+            self.width.clamp(1.0, (width - x).max(1.0)),
+            self.height.clamp(1.0, (height - y).max(1.0)),
+        )
+    }
+}
+
+/// Reasons the renderer could not be created, usually because WebGPU is unavailable.
+#[derive(Debug, thiserror::Error)]
+pub enum RendererInitError {
+    #[error("failed to create a surface for the canvas")]
+    SurfaceCreationFailed(#[source] wgpu::CreateSurfaceError),
+
+    #[error("no WebGPU adapter is available")]
+    NoAdapter(#[source] wgpu::RequestAdapterError),
+
+    #[error("failed to request a device from the adapter")]
+    DeviceRequestFailed(#[source] wgpu::RequestDeviceError),
+
+    #[error("the surface does not support the {0:?} format")]
+    UnsupportedSurfaceFormat(wgpu::TextureFormat),
+
+    #[error("the surface does not support the {0:?} present mode")]
+    UnsupportedPresentMode(wgpu::PresentMode),
+
+    #[error("failed to set up the scene")]
+    SceneSetupFailed(#[source] SceneError),
+}
+
+impl RendererInitError {
+    /// Stable name of the variant, for the page to decide what to show.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RendererInitError::SurfaceCreationFailed(_) => "SurfaceCreationFailed",
+            RendererInitError::NoAdapter(_) => "NoAdapter",
+            RendererInitError::DeviceRequestFailed(_) => "DeviceRequestFailed",
+            RendererInitError::UnsupportedSurfaceFormat(_) => "UnsupportedSurfaceFormat",
+            RendererInitError::UnsupportedPresentMode(_) => "UnsupportedPresentMode",
+            RendererInitError::SceneSetupFailed(_) => "SceneSetupFailed",
+        }
+    }
+}
+
+/// How frames are queued and presented.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresentConfig {
+    /// Frames the surface may queue ahead of the display. 1 gives the lowest input latency,
+    /// e.g. while dragging in the editor, 3 absorbs more frame time spikes.
+    pub max_frame_latency: u32,
+    /// Cap on the render rate in frames per second, below the display refresh, to save
+    /// battery. `None` renders on every animation frame.
+    pub max_frame_rate: Option<f32>,
+}
+
+impl Default for PresentConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_latency: 2,
+            max_frame_rate: None,
+        }
+    }
+}
+
+impl PresentConfig {
+    /// Shortest time between two rendered frames in milliseconds, if the rate is capped.
+    pub fn frame_interval_ms(&self) -> Option<f32> {
+        self.max_frame_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| 1000.0 / rate)
+    }
+}
+
+/// Device and surface setup, fixed when the renderer is created.
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    /// Device features beyond the WebGPU baseline, creation fails if they are missing.
+    pub required_features: wgpu::Features,
+    pub required_limits: wgpu::Limits,
+    /// `None` uses the surface's preferred present mode.
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// `None` uses the surface's preferred format.
+    pub surface_format: Option<wgpu::TextureFormat>,
+    pub present: PresentConfig,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            present_mode: None,
+            surface_format: None,
+            present: PresentConfig::default(),
+        }
+    }
+}
 
 pub struct RendererContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
-    pub surface: wgpu::Surface<'static>,
+    /// `None` for a headless context, which has nothing to present to.
+    pub surface: Option<wgpu::Surface<'static>>,
     pub depth_texture: wgpu::Texture,
     pub depth_view: wgpu::TextureView,
+    /// Sub-rectangle of the surface to render into, `None` renders to the whole surface.
+    pub viewport: Option<Viewport>,
+    pub present: PresentConfig,
 }
 
-pub struct Renderer<T: scene::Scene> {
-    canvas: web_sys::OffscreenCanvas,
-    events_chan: Receiver<WindowEvent>,
-    context: RendererContext,
-    resources: GpuResources,
-    scene: T,
-}
+impl RendererContext {
+    /// Context without a surface, to build scenes off the web worker, e.g. in tests.
+    pub fn headless(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: PresentConfig::default().max_frame_latency,
+        };
+        let (depth_texture, depth_view) =
+            RendererContext::create_depth_texture(&device, &surface_config);
 
-impl<T: Scene + 'static> Renderer<T> {
-    fn create_depth_texture(
+        Self {
+            device,
+            queue,
+            surface_config,
+            surface: None,
+            depth_texture,
+            depth_view,
+            viewport: None,
+            present: PresentConfig::default(),
+        }
+    }
+
+    pub(crate) fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
     ) -> (wgpu::Texture, wgpu::TextureView) {
@@ -291,7 +1682,10 @@ impl<T: Scene + 'static> Renderer<T> {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            // Sampled by the depth debug preview.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
 
@@ -300,14 +1694,89 @@ impl<T: Scene + 'static> Renderer<T> {
         (texture, view)
     }
 
+    /// Size of the area the scene is rendered into, in physical pixels.
+    ///
+    /// This is the viewport when one is set and the surface backing store otherwise, so
+    /// the camera aspect ratio always matches the pixels it ends up covering.
+    pub fn render_extent(&self) -> (f32, f32) {
+        match self.viewport {
+            Some(viewport) => (viewport.width, viewport.height),
+            None => (
+                self.surface_config.width as f32,
+                self.surface_config.height as f32,
+            ),
+        }
+    }
+}
+
+pub struct Renderer<T: scene::Scene> {
+    // `None` for a headless renderer, see `Renderer::headless`
+    canvas: Option<web_sys::OffscreenCanvas>,
+    events_chan: Receiver<WindowEvent>,
+    context: RendererContext,
+    resources: GpuResources,
+    scene: T,
+    // Name of `scene`, the others wait in `scenes` until they are switched to
+    active_scene: String,
+    scenes: HashMap<String, T>,
+    // Materials of the model `load_model` put in each scene, freed when it is replaced
+    model_materials: HashMap<String, ModelMaterials>,
+    scale_factor: f64,
+    background_pass: BackgroundPass,
+    overlay_pass: OverlayPass,
+    picking_pass: PickingPass,
+    texture_pool: TexturePool,
+    model_decoder: Option<ModelDecoder>,
+    depth_prepass: bool,
+    depth_debug: bool,
+    wireframe: bool,
+    // Meshes left out of the last frame for being outside the view
+    culled_meshes: usize,
+    // Aspect ratio the viewport is letterboxed to, refit whenever the surface resizes
+    letterbox: Option<f32>,
+    camera_mode: CameraMode,
+    // Codes of the keys currently pressed, for the fly camera
+    held_keys: HashSet<String>,
+    fly_speed: f32,
+    last_fly_time: Option<f32>,
+    // Frame the damped camera last advanced at
+    last_damping_time: Option<f32>,
+    pending_zoom: Option<WheelMessage>,
+    pending_fov_zoom: Option<WheelMessage>,
+    max_zoom_step: f64,
+    last_frame_time: Option<f32>,
+    // Frame the scene was last updated for, its delta time is measured from there
+    last_update_time: Option<f32>,
+    paused: bool,
+    render_on_demand: bool,
+    // Something changed since the last frame, only consulted when rendering on demand
+    redraw_requested: bool,
+    // Cursor in surface pixels as of the last mouse move
+    cursor: Option<Vec2>,
+    // Cursor the last hover pick was started at, so a still cursor is not picked again
+    hover_cursor: Option<Vec2>,
+    // A hover pick is being read back, later ones wait for it
+    hover_pending: bool,
+    hovered: Option<MeshId>,
+    adapter_info: wgpu::AdapterInfo,
+    clear_color: wgpu::Color,
+}
+
+impl<T: Scene + 'static> Renderer<T> {
     fn recreate_depth_texture(&mut self) {
-        let (texture, view) =
-            Self::create_depth_texture(&self.context.device, &self.context.surface_config);
+        let (texture, view) = RendererContext::create_depth_texture(
+            &self.context.device,
+            &self.context.surface_config,
+        );
         self.context.depth_texture = texture;
         self.context.depth_view = view;
     }
 
-    pub async fn new(canvas: web_sys::OffscreenCanvas, events_chan: Receiver<WindowEvent>) -> Self {
+    pub async fn new(
+        canvas: web_sys::OffscreenCanvas,
+        events_chan: Receiver<WindowEvent>,
+        config: RendererConfig,
+    ) -> Result<Self, RendererInitError> {
         let id = wgpu::InstanceDescriptor {
             backends: wgpu::Backends::BROWSER_WEBGPU,
             ..Default::default()
@@ -316,7 +1785,7 @@ impl<T: Scene + 'static> Renderer<T> {
         let instance = wgpu::Instance::new(&id);
         let surface = instance
             .create_surface(wgpu::SurfaceTarget::OffscreenCanvas(canvas.clone()))
-            .unwrap();
+            .map_err(RendererInitError::SurfaceCreationFailed)?;
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 compatible_surface: Some(&surface),
@@ -324,67 +1793,931 @@ impl<T: Scene + 'static> Renderer<T> {
                 ..Default::default()
             })
             .await
-            .unwrap();
+            .map_err(RendererInitError::NoAdapter)?;
+
+        info!("Adapter info: {:?}", adapter.get_info());
+        info!("Adapter features: {:?}", adapter.features());
+        info!("Adapter limits: {:?}", adapter.limits());
+
+        let (device, queue) = Self::request_device(&adapter, &config).await?;
+
+        let surface_caps = surface.get_capabilities(&adapter);
+        let format = match config.surface_format {
+            Some(format) if !surface_caps.formats.contains(&format) => {
+                return Err(RendererInitError::UnsupportedSurfaceFormat(format));
+            }
+            Some(format) => format,
+            None => surface_caps.formats[0],
+        };
+        let present_mode = match config.present_mode {
+            Some(mode) if !surface_caps.present_modes.contains(&mode) => {
+                return Err(RendererInitError::UnsupportedPresentMode(mode));
+            }
+            Some(mode) => mode,
+            None => surface_caps.present_modes[0],
+        };
+
+        let present = config.present;
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: canvas.clone().width(),
+            height: canvas.clone().height(),
+            present_mode,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: present.max_frame_latency.max(1),
+        };
+        info!(
+            "suface size: {} x {}",
+            surface_config.width, surface_config.height
+        );
+        surface.configure(&device, &surface_config);
+
+        let (depth_texture, depth_view) =
+            RendererContext::create_depth_texture(&device, &surface_config);
+
+        let context = RendererContext {
+            surface: Some(surface),
+            device,
+            queue,
+            surface_config,
+            depth_texture,
+            depth_view,
+            viewport: None,
+            present,
+        };
+
+        Self::from_context(Some(canvas), events_chan, context, &adapter).await
+    }
+
+    /// Renderer drawing into offscreen textures of `width` x `height` instead of a canvas,
+    /// on an adapter from the default native backends, e.g. to test rendering with
+    /// `cargo test`. Frames are drawn with `render_offscreen`.
+    ///
+    /// `config.surface_format` is the format of the offscreen textures, RGBA8 sRGB when
+    /// `None`. There are no window events, the scene is driven through the renderer.
+    #[cfg(any(test, feature = "native"))]
+    pub async fn headless(
+        width: u32,
+        height: u32,
+        config: RendererConfig,
+    ) -> Result<Self, RendererInitError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: None,
+                force_fallback_adapter: false,
+                ..Default::default()
+            })
+            .await
+            .map_err(RendererInitError::NoAdapter)?;
+        let (device, queue) = Self::request_device(&adapter, &config).await?;
+
+        let format = config
+            .surface_format
+            .unwrap_or(wgpu::TextureFormat::Rgba8UnormSrgb);
+        let mut context = RendererContext::headless(device, queue, width, height, format);
+        context.present = config.present;
+
+        // Nothing sends events to a headless renderer.
+        let (_, events_chan) = std::sync::mpsc::channel();
+        let mut renderer = Self::from_context(None, events_chan, context, &adapter).await?;
+        renderer.resize_scene();
+        Ok(renderer)
+    }
+
+    /// Draw a frame of the scene into an offscreen texture of the surface size and read
+    /// it back as tightly packed RGBA8 rows, e.g. to compare against a reference image.
+    ///
+    /// Blocks until the GPU is done, so it is only available off the browser.
+    #[cfg(any(test, feature = "native"))]
+    pub fn render_offscreen(&mut self) -> Result<Vec<u8>, CaptureError> {
+        self.update_scene(self.last_update_time.unwrap_or_default());
+
+        let target = CaptureTarget::new(&self.context);
+        let encoder = self.encode_frame(&target.view, true);
+        let readback = target.read(&self.context, encoder);
+        // A poll error shows up as a failed readback.
+        let _ = self.context.device.poll(wgpu::PollType::Wait);
+
+        futures::executor::block_on(readback.resolve_rgba())
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        config: &RendererConfig,
+    ) -> Result<(wgpu::Device, wgpu::Queue), RendererInitError> {
+        // Wireframes are optional, so only ask for line polygons where the adapter has them.
+        let optional_features = adapter.features() & wgpu::Features::POLYGON_MODE_LINE;
+        let descriptor = wgpu::DeviceDescriptor {
+            required_features: config.required_features | optional_features,
+            required_limits: config.required_limits.clone(),
+            label: None,
+            memory_hints: wgpu::MemoryHints::default(),
+            trace: wgpu::Trace::default(),
+        };
+
+        adapter
+            .request_device(&descriptor)
+            .await
+            .map_err(RendererInitError::DeviceRequestFailed)
+    }
+
+    /// Set up the resources and the scene once the device is ready.
+    async fn from_context(
+        canvas: Option<web_sys::OffscreenCanvas>,
+        events_chan: Receiver<WindowEvent>,
+        context: RendererContext,
+        adapter: &wgpu::Adapter,
+    ) -> Result<Self, RendererInitError> {
+        let mut resources = GpuResources::new();
+        resources.set_anisotropic_filtering(
+            adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING),
+        );
+        resources.set_polygon_mode_line(
+            context
+                .device
+                .features()
+                .contains(wgpu::Features::POLYGON_MODE_LINE),
+        );
+
+        let mut scene = Self::setup_scene(&context, &mut resources)
+            .await
+            .map_err(RendererInitError::SceneSetupFailed)?;
+        scene.on_surface_configured(&context.surface_config, &context.device);
+        let background_pass = BackgroundPass::new(&context.device);
+        let overlay_pass = OverlayPass::new(&context.device);
+        let picking_pass = PickingPass::new(&context.device);
+
+        Ok(Self {
+            canvas,
+            events_chan,
+            context,
+            scene,
+            active_scene: DEFAULT_SCENE.to_owned(),
+            scenes: HashMap::new(),
+            model_materials: HashMap::new(),
+            resources,
+            scale_factor: 1.0,
+            background_pass,
+            overlay_pass,
+            picking_pass,
+            texture_pool: TexturePool::default(),
+            model_decoder: None,
+            depth_prepass: false,
+            depth_debug: false,
+            wireframe: false,
+            culled_meshes: 0,
+            letterbox: None,
+            camera_mode: CameraMode::Orbit,
+            held_keys: HashSet::new(),
+            fly_speed: DEFAULT_FLY_SPEED,
+            last_fly_time: None,
+            last_damping_time: None,
+            pending_zoom: None,
+            pending_fov_zoom: None,
+            max_zoom_step: DEFAULT_MAX_ZOOM_STEP,
+            last_frame_time: None,
+            last_update_time: None,
+            paused: false,
+            render_on_demand: false,
+            redraw_requested: true,
+            cursor: None,
+            hover_cursor: None,
+            hover_pending: false,
+            hovered: None,
+            adapter_info: adapter.get_info(),
+            clear_color: wgpu::Color::BLACK,
+        })
+    }
+
+    /// Name of the scene being drawn and receiving input, `DEFAULT_SCENE` at first.
+    pub fn active_scene(&self) -> &str {
+        &self.active_scene
+    }
+
+    /// Names of every scene held, the active one included.
+    pub fn scene_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.active_scene.as_str()).chain(self.scenes.keys().map(String::as_str))
+    }
+
+    /// Hold `scene` under `name` to switch to later, handing back any scene already held
+    /// under that name. Under the active scene's name it replaces the active scene.
+    ///
+    /// Scenes share the `GpuResources`, so their meshes can use the same pipelines.
+    pub fn add_scene(&mut self, name: &str, scene: T) -> Option<T> {
+        if name == self.active_scene {
+            let previous = std::mem::replace(&mut self.scene, scene);
+            self.activate_scene();
+            return Some(previous);
+        }
+        self.scenes.insert(name.to_owned(), scene)
+    }
+
+    /// Set up a scene with `Scene::try_setup`.
+    ///
+    /// Invalid shaders only show up as validation errors, caught here as
+    /// `SceneError::Validation` instead of panicking as uncaptured ones.
+    async fn setup_scene(
+        context: &RendererContext,
+        resources: &mut GpuResources,
+    ) -> Result<T, SceneError> {
+        context
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let scene = T::try_setup(context, resources);
+        let error = context.device.pop_error_scope().await;
+        match (scene, error) {
+            (Err(err), _) => Err(err),
+            (Ok(_), Some(error)) => Err(SceneError::Validation(error)),
+            (Ok(scene), None) => Ok(scene),
+        }
+    }
+
+    /// Set up an empty scene with `Scene::try_setup` and hold it under `name`, see
+    /// `add_scene`.
+    pub async fn create_scene(&mut self, name: &str) -> Result<Option<T>, SceneError> {
+        let mut scene = Self::setup_scene(&self.context, &mut self.resources).await?;
+        scene.on_surface_configured(&self.context.surface_config, &self.context.device);
+        // Setting up replaced the bind group layouts with the new scene's.
+        if let Some(layouts) = self.scene.bind_group_layouts() {
+            self.resources.set_bind_group_layouts(layouts);
+        }
+        Ok(self.add_scene(name, scene))
+    }
+
+    /// Take out a scene that is not active, `None` if there is none under `name`.
+    pub fn remove_scene(&mut self, name: &str) -> Option<T> {
+        self.scenes.remove(name)
+    }
+
+    /// Draw the scene held under `name` from the next frame on, keeping the current one
+    /// with its meshes and camera. Returns `false` if there is no such scene.
+    ///
+    /// Scenes of one type create identical bind group layouts, which wgpu treats as
+    /// compatible, so pipelines work across them. The layouts are still re-set to the
+    /// new scene's for the pipelines created afterwards.
+    pub fn set_active_scene(&mut self, name: &str) -> bool {
+        if name == self.active_scene {
+            return true;
+        }
+        let Some(scene) = self.scenes.remove(name) else {
+            return false;
+        };
+
+        let previous = std::mem::replace(&mut self.scene, scene);
+        let previous_name = std::mem::replace(&mut self.active_scene, name.to_owned());
+        self.scenes.insert(previous_name, previous);
+        self.activate_scene();
+        true
+    }
+
+    /// Fit a scene that was just made active to the current surface and depth range.
+    fn activate_scene(&mut self) {
+        if let Some(layouts) = self.scene.bind_group_layouts() {
+            self.resources.set_bind_group_layouts(layouts);
+        }
+        let reverse_z = self.resources.reverse_z();
+        if let Some(camera) = self.scene.camera_mut() {
+            camera.set_reverse_z(reverse_z);
+        }
+        self.resize_scene();
+        self.culled_meshes = 0;
+        self.last_damping_time = None;
+    }
+
+    /// Color behind the meshes of scenes without a `Background`, black by default.
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+    }
+
+    /// Adapter the renderer was created on.
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// Parse imported models on a decode worker instead of the render thread.
+    pub fn set_model_decoder(&mut self, decoder: ModelDecoder) {
+        self.model_decoder = Some(decoder);
+    }
+
+    /// Stop drawing frames, e.g. while the canvas is hidden. Events are still handled, so
+    /// the scene is up to date once rendering resumes.
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused && !paused {
+            // Time-based motion picks up from the resumed frame, not from before the pause.
+            self.last_frame_time = None;
+            self.last_update_time = None;
+            self.last_fly_time = None;
+            self.last_damping_time = None;
+            self.redraw_requested = true;
+        }
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Only draw a frame when something may have changed: an event was handled, the
+    /// camera is still easing or flying, or `request_redraw` was called.
+    ///
+    /// Saves power for mostly static scenes. Scenes that animate on their own should keep
+    /// this off or request a redraw every frame.
+    pub fn set_render_on_demand(&mut self, on_demand: bool) {
+        self.render_on_demand = on_demand;
+        self.redraw_requested = true;
+    }
+
+    /// Draw the next frame even when rendering on demand.
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    /// Whether the animation frame loop should draw, given the pause and on-demand modes.
+    fn needs_redraw(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+        if !self.render_on_demand {
+            return true;
+        }
+
+        let flying = self.camera_mode == CameraMode::Fly && !self.held_keys.is_empty();
+        let easing = self
+            .scene
+            .camera_mut()
+            .is_some_and(|camera| camera.is_moving());
+        self.redraw_requested
+            || flying
+            || easing
+            || self.pending_zoom.is_some()
+            || self.pending_fov_zoom.is_some()
+    }
+
+    /// Limit how far a single frame can zoom, in wheel pixels.
+    ///
+    /// Wheel events are summed over a frame and applied as one step, so a burst of
+    /// trackpad events cannot make the camera jump.
+    pub fn set_max_zoom_step(&mut self, pixels: f64) {
+        self.max_zoom_step = pixels.max(0.0);
+    }
+
+    fn apply_pending_zoom(&mut self) {
+        let max_step = self.max_zoom_step;
+        let clamp = |mut msg: WheelMessage| {
+            msg.delta_y = msg.pixel_delta_y().clamp(-max_step, max_step);
+            msg.delta_mode = 0;
+            msg
+        };
+
+        if let Some(msg) = self.pending_zoom.take() {
+            let cursor = self.client_to_ndc(
+                (msg.client_x * msg.scale_factor) as f32,
+                (msg.client_y * msg.scale_factor) as f32,
+            );
+            self.scene.handle_zoom(&clamp(msg), cursor);
+        }
+        if let Some(msg) = self.pending_fov_zoom.take() {
+            self.scene.handle_fov_zoom(&clamp(msg));
+        }
+    }
+
+    /// Let held W/A/S/D/Q/E keys fly the camera, or leave it to the pointer. Toggled
+    /// with the F key.
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        if mode != CameraMode::Fly {
+            self.held_keys.clear();
+        }
+        self.camera_mode = mode;
+        self.last_fly_time = None;
+    }
+
+    pub fn camera_mode(&self) -> CameraMode {
+        self.camera_mode
+    }
+
+    /// Fly camera speed in world units per second.
+    pub fn set_fly_speed(&mut self, speed: f32) {
+        self.fly_speed = speed.max(0.0);
+    }
+
+    /// Move the camera for the keys held since the frame at `last_fly_time`.
+    fn apply_fly_movement(&mut self, time: f32) {
+        if self.camera_mode != CameraMode::Fly {
+            return;
+        }
+
+        let elapsed = match self.last_fly_time.replace(time) {
+            Some(last) => ((time - last) / 1000.0).clamp(0.0, MAX_FLY_STEP),
+            None => return,
+        };
+        let step =
+            fly_direction(self.held_keys.iter().map(String::as_str)) * (self.fly_speed * elapsed);
+
+        if let Some(camera) = self.scene.camera_mut() {
+            camera.translate_local(step.x, step.y, step.z);
+        }
+    }
+
+    /// Move a damped camera on toward where orbit, pan and zoom sent it.
+    fn apply_camera_damping(&mut self, time: f32) {
+        let last = self.last_damping_time.replace(time);
+        let Some(camera) = self.scene.camera_mut() else {
+            return;
+        };
+        let elapsed = last.map_or(0.0, |last| {
+            ((time - last) / 1000.0).clamp(0.0, MAX_FLY_STEP)
+        });
+        camera.advance(elapsed);
+    }
+
+    /// Change the frame latency and rate cap, reconfiguring the surface if needed.
+    pub fn set_present_config(&mut self, present: PresentConfig) {
+        let latency = present.max_frame_latency.max(1);
+        self.context.present = present;
+
+        if self.context.surface_config.desired_maximum_frame_latency != latency {
+            self.context.surface_config.desired_maximum_frame_latency = latency;
+            if let Some(surface) = &self.context.surface {
+                surface.configure(&self.context.device, &self.context.surface_config);
+            }
+        }
+    }
+
+    /// Whether the animation frame at `time` should render under the frame rate cap.
+    fn frame_due(&mut self, time: f32) -> bool {
+        if let (Some(interval), Some(last)) = (
+            self.context.present.frame_interval_ms(),
+            self.last_frame_time,
+        ) {
+            if time - last < interval - FRAME_PACING_SLACK_MS {
+                return false;
+            }
+        }
+
+        self.last_frame_time = Some(time);
+        true
+    }
+
+    /// Use a reversed `[1, 0]` depth range, which spreads float depth precision far more
+    /// evenly and avoids z-fighting on large scenes.
+    ///
+    /// Rebuilds the existing pipelines and switches the scene camera's projection. Held
+    /// scenes switch theirs once they are made active.
+    pub fn set_reverse_z(&mut self, enabled: bool) {
+        self.resources.set_reverse_z(&self.context.device, enabled);
+        if let Some(camera) = self.scene.camera_mut() {
+            camera.set_reverse_z(enabled);
+        }
+    }
+
+    /// Fill the depth buffer in a depth-only pass before shading, so expensive fragment
+    /// shaders only run once per visible pixel. Pays off on scenes with a lot of overdraw.
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
+
+    /// Merge the scene's static meshes to cut draw calls, see `Scene::merge_static_meshes`.
+    pub fn merge_static_meshes(&mut self, keep_originals: bool) -> merge::MergeStats {
+        self.request_redraw();
+        self.scene.merge_static_meshes(
+            &self.context.device,
+            &self.context.queue,
+            &mut self.resources,
+            keep_originals,
+        )
+    }
+
+    /// Number of visible meshes the last frame skipped because their bounds were outside
+    /// the camera's view.
+    pub fn culled_mesh_count(&self) -> usize {
+        self.culled_meshes
+    }
+
+    /// Draw triangle meshes as wireframes, for inspecting their geometry.
+    ///
+    /// Ignored with a warning when the device lacks `POLYGON_MODE_LINE`, which most
+    /// browsers do not expose yet.
+    pub fn set_wireframe(&mut self, enabled: bool) {
+        if enabled && !self.resources.supports_wireframe() {
+            log::warn!("Wireframe rendering needs the POLYGON_MODE_LINE feature");
+            return;
+        }
+        self.wireframe = enabled;
+    }
+
+    /// Show the depth buffer in grayscale in the bottom-right corner, near black and far
+    /// white. Toggled with the Z key.
+    pub fn set_depth_debug(&mut self, enabled: bool) {
+        self.depth_debug = enabled;
+    }
+
+    /// Where the depth debug preview goes and the depth range to linearize it with.
+    fn depth_preview(&mut self) -> Option<DepthPreview> {
+        if !self.depth_debug {
+            return None;
+        }
+
+        let (near, far) = self.scene.camera_mut()?.depth_range();
+        let depth_range = if self.resources.reverse_z() {
+            [far, near]
+        } else {
+            [near, far]
+        };
+
+        let (width, height) = (
+            self.context.surface_config.width as f32,
+            self.context.surface_config.height as f32,
+        );
+        let (preview_width, preview_height) =
+            (width * DEPTH_PREVIEW_SCALE, height * DEPTH_PREVIEW_SCALE);
+
+        Some(DepthPreview {
+            view: self.context.depth_view.clone(),
+            dest: Rect::new(
+                width - preview_width - DEPTH_PREVIEW_MARGIN,
+                height - preview_height - DEPTH_PREVIEW_MARGIN,
+                preview_width,
+                preview_height,
+            ),
+            depth_range,
+        })
+    }
+
+    /// Restrict rendering to a sub-rectangle of the surface, in physical pixels.
+    ///
+    /// The camera aspect ratio follows the viewport, so a viewport with a different shape
+    /// than the canvas letterboxes the image instead of stretching it.
+    pub fn set_viewport(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let viewport = Viewport::new(x, y, width, height).clamped_to(
+            self.context.surface_config.width as f32,
+            self.context.surface_config.height as f32,
+        );
+        self.letterbox = None;
+        self.context.viewport = Some(viewport);
+        self.resize_scene();
+    }
+
+    /// Keep the image at `aspect_ratio` whatever the canvas shape, drawn in the largest
+    /// viewport of that shape centered on the surface, with bars left at the sides or
+    /// top and bottom. The viewport is refit as the canvas resizes.
+    pub fn set_letterbox(&mut self, aspect_ratio: f32) {
+        self.letterbox = Some(aspect_ratio);
+        self.context.viewport = Some(self.letterboxed_viewport(aspect_ratio));
+        self.resize_scene();
+    }
+
+    fn letterboxed_viewport(&self, aspect_ratio: f32) -> Viewport {
+        Viewport::letterboxed(
+            self.context.surface_config.width as f32,
+            self.context.surface_config.height as f32,
+            aspect_ratio,
+        )
+    }
+
+    /// Render to the whole surface again.
+    pub fn clear_viewport(&mut self) {
+        self.letterbox = None;
+        self.context.viewport = None;
+        self.resize_scene();
+    }
+
+    /// Cast the camera ray through the clicked pixel and report where it meets the ground.
+    fn ground_click(&mut self, x: f32, y: f32) {
+        let (offset_x, offset_y) = self
+            .context
+            .viewport
+            .map_or((0.0, 0.0), |viewport| (viewport.x, viewport.y));
+        let (width, height) = self.context.render_extent();
+
+        let Some(camera) = self.scene.camera_mut() else {
+            return;
+        };
+        let ray = camera.screen_ray(
+            Vec2::new(x - offset_x, y - offset_y),
+            Vec2::new(width, height),
+        );
+
+        if let Some(point) = ray.intersect_ground() {
+            self.scene.on_ground_click(point);
+        }
+    }
+
+    /// Render the object ids under surface pixel (`x`, `y`), to be read once the GPU is done.
+    fn start_pick(&mut self, x: u32, y: u32) -> Option<PickReadback> {
+        let view_proj = Mat4::from(self.scene.camera_mut()?.view_proj);
+        let meshes = self.scene.meshes();
+        let ids = (0..meshes.len())
+            .map(|index| self.scene.mesh_id(index))
+            .collect();
+
+        self.picking_pass.pick(
+            &self.context,
+            &self.resources,
+            meshes,
+            ids,
+            view_proj,
+            (x, y),
+        )
+    }
+
+    /// Mesh under the cursor as of the last hover pick, see `Scene::handle_hover`.
+    pub fn hovered(&self) -> Option<MeshId> {
+        self.hovered
+    }
+
+    /// Pick the pixel under the cursor if it moved since the last hover pick, once that
+    /// one has been read back.
+    fn start_hover_pick(&mut self) -> Option<PickReadback> {
+        if self.paused || self.hover_pending {
+            return None;
+        }
+        let cursor = self.cursor?;
+        if self.hover_cursor == Some(cursor) {
+            return None;
+        }
+        self.hover_cursor = Some(cursor);
+
+        // Nothing is hovered while the cursor is outside the surface.
+        let readback = if cursor.x < 0.0 || cursor.y < 0.0 {
+            None
+        } else {
+            self.start_pick(cursor.x as u32, cursor.y as u32)
+        };
+        match readback {
+            Some(readback) => {
+                self.hover_pending = true;
+                Some(readback)
+            }
+            None => {
+                self.set_hovered(None);
+                None
+            }
+        }
+    }
+
+    fn finish_hover_pick(&mut self, id: Option<MeshId>) {
+        self.hover_pending = false;
+        self.set_hovered(id);
+    }
+
+    fn set_hovered(&mut self, id: Option<MeshId>) {
+        if self.hovered != id {
+            self.hovered = id;
+            self.scene.handle_hover(id);
+            self.request_redraw();
+        }
+    }
+
+    /// Mesh drawn at surface pixel (`x`, `y`), `None` over the background or for scenes
+    /// without a camera.
+    pub async fn read_object_id(&mut self, x: u32, y: u32) -> Option<MeshId> {
+        self.start_pick(x, y)?.resolve().await
+    }
+
+    fn resize_scene(&mut self) {
+        let (width, height) = self.context.render_extent();
+        self.scene.resize(
+            width as f64,
+            height as f64,
+            self.scale_factor,
+            &self.context.queue,
+        );
+    }
+
+    /// Let the scene advance to the animation frame at `time`, in milliseconds.
+    fn update_scene(&mut self, time: f32) {
+        let delta_time = self
+            .last_update_time
+            .map_or(0.0, |last| ((time - last) * 0.001).max(0.0));
+        self.last_update_time = Some(time);
+        self.scene
+            .update(&self.context, &mut self.resources, time * 0.001, delta_time);
+    }
+
+    fn render(&mut self, time: f32) {
+        self.apply_pending_zoom();
+        self.apply_fly_movement(time);
+        self.apply_camera_damping(time);
+        self.update_scene(time);
+
+        let Some(surface) = &self.context.surface else {
+            return;
+        };
+        let surface_texture = match surface.get_current_texture() {
+            Ok(texture) => texture,
+            // Common after the tab was in the background or the canvas resized mid-frame,
+            // the next frame draws to the reconfigured surface. It has to be requested
+            // again when rendering on demand.
+            Err(err @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                log::warn!("Reconfiguring the surface: {}", err);
+                surface.configure(&self.context.device, &self.context.surface_config);
+                self.redraw_requested = true;
+                return;
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("Out of memory acquiring the surface texture, skipping the frame");
+                return;
+            }
+            Err(err) => {
+                log::warn!("Skipping the frame: {}", err);
+                return;
+            }
+        };
+        let texture_view = surface_texture.texture.create_view(&Default::default());
+
+        let encoder = self.encode_frame(&texture_view, true);
+        self.context.queue.submit(std::iter::once(encoder.finish()));
+        surface_texture.present();
+    }
 
-        info!("Adapter info: {:?}", adapter.get_info());
-        info!("Adapter features: {:?}", adapter.features());
-        info!("Adapter limits: {:?}", adapter.limits());
+    /// Render the scene as it is now and encode it as PNG, e.g. for the page to download.
+    ///
+    /// The frame is drawn offscreen at the surface size, the surface is left untouched.
+    /// The future holds no borrow of the renderer, it only waits on the GPU.
+    pub fn capture_frame(
+        &mut self,
+    ) -> impl Future<Output = Result<Vec<u8>, CaptureError>> + 'static {
+        let target = CaptureTarget::new(&self.context);
+        let encoder = self.encode_frame(&target.view, true);
+        let readback = target.read(&self.context, encoder);
 
-        let descriptor = wgpu::DeviceDescriptor {
-            required_features: wgpu::Features::empty(),
-            required_limits: wgpu::Limits::default(),
-            label: None,
-            memory_hints: wgpu::MemoryHints::default(),
-            trace: wgpu::Trace::default(),
-        };
+        readback.resolve()
+    }
 
-        let (device, queue) = adapter.request_device(&descriptor).await.unwrap();
+    /// Render a `width` by `height` picture of the scene for an asset browser, as tightly
+    /// packed RGBA8 rows.
+    ///
+    /// A camera of that aspect ratio frames the visible meshes, without the overlay. The
+    /// live camera, surface size and depth buffer are back in place once this returns,
+    /// the future only waits on the GPU.
+    pub fn render_thumbnail(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> impl Future<Output = Result<Vec<u8>, CaptureError>> + 'static {
+        let (width, height) = (width.max(1), height.max(1));
+
+        let bounds = scene::visible_bounds(self.scene.meshes());
+        let cameras = self.scene.camera_mut().map(|live| {
+            let mut camera = Camera::new(width as f32 / height as f32);
+            camera.set_reverse_z(live.reverse_z());
+            camera.set_projection_mode(live.projection_mode());
+            match bounds {
+                Some(bounds) => camera.frame_bounds(bounds.min.into(), bounds.max.into()),
+                None => {
+                    camera.set_depth_range(live.z_near(), live.z_far());
+                    camera.look_at(live.position(), live.target());
+                }
+            }
+            (live.clone(), camera)
+        });
+        let live_camera = cameras.map(|(live, thumbnail)| {
+            self.scene.set_camera(thumbnail, &self.context.queue);
+            live
+        });
+        // Uploads the frame uniforms and camera at the thumbnail size, e.g. for points
+        // sized from the resolution.
+        self.scene.resize(
+            width as f64,
+            height as f64,
+            self.scale_factor,
+            &self.context.queue,
+        );
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_caps.formats[0],
-            width: canvas.clone().width(),
-            height: canvas.clone().height(),
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        info!(
-            "suface size: {} x {}",
-            surface_config.width, surface_config.height
+        let live_size = (
+            self.context.surface_config.width,
+            self.context.surface_config.height,
         );
-        surface.configure(&device, &surface_config);
+        self.context.surface_config.width = width;
+        self.context.surface_config.height = height;
+        let (depth_texture, depth_view) = RendererContext::create_depth_texture(
+            &self.context.device,
+            &self.context.surface_config,
+        );
+        let live_depth_texture = std::mem::replace(&mut self.context.depth_texture, depth_texture);
+        let live_depth_view = std::mem::replace(&mut self.context.depth_view, depth_view);
+        let live_viewport = self.context.viewport.take();
+        let culled_meshes = self.culled_meshes;
+
+        let target = CaptureTarget::new(&self.context);
+        let encoder = self.encode_frame(&target.view, false);
+        let readback = target.read(&self.context, encoder);
+
+        (
+            self.context.surface_config.width,
+            self.context.surface_config.height,
+        ) = live_size;
+        self.context.depth_texture = live_depth_texture;
+        self.context.depth_view = live_depth_view;
+        self.context.viewport = live_viewport;
+        self.culled_meshes = culled_meshes;
+        // Uploads land before the next submission, so the thumbnail keeps its uniforms.
+        if let Some(camera) = live_camera {
+            self.scene.set_camera(camera, &self.context.queue);
+        }
+        self.resize_scene();
 
-        let (depth_texture, depth_view) = Self::create_depth_texture(&device, &surface_config);
+        readback.resolve_rgba()
+    }
 
-        let mut resources = GpuResources::new();
-        let context = RendererContext {
-            surface,
-            device,
-            queue,
-            surface_config,
-            depth_texture,
-            depth_view,
+    /// Record the scene and background passes drawing into `target`, and the overlay pass
+    /// with `overlay`.
+    fn encode_frame(&mut self, target: &wgpu::TextureView, overlay: bool) -> wgpu::CommandEncoder {
+        let background = match self.scene.background_mut() {
+            Some(background) => background.clone(),
+            None => Background::Solid(self.clear_color),
+        };
+        let (view_proj, camera_position) = match self.scene.camera_mut() {
+            Some(cam) => (Mat4::from(cam.view_proj), cam.position()),
+            None => (Mat4::identity(), Vec3::zero()),
         };
 
-        let scene = T::setup(&context, &mut resources);
+        // Layered scenes can draw over the previous contents instead of clearing them.
+        let color_load = match self.scene.color_load_op() {
+            AttachmentLoad::Clear => wgpu::LoadOp::Clear(background.clear_color()),
+            AttachmentLoad::Load => wgpu::LoadOp::Load,
+        };
+        let depth_load = match self.scene.depth_load_op() {
+            AttachmentLoad::Clear => wgpu::LoadOp::Clear(self.resources.depth_clear_value()),
+            AttachmentLoad::Load => wgpu::LoadOp::Load,
+        };
 
-        Self {
-            canvas,
-            events_chan,
-            context,
-            scene,
-            resources,
+        let draw_background = self.scene.color_load_op() == AttachmentLoad::Clear
+            && self.background_pass.prepare(
+                &self.context.device,
+                &self.context.queue,
+                self.context.surface_config.format,
+                &background,
+                view_proj,
+                camera_position,
+            );
+
+        // Sprites are queued for one frame, so they stay queued while the overlay is off.
+        let draw_overlay = overlay && {
+            let depth_preview = self.depth_preview();
+            let mut batch = self.scene.sprites_mut();
+            let draw_overlay = self.overlay_pass.prepare(
+                &self.context.device,
+                &self.context.queue,
+                self.context.surface_config.format,
+                batch.as_deref().map_or(&[], SpriteBatch::sprites),
+                (
+                    self.context.surface_config.width as f32,
+                    self.context.surface_config.height as f32,
+                ),
+                depth_preview,
+            );
+            if let Some(batch) = batch.as_mut() {
+                batch.clear();
+            }
+            draw_overlay
+        };
+
+        let device = &self.context.device;
+        for mesh in self.scene.meshes() {
+            // Meshes already drawn as lines or points keep their own pipeline.
+            let triangles = self
+                .resources
+                .pipeline_options(mesh.pipeline_index)
+                .topology
+                == wgpu::PrimitiveTopology::TriangleList;
+            let variants: &[PipelineVariant] = if self.wireframe {
+                if triangles {
+                    &[PipelineVariant::Wireframe]
+                } else {
+                    &[]
+                }
+            } else if mesh.blend_mode == BlendMode::Blend {
+                &[PipelineVariant::Blend]
+            } else if self.depth_prepass {
+                &[PipelineVariant::DepthPrepass, PipelineVariant::DepthEqual]
+            } else {
+                &[]
+            };
+
+            for &variant in variants {
+                self.resources
+                    .get_or_create_pipeline_variant(device, mesh.pipeline_index, variant);
+            }
         }
-    }
 
-    fn render(&mut self, time: f32) {
-        self.scene.update(&self.context, &mut self.resources);
+        let camera_position = self.scene.camera_mut().map(|camera| camera.position());
+        let mut draw_order = scene::draw_order(self.scene.meshes(), camera_position);
+        if camera_position.is_some() {
+            self.culled_meshes = scene::cull_meshes(
+                &mut draw_order,
+                self.scene.meshes(),
+                &Frustum::from_view_proj(view_proj),
+            );
+        }
+        let draw_order = &draw_order;
 
-        let surface_texture = self.context.surface.get_current_texture().unwrap();
-        let texture_view = surface_texture.texture.create_view(&Default::default());
         let mut encoder =
             self.context
                 .device
@@ -392,27 +2725,68 @@ impl<T: Scene + 'static> Renderer<T> {
                     label: Some("Render command encoder"),
                 });
 
-        {
+        let mut texture_pool = std::mem::take(&mut self.texture_pool);
+        let this = &*self;
+        let background = &background;
+
+        let mut graph = FrameGraph::new();
+        let color = graph.import_texture(target);
+        let depth = graph.import_texture(&this.context.depth_view);
+
+        // Edges would fail the `Equal` test against the filled depth, so wireframes skip it.
+        let depth_prepass = this.depth_prepass && !this.wireframe;
+
+        if depth_prepass {
+            graph.add_pass("depth_prepass", &[], &[depth], move |encoder, textures| {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Depth prepass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: textures.view(depth),
+                        depth_ops: Some(wgpu::Operations {
+                            load: depth_load,
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                this.apply_viewport(&mut render_pass);
+                this.draw_meshes(
+                    &mut render_pass,
+                    draw_order,
+                    Some(PipelineVariant::DepthPrepass),
+                );
+            });
+        }
+
+        graph.add_pass("main", &[], &[color, depth], move |encoder, textures| {
+            // After a prepass the depth buffer already holds the closest surfaces.
+            let (depth_load, mesh_variant) = if depth_prepass {
+                (wgpu::LoadOp::Load, Some(PipelineVariant::DepthEqual))
+            } else if this.wireframe {
+                (depth_load, Some(PipelineVariant::Wireframe))
+            } else {
+                (depth_load, None)
+            };
+
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     depth_slice: None,
-                    view: &texture_view,
+                    view: textures.view(color),
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
+                        load: color_load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.context.depth_view,
+                    view: textures.view(depth),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: depth_load,
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
@@ -421,61 +2795,176 @@ impl<T: Scene + 'static> Renderer<T> {
                 timestamp_writes: None,
             });
 
-            for (i, bind_group) in self.scene.bind_groups().iter().enumerate() {
-                render_pass.set_bind_group(i as u32, bind_group, &[]);
+            this.apply_viewport(&mut render_pass);
+
+            if draw_background {
+                this.background_pass.draw(&mut render_pass, background);
             }
 
-            for mesh in self.scene.meshes() {
-                render_pass.set_pipeline(self.resources.get_pipeline_by_index(mesh.pipeline_index));
+            // Drawn first, so blended meshes show it through them.
+            if let Some(grid) = this.scene.grid().and_then(Grid::mesh) {
+                this.draw_mesh_list(&mut render_pass, std::iter::once(grid), None);
+            }
 
-                render_pass.set_vertex_buffer(
-                    0,
-                    self.resources
-                        .get_buffer(&mesh.position_buffer_index)
-                        .slice(..),
-                );
-                render_pass.set_vertex_buffer(
-                    1,
-                    self.resources
-                        .get_buffer(&mesh.normal_buffer_index)
-                        .slice(..),
-                );
-                render_pass.set_vertex_buffer(
-                    2,
-                    self.resources.get_buffer(&mesh.uv_buffer_index).slice(..),
-                );
-                render_pass.set_vertex_buffer(
-                    3,
-                    self.resources
-                        .get_buffer(&mesh.model_buffer_index)
-                        .slice(..),
-                );
+            this.draw_meshes(&mut render_pass, draw_order, mesh_variant);
+        });
+
+        if draw_overlay {
+            // The depth preview samples the depth buffer the main pass wrote.
+            let reads: &[_] = if this.depth_debug { &[depth] } else { &[] };
+            graph.add_pass("overlay", reads, &[color], move |encoder, textures| {
+                // Sprites are placed in surface pixels, so the pass ignores the viewport.
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Overlay pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        depth_slice: None,
+                        view: textures.view(color),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                this.overlay_pass.draw(&mut render_pass);
+            });
+        }
+
+        if let Err(err) = graph.execute(&this.context.device, &mut encoder, &mut texture_pool) {
+            log::error!("Failed to record the frame: {}", err);
+        }
+        self.texture_pool = texture_pool;
+
+        encoder
+    }
+
+    fn apply_viewport(&self, render_pass: &mut wgpu::RenderPass) {
+        if let Some(viewport) = self.context.viewport {
+            render_pass.set_viewport(
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                0.0,
+                1.0,
+            );
+        }
+    }
+
+    /// Draw the scene meshes in `order`, with `variant` of their pipeline when one is given.
+    ///
+    /// Blended meshes use their `Blend` variant instead, and are left out of the depth
+    /// prepass.
+    fn draw_meshes(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        order: &[usize],
+        variant: Option<PipelineVariant>,
+    ) {
+        let meshes = self.scene.meshes();
+        let ordered = order.iter().filter_map(|&index| meshes.get(index));
+        self.draw_mesh_list(render_pass, ordered, variant);
+    }
+
+    fn draw_mesh_list<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass,
+        meshes: impl Iterator<Item = &'a Mesh>,
+        variant: Option<PipelineVariant>,
+    ) {
+        let scene_bind_groups = self.scene.bind_groups();
+        let mut bound_groups = None;
+
+        for mesh in meshes.filter(|mesh| mesh.visible && mesh.passes.color()) {
+            let variant = match (mesh.blend_mode, variant) {
+                (BlendMode::Blend, Some(PipelineVariant::DepthPrepass)) => continue,
+                (BlendMode::Blend, Some(PipelineVariant::Wireframe)) | (BlendMode::Opaque, _) => {
+                    variant
+                }
+                (BlendMode::Blend, _) => Some(PipelineVariant::Blend),
+            };
+
+            // Meshes missing an attribute the pipeline reads cannot be drawn with it.
+            let attributes = self.resources.pipeline_attributes(mesh.pipeline_index);
+            if attributes
+                .iter()
+                .any(|&attribute| mesh.vertex_buffer_index(attribute).is_none())
+            {
+                continue;
+            }
 
-                render_pass.set_index_buffer(
+            let pipeline_index = variant
+                .and_then(|variant| {
                     self.resources
-                        .get_buffer(&mesh.index_buffer_index)
-                        .slice(..),
-                    mesh.index_format,
-                );
+                        .pipeline_variant(mesh.pipeline_index, variant)
+                })
+                .unwrap_or(mesh.pipeline_index);
+            render_pass.set_pipeline(self.resources.get_pipeline_by_index(pipeline_index));
+
+            // Pipelines may bind a subset of the scene groups, so rebind when it changes.
+            let groups = self.resources.pipeline_bind_groups(mesh.pipeline_index);
+            let object_params_group = groups.len() as u32;
+            if bound_groups != Some(groups) {
+                for (group, &index) in groups.iter().enumerate() {
+                    if let Some(bind_group) = scene_bind_groups.get(index) {
+                        render_pass.set_bind_group(group as u32, bind_group, &[]);
+                    }
+                }
+                bound_groups = Some(groups);
+            }
+
+            let textured = self
+                .resources
+                .pipeline_options(mesh.pipeline_index)
+                .textured;
+            if let Some(bind_group) = self
+                .resources
+                .object_params_bind_group(mesh.params_index, textured)
+            {
+                render_pass.set_bind_group(object_params_group, bind_group, &[]);
+            }
 
-                render_pass.draw_indexed(0..mesh.index_count, 0, 0..mesh.instance_count);
+            for (slot, &attribute) in attributes.iter().enumerate() {
+                if let Some(buffer_index) = mesh.vertex_buffer_index(attribute) {
+                    render_pass.set_vertex_buffer(
+                        slot as u32,
+                        self.resources.get_buffer(&buffer_index).slice(..),
+                    );
+                }
+            }
+            render_pass.set_vertex_buffer(
+                attributes.len() as u32,
+                self.resources
+                    .get_buffer(&mesh.model_buffer_index)
+                    .slice(..),
+            );
+
+            match &mesh.index_buffer_index {
+                Some(index_buffer_index) => {
+                    render_pass.set_index_buffer(
+                        self.resources.get_buffer(index_buffer_index).slice(..),
+                        mesh.index_format,
+                    );
+                    render_pass.draw_indexed(0..mesh.index_count, 0, 0..mesh.instance_count);
+                }
+                None => render_pass.draw(0..mesh.vertex_count, 0..mesh.instance_count),
             }
         }
-        self.context.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
     }
 
-    pub async fn read_pixel_from_texture(&self, x: u32, y: u32) -> Vec4 {
+    /// Depth buffer value at surface pixel (`x`, `y`) as stored, read once the GPU is done.
+    ///
+    /// `None` outside the buffer. The future holds no borrow of the renderer.
+    fn read_depth(&self, x: u32, y: u32) -> impl Future<Output = Option<f32>> + 'static {
         let width = self.context.depth_texture.width();
         let height = self.context.depth_texture.height();
 
-        if width == 0 || height == 0 {
-            log::warn!("Depth texture has zero extent ({} x {})", width, height);
-            return Vec4::zero();
-        }
-
         // Validate coordinates
-        if x >= width || y >= height {
+        let readback = if x >= width || y >= height {
             log::warn!(
                 "Pixel coordinates ({}, {}) out of bounds for texture size {}x{}",
                 x,
@@ -483,21 +2972,44 @@ impl<T: Scene + 'static> Renderer<T> {
                 width,
                 height
             );
-            return Vec4::zero();
-        }
-
-        let pixel_size = std::mem::size_of::<f32>() as u32;
-        let unpadded_row_bytes = width * pixel_size;
-        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-        let padded_row_bytes = if unpadded_row_bytes % align == 0 {
-            unpadded_row_bytes
+            None
         } else {
-            (unpadded_row_bytes / align + 1) * align
+            Some(self.copy_depth_pixel(x, y))
         };
-        let buffer_size = padded_row_bytes as u64 * height as u64;
+
+        async move {
+            let (buffer, mapped) = readback?;
+            if !matches!(mapped.await, Ok(Ok(()))) {
+                log::warn!("Failed to read back the depth pixel");
+                return None;
+            }
+
+            let depth_value = {
+                let data = buffer.slice(..).get_mapped_range();
+                let mut depth_bytes = [0u8; 4];
+                depth_bytes.copy_from_slice(&data[..4]);
+                f32::from_le_bytes(depth_bytes)
+            };
+            buffer.unmap();
+
+            Some(depth_value)
+        }
+    }
+
+    /// Copy the depth texel at (`x`, `y`) into a buffer and start mapping it.
+    fn copy_depth_pixel(
+        &self,
+        x: u32,
+        y: u32,
+    ) -> (
+        wgpu::Buffer,
+        oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    ) {
+        // Only the requested texel is copied, rather than the whole depth buffer.
+        let pixel_size = std::mem::size_of::<f32>() as u64;
         let buffer = self.context.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("depth pixel read buffer"),
-            size: buffer_size,
+            size: pixel_size,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
@@ -514,49 +3026,87 @@ impl<T: Scene + 'static> Renderer<T> {
             wgpu::TexelCopyTextureInfo {
                 texture: &self.context.depth_texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+                origin: wgpu::Origin3d { x, y, z: 0 },
                 aspect: wgpu::TextureAspect::DepthOnly,
             },
             wgpu::TexelCopyBufferInfo {
                 buffer: &buffer,
                 layout: wgpu::TexelCopyBufferLayout {
                     offset: 0,
-                    bytes_per_row: Some(padded_row_bytes),
-                    rows_per_image: Some(height),
+                    bytes_per_row: None,
+                    rows_per_image: None,
                 },
             },
             wgpu::Extent3d {
-                width,
-                height,
+                width: 1,
+                height: 1,
                 depth_or_array_layers: 1,
             },
         );
 
         self.context.queue.submit(std::iter::once(encoder.finish()));
 
-        // Map the buffer and read the pixel
-        let slice = buffer.slice(..);
         let (tx, rx) = oneshot::channel();
-        slice.map_async(wgpu::MapMode::Read, move |result| {
-            tx.send(result).unwrap();
-        });
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
 
-        // Poll the device to process the mapping
+        (buffer, rx)
+    }
+
+    pub async fn read_pixel_from_texture(&self, x: u32, y: u32) -> Vec4 {
+        let Some(depth_value) = self.read_depth(x, y).await else {
+            return Vec4::zero();
+        };
 
-        rx.await.unwrap().unwrap();
-        let depth_value = {
-            let data = slice.get_mapped_range();
-            let row_pitch = padded_row_bytes as usize;
-            let byte_offset = y as usize * row_pitch + x as usize * pixel_size as usize;
-            let mut depth_bytes = [0u8; 4];
-            depth_bytes.copy_from_slice(&data[byte_offset..byte_offset + 4]);
-            f32::from_le_bytes(depth_bytes)
+        // Report depth as 0 at the near plane and 1 at the far plane either way.
+        let depth_value = if self.resources.reverse_z() {
+            1.0 - depth_value
+        } else {
+            depth_value
         };
-        buffer.unmap();
 
         Vec4::new(depth_value, 0.0, 0.0, 0.0)
     }
 
+    /// Normalized device coordinates of the canvas pixel `x`, `y`, -1 to 1 from the left
+    /// and bottom edges of the viewport.
+    fn client_to_ndc(&self, x: f32, y: f32) -> Vec2 {
+        let (offset_x, offset_y) = self
+            .context
+            .viewport
+            .map_or((0.0, 0.0), |viewport| (viewport.x, viewport.y));
+        let (width, height) = self.context.render_extent();
+
+        Vec2::new(
+            (x - offset_x) / width.max(1.0) * 2.0 - 1.0,
+            1.0 - (y - offset_y) / height.max(1.0) * 2.0,
+        )
+    }
+
+    /// World position drawn at surface pixel (`x`, `y`) with depth buffer value `depth`,
+    /// `None` where only the background was drawn or without a camera.
+    fn depth_to_world(&mut self, x: f32, y: f32, depth: f32) -> Option<Vec3> {
+        if depth == self.resources.depth_clear_value() {
+            return None;
+        }
+
+        let (offset_x, offset_y) = self
+            .context
+            .viewport
+            .map_or((0.0, 0.0), |viewport| (viewport.x, viewport.y));
+        let (width, height) = self.context.render_extent();
+
+        let camera = self.scene.camera_mut()?;
+        Some(camera.screen_to_world(
+            Vec2::new(x - offset_x, y - offset_y),
+            Vec2::new(width, height),
+            depth,
+        ))
+    }
+
     pub async fn handle_event(renderer: Rc<RefCell<Self>>, event: WindowEvent) {
         match event {
             WindowEvent::PointerMove(msg) => {
@@ -573,58 +3123,242 @@ impl<T: Scene + 'static> Renderer<T> {
                     let x = (msg.offset_x * msg.scale_factor) as f32;
                     let y = (msg.offset_y * msg.scale_factor) as f32;
                     r.scene.handle_mouse_click(x, y);
+                    if msg.button == 0.0 {
+                        r.ground_click(x, y);
+                    }
                     log::info!("clicked");
                 }
 
-                // Read pixel from depth texture at click coordinates
-                // let renderer_clone = renderer.clone();
-                // let x_coord = msg.offset_x as u32;
-                // let y_coord = msg.offset_y as u32;
-                // let pixel_value = renderer_clone
-                //     .borrow()
-                //     .read_pixel_from_texture(x_coord, y_coord)
-                //     .await;
-                // log::info!(
-                //     "Depth pixel at ({}, {}): {:?}",
-                //     x_coord,
-                //     y_coord,
-                //     pixel_value
-                // );
+                // Resolved without holding the renderer, the readback waits on the GPU.
+                let x = (msg.offset_x * msg.scale_factor) as u32;
+                let y = (msg.offset_y * msg.scale_factor) as u32;
+                let readback = renderer.borrow_mut().start_pick(x, y);
+                if let Some(readback) = readback {
+                    let picked = readback.resolve().await;
+                    renderer.borrow_mut().scene.handle_pick(picked);
+                }
+
+                if msg.button == 0.0 {
+                    let depth = renderer.borrow().read_depth(x, y);
+                    if let Some(depth) = depth.await {
+                        let mut r = renderer.borrow_mut();
+                        let point = r.depth_to_world(x as f32, y as f32, depth);
+                        if let Some(point) = point {
+                            r.scene.handle_world_click(point);
+                        }
+                    }
+                }
+            }
+            // Focus the camera on the surface under the cursor, the background is ignored.
+            WindowEvent::PointerDoubleClick(msg) => {
+                let x = (msg.offset_x * msg.scale_factor) as u32;
+                let y = (msg.offset_y * msg.scale_factor) as u32;
+                let depth = renderer.borrow().read_depth(x, y);
+                if let Some(depth) = depth.await {
+                    let mut r = renderer.borrow_mut();
+                    let point = r.depth_to_world(x as f32, y as f32, depth);
+                    if let (Some(point), Some(camera)) = (point, r.scene.camera_mut()) {
+                        camera.focus_on(point);
+                    }
+                }
             }
+            // Ctrl/Cmd + scroll changes the field of view, plain scroll dollies. Both are
+            // applied once per frame in `apply_pending_zoom`.
             WindowEvent::PointerWheel(msg) => {
                 let mut r = renderer.borrow_mut();
-                r.scene.handle_zoom(msg.delta_y as f32);
+                let pending = if msg.ctrl_key || msg.meta_key {
+                    &mut r.pending_fov_zoom
+                } else {
+                    &mut r.pending_zoom
+                };
+
+                match pending {
+                    Some(accumulated) => accumulated.accumulate(&msg),
+                    None => *pending = Some(msg),
+                }
+            }
+            WindowEvent::ReloadShader { name, source } => {
+                // The renderer is not borrowed while waiting for validation, so frames
+                // keep being drawn with the old pipeline in the meantime.
+                let device = renderer.borrow().context.device.clone();
+                device.push_error_scope(wgpu::ErrorFilter::Validation);
+                let reload = renderer
+                    .borrow_mut()
+                    .resources
+                    .rebuild_pipeline(&device, &name, &source);
+                let error = device.pop_error_scope().await;
+
+                match (reload, error) {
+                    (Ok(reload), None) => {
+                        renderer
+                            .borrow_mut()
+                            .resources
+                            .apply_pipeline_reload(reload);
+                        log::info!("Reloaded shader for pipeline '{}'", name);
+                    }
+                    (Err(err), _) => log::error!("Failed to reload shader: {}", err),
+                    (_, Some(error)) => {
+                        log::error!("Shader for pipeline '{}' is invalid: {}", name, error)
+                    }
+                }
             }
             WindowEvent::Keyboard(msg) => {
                 log::info!("Key event received: {:?}", msg);
 
+                renderer.borrow_mut().held_keys.insert(msg.code.clone());
+
+                if (msg.key == "z" || msg.key == "Z") && !msg.repeat {
+                    let mut r = renderer.borrow_mut();
+                    let enabled = !r.depth_debug;
+                    r.set_depth_debug(enabled);
+                }
+
+                if (msg.key == "f" || msg.key == "F") && !msg.repeat {
+                    let mut r = renderer.borrow_mut();
+                    let mode = match r.camera_mode {
+                        CameraMode::Orbit => CameraMode::Fly,
+                        CameraMode::Fly => CameraMode::Orbit,
+                    };
+                    r.set_camera_mode(mode);
+                }
+
+                if (msg.key == "r" || msg.key == "R" || msg.key == "Home") && !msg.repeat {
+                    renderer.borrow_mut().scene.reset_camera();
+                }
+
+                if (msg.key == "g" || msg.key == "G") && !msg.repeat {
+                    let mut r = renderer.borrow_mut();
+                    let visible = r.scene.grid().is_some_and(Grid::is_visible);
+                    r.scene.set_grid_visible(!visible);
+                }
+
+                if (msg.key == "p" || msg.key == "P") && !msg.repeat {
+                    spawn_local(Self::post_captured_frame(renderer.clone()));
+                }
+
                 // Check for 'L' key press
                 if msg.key == "l" || msg.key == "L" {
                     let renderer_clone = renderer.clone();
                     spawn_local(async move {
-                        if let Err(e) = Self::show_file_picker_and_load(renderer_clone).await {
-                            log::error!("Failed to load file: {:?}", e);
+                        match Self::show_file_picker_and_load(renderer_clone).await {
+                            Ok(meshes) => {
+                                Self::post_message(&WorkerMessage::ModelLoaded { meshes })
+                            }
+                            Err(e) => log::error!("Failed to load file: {:?}", e),
                         }
                     });
                 }
             }
+            WindowEvent::LoadModel(bytes) => {
+                spawn_local(async move {
+                    let loaded = Self::load_model_bytes(renderer, bytes, Self::post_load_progress);
+                    match loaded.await {
+                        Ok(meshes) => Self::post_message(&WorkerMessage::ModelLoaded { meshes }),
+                        Err(e) => log::error!("Failed to load the dropped model: {}", e),
+                    }
+                });
+            }
+            WindowEvent::CaptureFrame => Self::post_captured_frame(renderer).await,
+            WindowEvent::SetPaused(paused) => renderer.borrow_mut().set_paused(paused),
+            WindowEvent::SetRenderOnDemand(on_demand) => {
+                renderer.borrow_mut().set_render_on_demand(on_demand)
+            }
+            WindowEvent::KeyboardUp(msg) => {
+                let mut r = renderer.borrow_mut();
+                r.held_keys.remove(&msg.code);
+                r.scene.handle_key_up(&msg);
+            }
+            // Whatever was held is let go without a keyup, so the fly camera stops.
+            WindowEvent::FocusLost => renderer.borrow_mut().held_keys.clear(),
         }
     }
 
+    /// Capture the current frame and post the PNG to the main thread, where it is
+    /// dispatched as a `frame-captured` message for the page to download.
+    async fn post_captured_frame(renderer: Rc<RefCell<Self>>) {
+        let capture = renderer.borrow_mut().capture_frame();
+        match capture.await {
+            Ok(png) => Self::post_message(&WorkerMessage::FrameCaptured(png)),
+            Err(err) => log::error!("Failed to capture the frame: {}", err),
+        }
+    }
+
+    fn post_message(message: &WorkerMessage) {
+        let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+        if let Err(err) = global.post_message(&message.to_js()) {
+            log::error!("Failed to post a message to the main thread: {:?}", err);
+        }
+    }
+
+    /// Post the progress of a model load to the main thread, where the page can show a
+    /// loading bar from the `model-load-progress` messages.
+    fn post_load_progress(fraction: f32) {
+        Self::post_message(&WorkerMessage::ModelLoadProgress(fraction));
+    }
+
+    /// Resolve once the next animation frame has been drawn.
+    async fn next_animation_frame() {
+        let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+            let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+            if global.request_animation_frame(&resolve).is_err() {
+                let _ = resolve.call0(&wasm_bindgen::JsValue::NULL);
+            }
+        });
+        let _ = JsFuture::from(promise).await;
+    }
+
     fn drain_events(renderer: &Rc<RefCell<Self>>) -> Result<(), DrainEventError> {
         loop {
             let event = renderer.try_borrow_mut()?
                 .events_chan.try_recv()?;
 
+            // Redrawn once the event is picked up and again once it is fully handled, for
+            // events that change the scene after waiting on the GPU or the network.
+            renderer.try_borrow_mut()?.request_redraw();
             let renderer_clone = renderer.clone();
             spawn_local(async move {
-                Self::handle_event(renderer_clone, event).await;
+                Self::handle_event(renderer_clone.clone(), event).await;
+                if let Ok(mut r) = renderer_clone.try_borrow_mut() {
+                    r.request_redraw();
+                }
             });
         }
     }
 
+    /// Read back the mesh under the cursor without holding the renderer, then report it
+    /// to the scene if it changed.
+    fn update_hover(renderer: &Rc<RefCell<Self>>) {
+        let Some(readback) = renderer
+            .try_borrow_mut()
+            .ok()
+            .and_then(|mut r| r.start_hover_pick())
+        else {
+            return;
+        };
+
+        let renderer = renderer.clone();
+        spawn_local(async move {
+            let hovered = readback.resolve().await;
+            renderer.borrow_mut().finish_hover_pick(hovered);
+        });
+    }
+
+    fn request_animation_frame(callback: &Closure<dyn FnMut(f32)>) {
+        let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+
+        global
+            .request_animation_frame(callback.as_ref().unchecked_ref())
+            .unwrap();
+    }
+
     pub fn run_render_loop(renderer: Rc<RefCell<Renderer<T>>>) {
-        let render_frame: Closure<dyn FnMut(f32)> = Closure::new(move |time: f32| {
+        // A single closure drives the whole loop by re-requesting itself, instead of a new
+        // closure being allocated and leaked every frame. It owns an Rc to itself, so it
+        // stays alive for as long as the loop runs.
+        let render_frame: FrameCallback = Rc::new(RefCell::new(None));
+        let next_frame = render_frame.clone();
+
+        *render_frame.borrow_mut() = Some(Closure::new(move |time: f32| {
             {
                 if let Err(e) = Self::drain_events(&renderer) {
                     match e {
@@ -643,39 +3377,56 @@ impl<T: Scene + 'static> Renderer<T> {
 
             {
                 if let Ok(mut r) = renderer.try_borrow_mut() {
-                    r.render(time);
+                    if r.needs_redraw() && r.frame_due(time) {
+                        r.redraw_requested = false;
+                        r.render(time);
+                    }
                 }
             }
 
-            Self::run_render_loop(renderer.clone());
-        });
-
-        let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+            Self::update_hover(&renderer);
 
-        global
-            .request_animation_frame(render_frame.as_ref().unchecked_ref())
-            .unwrap();
+            if let Some(callback) = next_frame.borrow().as_ref() {
+                Self::request_animation_frame(callback);
+            }
+        }));
 
-        render_frame.forget();
+        let first_frame = render_frame.borrow();
+        if let Some(callback) = first_frame.as_ref() {
+            Self::request_animation_frame(callback);
+        }
     }
 
     fn resize(&mut self, msg: ResizeMessage) {
-        let new_width = (msg.width * msg.scale_factor) as u32;
-        let new_height = (msg.height * msg.scale_factor) as u32;
-        if new_width != self.canvas.width() || new_height != self.canvas.height() {
+        let new_width = ((msg.width * msg.scale_factor) as u32).max(1);
+        let new_height = ((msg.height * msg.scale_factor) as u32).max(1);
+        self.scale_factor = msg.scale_factor;
+        let (width, height) = match &self.canvas {
+            Some(canvas) => (canvas.width(), canvas.height()),
+            None => (
+                self.context.surface_config.width,
+                self.context.surface_config.height,
+            ),
+        };
+        if new_width != width || new_height != height {
             self.context.surface_config.width = new_width;
             self.context.surface_config.height = new_height;
-            self.context
-                .surface
-                .configure(&self.context.device, &self.context.surface_config);
+            if let Some(surface) = &self.context.surface {
+                surface.configure(&self.context.device, &self.context.surface_config);
+            }
             self.recreate_depth_texture();
+            self.scene
+                .on_surface_configured(&self.context.surface_config, &self.context.device);
 
-            self.scene.resize(
-                new_width as f64,
-                new_height as f64,
-                msg.scale_factor,
-                &self.context.queue,
-            );
+            self.context.viewport = match self.letterbox {
+                Some(aspect_ratio) => Some(self.letterboxed_viewport(aspect_ratio)),
+                None => self
+                    .context
+                    .viewport
+                    .map(|viewport| viewport.clamped_to(new_width as f32, new_height as f32)),
+            };
+
+            self.resize_scene();
 
             info!(
                 "Resized: ({}, {}), scale: {}",
@@ -685,82 +3436,158 @@ impl<T: Scene + 'static> Renderer<T> {
     }
 
     pub fn mouse_move(&mut self, msg: MouseMessage) {
-        if (msg.buttons & 0x04) != 0 {
-            let delta_x = (msg.movement_x * msg.scale_factor) as f32;
-            let delta_y = (msg.movement_y * msg.scale_factor) as f32;
+        let cursor = Vec2::new(
+            (msg.offset_x * msg.scale_factor) as f32,
+            (msg.offset_y * msg.scale_factor) as f32,
+        );
+        self.cursor = Some(cursor);
+        if let Some(fm) = self.scene.frame_metadata_mut() {
+            fm.mouse_move = cursor.into();
+        }
+
+        let delta_x = (msg.movement_x * msg.scale_factor) as f32;
+        let delta_y = (msg.movement_y * msg.scale_factor) as f32;
+        let middle = (msg.buttons & 0x04) != 0;
+        let right = (msg.buttons & 0x02) != 0;
+
+        if right || (middle && msg.shift_key) {
+            self.scene.handle_pan(delta_x, delta_y);
+        } else if middle {
             self.scene.handle_orbit(delta_x, delta_y);
         }
     }
 
-    // currently this replaces everything, will need more sophisticated mechanisms later
-    pub async fn load_assets_async(renderer: Rc<RefCell<Renderer<T>>>) -> Result<(), ImportError> {
-        let (device, surface_format) = {
-            let r = renderer.borrow();
-            (r.context.device.clone(), r.context.surface_config.format)
-        };
+    /// Fetch the default model and replace the scene's meshes with it, see
+    /// `load_model_bytes`.
+    pub async fn load_assets_async(
+        renderer: Rc<RefCell<Renderer<T>>>,
+        on_progress: impl FnMut(f32),
+    ) -> Result<usize, ImportError> {
+        // currently this replaces everything, will need more sophisticated mechanisms later
+        let mut progress = LoadProgress::new(on_progress);
+        let (bytes, external) = fetch_model(DEFAULT_MODEL_URL, &mut progress).await?;
+        Self::load_model(renderer, bytes, external, &mut progress).await
+    }
 
-        let mut meshes = Vec::new();
+    /// Replace the scene's meshes with the model in `bytes`, e.g. a file the user picked,
+    /// and frame the camera on it. Returns the number of meshes the model became.
+    ///
+    /// `on_progress` is called with the fraction loaded, from 0 to 1, see `LoadProgress`.
+    /// Only buffers embedded in the file are read, there is no URL to fetch others from.
+    pub async fn load_model_bytes(
+        renderer: Rc<RefCell<Renderer<T>>>,
+        bytes: Vec<u8>,
+        on_progress: impl FnMut(f32),
+    ) -> Result<usize, ImportError> {
+        let format = ModelFormat::sniff(&bytes).ok_or(ImportError::NotGltf)?;
+        info!("Loading a {:?} glTF model of {} bytes", format, bytes.len());
+        let mut progress = LoadProgress::new(on_progress);
+        progress.downloaded(bytes.len(), bytes.len());
+        Self::load_model(renderer, bytes, ExternalBuffers::new(), &mut progress).await
+    }
 
-        let mut original_resources = {
-            let mut r = renderer.borrow_mut();
-            r.scene.clear();
-            std::mem::take(&mut r.resources)
+    async fn load_model(
+        renderer: Rc<RefCell<Renderer<T>>>,
+        bytes: Vec<u8>,
+        external: ExternalBuffers,
+        progress: &mut LoadProgress<'_>,
+    ) -> Result<usize, ImportError> {
+        // Parsing happens off the render thread when a decode worker is available, so
+        // frames keep being drawn while a large model is decoded.
+        let decoder = renderer.borrow().model_decoder.clone();
+        let model = match decoder {
+            Some(decoder) => decoder.decode(bytes, external).await?,
+            None => parse_gltf_with_buffers(&bytes, &external)?,
         };
 
-        let bounds = load_gltf_model(
-            &device,
-            &mut original_resources,
-            &mut meshes,
-            surface_format,
-        )
-        .await?;
-
-        {
+        let uploader = {
             let mut r = renderer.borrow_mut();
-            r.resources = original_resources;
+            let r = &mut *r;
+            ModelUploader::new(
+                &r.context.device,
+                &r.context.queue,
+                &mut r.resources,
+                &model,
+                r.context.surface_config.format,
+            )
+        };
 
-            for mesh in meshes {
-                r.scene.add_mesh(mesh);
+        // The old meshes stay on screen while the new ones are uploaded a few at a time,
+        // with a frame drawn in between.
+        let total = model.primitives.len();
+        let mut meshes = Vec::with_capacity(total);
+        for primitives in model.primitives.chunks(PRIMITIVES_PER_FRAME) {
+            {
+                let mut r = renderer.borrow_mut();
+                let r = &mut *r;
+                for primitive in primitives {
+                    match uploader.upload(&r.context.device, &mut r.resources, primitive) {
+                        Ok(mesh) => meshes.push(mesh),
+                        Err(err) => {
+                            for mesh in &meshes {
+                                r.resources.remove_mesh_buffers(mesh);
+                            }
+                            uploader.into_materials().remove(&mut r.resources);
+                            return Err(err.into());
+                        }
+                    }
+                }
             }
+            progress.uploaded(meshes.len(), total);
+            Self::next_animation_frame().await;
+        }
+        progress.uploaded(total, total);
 
-            if let Some(ModelBounds { min, max }) = bounds {
-                let center = ultraviolet::Vec3::new(
-                    (min[0] + max[0]) * 0.5,
-                    (min[1] + max[1]) * 0.5,
-                    (min[2] + max[2]) * 0.5,
-                );
-
-                let extent =
-                    ultraviolet::Vec3::new(max[0] - min[0], max[1] - min[1], max[2] - min[2]);
-                let radius =
-                    0.5 * (extent.x * extent.x + extent.y * extent.y + extent.z * extent.z).sqrt();
-                let radius = radius.max(1.0);
+        let mesh_count = meshes.len();
+        {
+            let mut r = renderer.borrow_mut();
+            let r = &mut *r;
+            r.request_redraw();
 
-                // set the camera position after load, so we are not disoriented
-                let eye_offset = ultraviolet::Vec3::new(0.0, radius * 0.05, radius * 0.25);
+            info!("Estimated GPU memory: {}", r.resources.gpu_memory_usage());
 
-                // Keep the near plane proportional to the model size to avoid
-                // extreme depth ranges when loading very large assets
-                let near_plane = (radius * 0.001).max(0.1);
+            for mesh in r.scene.meshes() {
+                r.resources.remove_mesh_buffers(mesh);
+            }
+            let materials = uploader.into_materials();
+            if let Some(old) = r.model_materials.insert(r.active_scene.clone(), materials) {
+                old.remove(&mut r.resources);
+            }
+            r.scene.clear();
+            let mesh_ids: Vec<MeshId> = meshes
+                .into_iter()
+                .map(|mesh| r.scene.add_mesh(mesh))
+                .collect();
+            if let Some(graph) = r.scene.scene_graph_mut() {
+                *graph = SceneGraph::from_model(&model, &mesh_ids);
+            }
 
-                // The far plane must be far enough to cover the entire model.
-                // Using a fixed upper clamp caused large models to be clipped
-                // completely; relying on the model radius instead.
-                let far_plane = (radius * 4.0).max(near_plane + 1.0);
-                r.scene.set_camera_depth_range(near_plane, far_plane);
-                r.scene.set_camera_look_at(center + eye_offset, center);
+            if let Some(bounds) = model.bounds {
+                r.scene.frame_bounds(&bounds);
+
+                if r.scene.grid().is_some_and(Grid::is_built) {
+                    let (extent, spacing) = Grid::size_for(&bounds);
+                    r.scene.add_grid(
+                        &r.context.device,
+                        &mut r.resources,
+                        r.context.surface_config.format,
+                        extent,
+                        spacing,
+                    );
+                }
             }
         }
 
-        Ok(())
+        info!("Loaded a model of {} meshes", mesh_count);
+        Ok(mesh_count)
     }
 
     async fn show_file_picker_and_load(
         renderer: Rc<RefCell<Renderer<T>>>,
-    ) -> Result<(), ImportError> {
-        // For now, we'll just call load_assets_async which loads the default model
-        // In a full implementation, we'd modify load_gltf_model to accept the file data
-        Self::load_assets_async(renderer).await
+    ) -> Result<usize, ImportError> {
+        // The worker has no DOM to show a picker from, so this still loads the default
+        // model. Picked files only need their bytes handed to `load_model_bytes`.
+        Self::load_assets_async(renderer, Self::post_load_progress).await
     }
 }
 
@@ -0,0 +1,480 @@
+use wgpu::util::DeviceExt;
+
+/// Axis-aligned rectangle with a top-left origin, in pixels or texels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// Handle to a 2D texture drawn by sprites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteTexture {
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl SpriteTexture {
+    pub fn new(texture: &wgpu::Texture) -> Self {
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            width: texture.width(),
+            height: texture.height(),
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Whole texture as a source rectangle, in texels.
+    pub fn full_rect(&self) -> Rect {
+        Rect::new(0.0, 0.0, self.width as f32, self.height as f32)
+    }
+}
+
+/// One textured quad of the overlay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub texture: SpriteTexture,
+    /// Where the sprite lands on the surface, in physical pixels.
+    pub dest: Rect,
+    /// Part of the texture to draw, in texels.
+    pub src: Rect,
+}
+
+/// Sprites queued for the next frame, drawn in order over the 3D scene.
+///
+/// The renderer empties the batch after every frame, so sprites are queued again each
+/// frame, typically from `Scene::update`.
+#[derive(Debug, Default)]
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
+struct OverlayUniform {
+    screen_size: [f32; 2],
+    depth_range: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug)]
+struct SpriteInstance {
+    dest: [f32; 4],
+    uv_rect: [f32; 4],
+}
+
+impl SpriteInstance {
+    fn new(sprite: &Sprite) -> Self {
+        let (width, height) = (
+            sprite.texture.width.max(1) as f32,
+            sprite.texture.height.max(1) as f32,
+        );
+        let (dest, src) = (sprite.dest, sprite.src);
+
+        Self {
+            dest: [dest.x, dest.y, dest.width, dest.height],
+            uv_rect: [
+                src.x / width,
+                src.y / height,
+                src.width / width,
+                src.height / height,
+            ],
+        }
+    }
+}
+
+/// Depth buffer drawn as a grayscale quad over the scene, to debug depth precision.
+pub(crate) struct DepthPreview {
+    pub view: wgpu::TextureView,
+    /// Where the quad lands on the surface, in physical pixels.
+    pub dest: Rect,
+    /// Camera near and far planes, swapped under reverse-Z: the reversed depth mapping
+    /// is the regular one with the two planes exchanged.
+    pub depth_range: [f32; 2],
+}
+
+const SPRITE_INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+
+/// Consecutive sprites sharing a texture, drawn with one instanced call.
+struct SpriteRun {
+    bind_group: usize,
+    instances: std::ops::Range<u32>,
+}
+
+/// Pass drawing `SpriteBatch`es in pixel coordinates after the 3D scene, with alpha
+/// blending and without depth testing. It can also show the depth buffer, see
+/// `DepthPreview`.
+pub(crate) struct OverlayPass {
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    uniform_layout: wgpu::BindGroupLayout,
+    texture_layout: wgpu::BindGroupLayout,
+    depth_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    shader: wgpu::ShaderModule,
+    pipeline: Option<wgpu::RenderPipeline>,
+    depth_pipeline: Option<wgpu::RenderPipeline>,
+    instance_buffer: Option<wgpu::Buffer>,
+    texture_bind_groups: Vec<(SpriteTexture, wgpu::BindGroup)>,
+    // Recreated when the depth texture is, e.g. after a resize
+    depth_bind_group: Option<(wgpu::TextureView, wgpu::BindGroup)>,
+    runs: Vec<SpriteRun>,
+    // Instance drawing the depth preview, after the sprites
+    depth_instance: Option<u32>,
+    // Scratch space reused every frame
+    instances: Vec<SpriteInstance>,
+}
+
+impl OverlayPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("overlay uniform buffer"),
+            contents: bytemuck::cast_slice(&[OverlayUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay bind group"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        // Depth is read as unfilterable floats with `textureLoad`, which every backend
+        // supports, so it needs no sampler.
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth preview bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        // Nearest filtering keeps pixel art and text crisp when drawn at integer scales.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sprite sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("overlay"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./overlay.wgsl").into()),
+        });
+
+        Self {
+            uniform_buffer,
+            uniform_bind_group,
+            uniform_layout,
+            texture_layout,
+            depth_layout,
+            sampler,
+            shader,
+            pipeline: None,
+            depth_pipeline: None,
+            instance_buffer: None,
+            texture_bind_groups: Vec::new(),
+            depth_bind_group: None,
+            runs: Vec::new(),
+            depth_instance: None,
+            instances: Vec::new(),
+        }
+    }
+
+    /// Quad pipeline shading with `fragment_entry`, reading its texture from a bind group
+    /// of `texture_layout`.
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        label: &str,
+        texture_layout: &wgpu::BindGroupLayout,
+        fragment_entry: &str,
+    ) -> wgpu::RenderPipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&self.uniform_layout, texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &SPRITE_INSTANCE_ATTRIBUTES,
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            // Drawn in its own pass after the scene, so there is no depth attachment.
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some(fragment_entry),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn texture_bind_group(&mut self, device: &wgpu::Device, texture: &SpriteTexture) -> usize {
+        if let Some(index) = self
+            .texture_bind_groups
+            .iter()
+            .position(|(bound, _)| bound == texture)
+        {
+            return index;
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite bind group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        self.texture_bind_groups.push((texture.clone(), bind_group));
+        self.texture_bind_groups.len() - 1
+    }
+
+    fn update_depth_bind_group(&mut self, device: &wgpu::Device, view: &wgpu::TextureView) {
+        if matches!(&self.depth_bind_group, Some((bound, _)) if bound == view) {
+            return;
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth preview bind group"),
+            layout: &self.depth_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(view),
+            }],
+        });
+        self.depth_bind_group = Some((view.clone(), bind_group));
+    }
+
+    /// Upload the sprites, group them by texture and add the depth preview, if any.
+    ///
+    /// Returns `false` when there is nothing to draw.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        sprites: &[Sprite],
+        screen_size: (f32, f32),
+        depth_preview: Option<DepthPreview>,
+    ) -> bool {
+        self.runs.clear();
+        self.depth_instance = None;
+        if sprites.is_empty() && depth_preview.is_none() {
+            return false;
+        }
+
+        if !sprites.is_empty() && self.pipeline.is_none() {
+            self.pipeline = Some(self.create_pipeline(
+                device,
+                surface_format,
+                "overlay_sprites",
+                &self.texture_layout,
+                "fs_main",
+            ));
+        }
+
+        // Bind groups of textures no longer drawn would otherwise keep them alive.
+        self.texture_bind_groups
+            .retain(|(texture, _)| sprites.iter().any(|sprite| &sprite.texture == texture));
+
+        for (i, sprite) in sprites.iter().enumerate() {
+            let bind_group = self.texture_bind_group(device, &sprite.texture);
+            let i = i as u32;
+            match self.runs.last_mut() {
+                Some(run) if run.bind_group == bind_group => run.instances.end = i + 1,
+                _ => self.runs.push(SpriteRun {
+                    bind_group,
+                    instances: i..i + 1,
+                }),
+            }
+        }
+
+        self.instances.clear();
+        self.instances
+            .extend(sprites.iter().map(SpriteInstance::new));
+
+        let mut depth_range = [0.0; 2];
+        if let Some(preview) = depth_preview {
+            if self.depth_pipeline.is_none() {
+                self.depth_pipeline = Some(self.create_pipeline(
+                    device,
+                    surface_format,
+                    "overlay_depth_preview",
+                    &self.depth_layout,
+                    "fs_depth",
+                ));
+            }
+            self.update_depth_bind_group(device, &preview.view);
+
+            let dest = preview.dest;
+            self.depth_instance = Some(self.instances.len() as u32);
+            self.instances.push(SpriteInstance {
+                dest: [dest.x, dest.y, dest.width, dest.height],
+                uv_rect: [0.0, 0.0, 1.0, 1.0],
+            });
+            depth_range = preview.depth_range;
+        }
+
+        let contents: &[u8] = bytemuck::cast_slice(&self.instances);
+        match &self.instance_buffer {
+            Some(buffer) if buffer.size() >= contents.len() as wgpu::BufferAddress => {
+                queue.write_buffer(buffer, 0, contents);
+            }
+            _ => {
+                self.instance_buffer = Some(device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("sprite instance buffer"),
+                        contents,
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    },
+                ));
+            }
+        }
+
+        let uniform = OverlayUniform {
+            screen_size: [screen_size.0.max(1.0), screen_size.1.max(1.0)],
+            depth_range,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        true
+    }
+
+    /// Draw the sprites and depth preview uploaded by the last `prepare` call.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some(instance_buffer) = &self.instance_buffer else {
+            return;
+        };
+
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+
+        if let Some(pipeline) = self.pipeline.as_ref().filter(|_| !self.runs.is_empty()) {
+            render_pass.set_pipeline(pipeline);
+            for run in &self.runs {
+                render_pass.set_bind_group(1, &self.texture_bind_groups[run.bind_group].1, &[]);
+                render_pass.draw(0..4, run.instances.clone());
+            }
+        }
+
+        if let (Some(instance), Some(pipeline), Some((_, bind_group))) = (
+            self.depth_instance,
+            &self.depth_pipeline,
+            &self.depth_bind_group,
+        ) {
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw(0..4, instance..instance + 1);
+        }
+    }
+}
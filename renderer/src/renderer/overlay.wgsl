@@ -0,0 +1,67 @@
+struct OverlayUniform {
+    screen_size: vec2<f32>,
+    // Camera near and far planes for the depth preview, swapped under reverse-Z.
+    depth_range: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> overlay: OverlayUniform;
+
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+
+// Bound instead of the sprite texture by the depth preview pipeline.
+@group(1) @binding(2) var depth_texture: texture_2d<f32>;
+
+struct SpriteInput {
+    // x, y, width, height in pixels, origin at the top left of the surface.
+    @location(0) dest: vec4<f32>,
+    // Same layout in normalized texture coordinates.
+    @location(1) uv_rect: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Four vertices per sprite, drawn as a triangle strip.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, sprite: SpriteInput) -> VertexOutput {
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32((vertex_index >> 1u) & 1u));
+    let pixel = sprite.dest.xy + corner * sprite.dest.zw;
+
+    // Orthographic projection from pixels to NDC, flipping y so it grows downwards.
+    let ndc = vec2<f32>(
+        pixel.x / overlay.screen_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / overlay.screen_size.y * 2.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = sprite.uv_rect.xy + corner * sprite.uv_rect.zw;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(sprite_texture, sprite_sampler, in.uv);
+}
+
+// Depth buffer in grayscale, black at the near plane to white at the far plane. Distance
+// is shown on a log scale so both nearby and distant geometry stay distinguishable.
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(depth_texture));
+    let texel = vec2<i32>(min(in.uv * size, size - 1.0));
+    let depth = textureLoad(depth_texture, texel, 0).r;
+
+    // Inverse of the perspective depth mapping, giving the view space distance.
+    let near = overlay.depth_range.x;
+    let far = overlay.depth_range.y;
+    let distance = near * far / (far - depth * (far - near));
+
+    let closest = min(near, far);
+    let farthest = max(near, far);
+    let shade = clamp(log(distance / closest) / log(farthest / closest), 0.0, 1.0);
+    return vec4<f32>(vec3<f32>(shade), 1.0);
+}
@@ -0,0 +1,432 @@
+use futures::channel::oneshot;
+use ultraviolet::{Mat4, Vec4};
+use wgpu::util::DeviceExt;
+
+use super::{
+    scene::{Mesh, MeshId},
+    GpuResources, RendererContext, DEPTH_FORMAT,
+};
+use crate::camera::Frustum;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Bytes of each mesh id in the id buffer, read as a `vec4<u32>`.
+const ID_SIZE: u64 = 16;
+
+/// Pixel of the object id target copied for reading, with the meshes it may refer to.
+pub(crate) struct PickReadback {
+    buffer: wgpu::Buffer,
+    mapped: oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    // Id of the mesh at each index when the pick was rendered
+    ids: Vec<Option<MeshId>>,
+}
+
+impl PickReadback {
+    /// Mesh under the picked pixel, `None` where no mesh was drawn.
+    pub async fn resolve(self) -> Option<MeshId> {
+        if !matches!(self.mapped.await, Ok(Ok(()))) {
+            log::warn!("Failed to read back the picked object id");
+            return None;
+        }
+
+        let value = {
+            let data = self.buffer.slice(..).get_mapped_range();
+            u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+        };
+        self.buffer.unmap();
+
+        let index = value.checked_sub(1)?;
+        self.ids.get(index as usize).copied().flatten()
+    }
+}
+
+/// Draws the index of each mesh into an `R32Uint` target to find the one under a pixel.
+///
+/// The picked pixel is stretched over one texel targets, so only it is rasterized and
+/// only the meshes in front of it are drawn, and only when a pick is requested.
+pub(crate) struct PickingPass {
+    view_proj_buffer: wgpu::Buffer,
+    view_proj_bind_group: wgpu::BindGroup,
+    id_layout: wgpu::BindGroupLayout,
+    // Ids 1..=n at dynamic offsets, grown as scenes get more meshes
+    ids: Option<(usize, wgpu::BindGroup)>,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    // Built for the depth convention in the flag, rebuilt when it changes
+    pipeline: Option<(bool, wgpu::RenderPipeline)>,
+    id_texture: wgpu::Texture,
+    id_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+}
+
+impl PickingPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let view_proj_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("picking view projection buffer"),
+            contents: bytemuck::cast_slice(Mat4::identity().as_slice()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let view_proj_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Picking view projection layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let view_proj_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Picking view projection bind group"),
+            layout: &view_proj_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: view_proj_buffer.as_entire_binding(),
+            }],
+        });
+
+        let id_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Picking object id layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(ID_SIZE),
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Picking pipeline layout"),
+            bind_group_layouts: &[&view_proj_layout, &id_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Picking shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./picking.wgsl").into()),
+        });
+
+        let target = |label, format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let id_texture = target("object id target", ID_FORMAT);
+        let id_view = id_texture.create_view(&Default::default());
+        let depth_view = target("object id depth", DEPTH_FORMAT).create_view(&Default::default());
+
+        Self {
+            view_proj_buffer,
+            view_proj_bind_group,
+            id_layout,
+            ids: None,
+            pipeline_layout,
+            shader,
+            pipeline: None,
+            id_texture,
+            id_view,
+            depth_view,
+        }
+    }
+
+    fn ensure_pipeline(&mut self, device: &wgpu::Device, reverse_z: bool) {
+        if self
+            .pipeline
+            .as_ref()
+            .is_some_and(|(built, _)| *built == reverse_z)
+        {
+            return;
+        }
+
+        let depth_compare = if reverse_z {
+            wgpu::CompareFunction::GreaterEqual
+        } else {
+            wgpu::CompareFunction::LessEqual
+        };
+
+        // Position and instance model matrix at the locations of the standard layout.
+        let model_attributes = wgpu::vertex_attr_array![
+            1 => Float32x4, 2 => Float32x4, 3 => Float32x4, 4 => Float32x4
+        ];
+        let vertex_buffers = [
+            wgpu::VertexBufferLayout {
+                array_stride: 12,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+            },
+            wgpu::VertexBufferLayout {
+                array_stride: 64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &model_attributes,
+            },
+        ];
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Picking pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &vertex_buffers,
+            },
+            // Back faces are pickable too, so open meshes can be selected from behind.
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(ID_FORMAT.into())],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        self.pipeline = Some((reverse_z, pipeline));
+    }
+
+    /// Make room for the ids of `count` meshes.
+    fn ensure_ids(&mut self, device: &wgpu::Device, count: usize) -> u64 {
+        let stride = device
+            .limits()
+            .min_uniform_buffer_offset_alignment
+            .max(ID_SIZE as u32) as u64;
+        if self
+            .ids
+            .as_ref()
+            .is_some_and(|(capacity, _)| *capacity >= count)
+        {
+            return stride;
+        }
+
+        let capacity = count.next_power_of_two().max(64);
+        let mut contents = vec![0u8; capacity * stride as usize];
+        for index in 0..capacity {
+            let offset = index * stride as usize;
+            let id = index as u32 + 1;
+            contents[offset..offset + 4].copy_from_slice(&id.to_le_bytes());
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("picking object id buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Picking object id bind group"),
+            layout: &self.id_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(ID_SIZE),
+                }),
+            }],
+        });
+
+        self.ids = Some((capacity, bind_group));
+        stride
+    }
+
+    /// Draw `meshes` at surface pixel (`x`, `y`) and copy the id of the closest one
+    /// into a buffer, `None` if the pixel is outside the surface or its viewport.
+    ///
+    /// `ids` gives the id of the mesh at each index, see `Scene::mesh_id`.
+    pub fn pick(
+        &mut self,
+        context: &RendererContext,
+        resources: &GpuResources,
+        meshes: &[Mesh],
+        ids: Vec<Option<MeshId>>,
+        view_proj: Mat4,
+        (x, y): (u32, u32),
+    ) -> Option<PickReadback> {
+        let device = &context.device;
+        if x >= context.surface_config.width || y >= context.surface_config.height {
+            return None;
+        }
+        let view_proj = pixel_view_proj(context, view_proj, (x, y))?;
+        let frustum = Frustum::from_view_proj(view_proj);
+
+        self.ensure_pipeline(device, resources.reverse_z());
+        let stride = self.ensure_ids(device, meshes.len());
+        context.queue.write_buffer(
+            &self.view_proj_buffer,
+            0,
+            bytemuck::cast_slice(view_proj.as_slice()),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Picking encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Picking pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    depth_slice: None,
+                    view: &self.id_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(resources.depth_clear_value()),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            let (_, pipeline) = self.pipeline.as_ref().expect("pipeline was just built");
+            let (_, id_bind_group) = self.ids.as_ref().expect("ids were just allocated");
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.view_proj_bind_group, &[]);
+
+            for (index, mesh) in meshes.iter().enumerate() {
+                // Lines and point sprites have no surface to click on.
+                if !mesh.visible
+                    || !mesh.passes.picking()
+                    || resources.pipeline_options(mesh.pipeline_index).topology
+                        != wgpu::PrimitiveTopology::TriangleList
+                {
+                    continue;
+                }
+                if mesh
+                    .bounds
+                    .as_ref()
+                    .is_some_and(|bounds| !frustum.intersects(bounds))
+                {
+                    continue;
+                }
+
+                let offset = (index as u64 * stride) as u32;
+                render_pass.set_bind_group(1, id_bind_group, &[offset]);
+                render_pass.set_vertex_buffer(
+                    0,
+                    resources.get_buffer(&mesh.position_buffer_index).slice(..),
+                );
+                render_pass
+                    .set_vertex_buffer(1, resources.get_buffer(&mesh.model_buffer_index).slice(..));
+
+                match &mesh.index_buffer_index {
+                    Some(index_buffer_index) => {
+                        render_pass.set_index_buffer(
+                            resources.get_buffer(index_buffer_index).slice(..),
+                            mesh.index_format,
+                        );
+                        render_pass.draw_indexed(0..mesh.index_count, 0, 0..mesh.instance_count);
+                    }
+                    None => render_pass.draw(0..mesh.vertex_count, 0..mesh.instance_count),
+                }
+            }
+        }
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("object id read buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        let (tx, mapped) = oneshot::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+
+        Some(PickReadback {
+            buffer,
+            mapped,
+            ids,
+        })
+    }
+}
+
+/// `view_proj` followed by a scale and offset in clip space that stretch surface pixel
+/// (`x`, `y`) over the whole clip square, `None` if the pixel is outside the viewport.
+fn pixel_view_proj(context: &RendererContext, view_proj: Mat4, (x, y): (u32, u32)) -> Option<Mat4> {
+    let (left, top) = context
+        .viewport
+        .map_or((0.0, 0.0), |viewport| (viewport.x, viewport.y));
+    let (width, height) = context.render_extent();
+    let (x, y) = (x as f32 + 0.5 - left, y as f32 + 0.5 - top);
+    if x < 0.0 || y < 0.0 || x > width || y > height {
+        return None;
+    }
+
+    // Pixel center in normalized device coordinates, a pixel is 2 / width across.
+    let center_x = x / width * 2.0 - 1.0;
+    let center_y = 1.0 - y / height * 2.0;
+    let to_pixel = Mat4::new(
+        Vec4::new(width, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, height, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, 1.0, 0.0),
+        Vec4::new(-center_x * width, -center_y * height, 0.0, 1.0),
+    );
+    Some(to_pixel * view_proj)
+}
@@ -0,0 +1,23 @@
+@group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
+
+// Index of the mesh being drawn plus one, 0 is left where nothing was drawn.
+@group(1) @binding(0) var<uniform> object_id: vec4<u32>;
+
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) model_col0: vec4<f32>,
+    @location(2) model_col1: vec4<f32>,
+    @location(3) model_col2: vec4<f32>,
+    @location(4) model_col3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    let model = mat4x4<f32>(in.model_col0, in.model_col1, in.model_col2, in.model_col3);
+    return view_proj * model * vec4<f32>(in.pos, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) u32 {
+    return object_id.x;
+}
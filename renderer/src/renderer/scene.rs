@@ -1,9 +1,19 @@
-use ultraviolet::Mat4;
+use ultraviolet::{Mat4, Vec2, Vec3};
 use wgpu::util::DeviceExt;
 
 use crate::{
-    camera::Camera,
-    renderer::{self, BufferIndex, GpuResources, Index, ModelMatrix, Normal, Position, UV},
+    camera::{Camera, Frustum},
+    gltf::ModelBounds,
+    message::{KeyboardMessage, WheelMessage},
+    renderer::{
+        self,
+        background::Background,
+        grid::Grid,
+        merge::{self, MergeStats, MeshMerger},
+        overlay::{Rect, Sprite, SpriteBatch, SpriteTexture},
+        scene_graph::{NodeId, SceneGraph},
+        AttributeData, BufferIndex, GpuResources, Index, ModelMatrix, Normal, Position, UV,
+    },
 };
 
 pub struct UniformResource {
@@ -12,7 +22,27 @@ pub struct UniformResource {
     pub bind_group_layout: wgpu::BindGroupLayout,
 }
 
+/// Point cloud point diameter used until `Scene::set_point_size` is called, in pixels.
+pub const DEFAULT_POINT_SIZE: f32 = 4.0;
+
+/// Number of app-defined floats available in `FrameMetadata::user_params`.
+pub const USER_PARAM_COUNT: usize = 4;
+
 /// Simple uniform data.
+///
+/// Shaders see it at `@group(0) @binding(0)` with this layout:
+///
+/// ```wgsl
+/// struct UniformData {
+///     mouse_move: vec2<f32>,
+///     mouse_click: vec2<f32>,
+///     resolution: vec2<f32>,
+///     time: f32,
+///     point_size: f32,
+///     camera_position: vec4<f32>,
+///     user_params: vec4<f32>,
+/// }
+/// ```
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
 pub struct FrameMetadata {
@@ -20,8 +50,11 @@ pub struct FrameMetadata {
     pub mouse_click: [f32; 2],
     pub resolution: [f32; 2],
     time: f32,
-    _padding0: f32,
+    /// Diameter of point cloud points in physical pixels.
+    pub point_size: f32,
     pub camera_position: [f32; 4],
+    /// Free for app-specific shader globals, e.g. a selection color or a time scale.
+    pub user_params: [f32; USER_PARAM_COUNT],
 }
 
 impl FrameMetadata {
@@ -30,7 +63,7 @@ impl FrameMetadata {
             resolution: dimension.into(),
             mouse_move: [std::f32::MIN, std::f32::MIN],
             mouse_click: [std::f32::MIN, std::f32::MIN],
-            _padding0: 0.0,
+            point_size: DEFAULT_POINT_SIZE,
             camera_position: [0.0, 0.0, 0.0, 1.0],
             ..Default::default()
         }
@@ -40,38 +73,58 @@ impl FrameMetadata {
         self.camera_position = [position.x, position.y, position.z, 1.0];
     }
 
+    /// Set `user_params[index]`, read in WGSL as `uni.user_params[index]`.
+    ///
+    /// Panics if `index` is not below `USER_PARAM_COUNT`.
+    pub fn set_user_param(&mut self, index: usize, value: f32) {
+        self.user_params[index] = value;
+    }
+
+    /// Set the shader `time` uniform, in seconds.
+    pub fn set_time(&mut self, seconds: f32) {
+        self.time = seconds;
+    }
+
     pub fn update_dimension(&mut self, dimension: ultraviolet::Vec2) {
         self.resolution = dimension.into();
     }
 
-    pub fn create_uniform_resource(self, device: &wgpu::Device) -> UniformResource {
+    /// Bind group with the frame metadata at binding 0 and `light` at binding 1.
+    pub fn create_uniform_resource(self, device: &wgpu::Device, light: &Light) -> UniformResource {
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("frame metadata uniform buffer"),
             contents: bytemuck::cast_slice(&[self][..]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Uniform bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
+            entries: &[uniform_entry(0), uniform_entry(1)],
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Uniform bind group"),
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light.buffer().as_entire_binding(),
+                },
+            ],
         });
 
         UniformResource {
@@ -82,19 +135,378 @@ impl FrameMetadata {
     }
 }
 
+/// Directional light data, seen by shaders at `@group(0) @binding(1)` as
+///
+/// ```wgsl
+/// struct Light {
+///     direction: vec4<f32>,
+///     color: vec4<f32>,
+/// }
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, PartialEq)]
+pub struct LightUniform {
+    /// Normalized direction the light travels in, w is 0.
+    pub direction: [f32; 4],
+    /// Linear RGB, w is 1.
+    pub color: [f32; 4],
+}
+
+/// The scene's directional light, which the glTF shader uses for Lambert shading.
+pub struct Light {
+    uniform: LightUniform,
+    buffer: wgpu::Buffer,
+}
+
+impl Light {
+    /// Warm light from above, slightly in front of the origin.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let direction = -Vec3::new(0.35, 1.0, 0.45).normalized();
+        let uniform = LightUniform {
+            direction: [direction.x, direction.y, direction.z, 0.0],
+            color: [1.0, 0.95, 0.85, 1.0],
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("light uniform buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { uniform, buffer }
+    }
+
+    pub fn uniform(&self) -> LightUniform {
+        self.uniform
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Direction the light travels in, e.g. `-Vec3::unit_y()` for light from straight
+    /// above. Zero vectors are ignored.
+    pub fn set_direction(&mut self, direction: Vec3, queue: &wgpu::Queue) {
+        if direction.mag_sq() <= f32::EPSILON {
+            return;
+        }
+        let direction = direction.normalized();
+        self.uniform.direction = [direction.x, direction.y, direction.z, 0.0];
+        self.upload(queue);
+    }
+
+    /// Linear RGB color of the light, components above 1 make it brighter.
+    pub fn set_color(&mut self, color: [f32; 3], queue: &wgpu::Queue) {
+        self.uniform.color = [color[0], color[1], color[2], 1.0];
+        self.upload(queue);
+    }
+
+    fn upload(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
+
+/// Per-object shader data, a lightweight stand-in for a material.
+///
+/// Bound at `GpuResources::object_params_group()`, right after the scene's own groups:
+///
+/// ```wgsl
+/// struct ObjectParams {
+///     color: vec4<f32>,
+///     params: vec4<f32>,
+/// }
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, PartialEq)]
+pub struct ObjectParams {
+    /// Tint multiplied into the mesh color.
+    pub color: [f32; 4],
+    /// Free for shader-specific values such as roughness.
+    pub params: [f32; 4],
+}
+
+impl Default for ObjectParams {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            params: [0.0; 4],
+        }
+    }
+}
+
+impl ObjectParams {
+    pub fn with_color(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+}
+
 pub struct Mesh {
     pub pipeline_index: usize,
     pub position_buffer_index: BufferIndex<Position>,
     pub normal_buffer_index: BufferIndex<Normal>,
     pub uv_buffer_index: BufferIndex<UV>,
+    /// Buffers of the attributes other than position, normal and uv.
+    pub attribute_buffers: Vec<(VertexAttribute, BufferIndex<AttributeData>)>,
     pub model_buffer_index: BufferIndex<ModelMatrix>,
-    pub index_buffer_index: BufferIndex<Index>,
+    /// `None` draws the vertices in order, without an index buffer.
+    pub index_buffer_index: Option<BufferIndex<Index>>,
     pub index_format: wgpu::IndexFormat,
+    /// Number of indices, 0 for a mesh without an index buffer.
     pub index_count: u32,
+    pub vertex_count: u32,
     pub instance_count: u32,
+    /// Index of the mesh's `ObjectParams`, `None` binds the defaults.
+    pub params_index: Option<usize>,
+    pub blend_mode: BlendMode,
+    /// Translation of the model matrix, blended meshes are sorted by its distance to
+    /// the camera.
+    pub origin: Vec3,
+    /// Name to look the mesh up by, e.g. its glTF node's.
+    pub name: Option<String>,
+    /// Hidden meshes keep their buffers but are neither drawn nor picked.
+    pub visible: bool,
+    /// Which passes draw the mesh when it is visible.
+    pub passes: MeshPasses,
+    /// World-space box around the mesh, meshes outside the view are not drawn. `None`
+    /// meshes are always drawn, e.g. instanced ones.
+    pub bounds: Option<ModelBounds>,
+}
+
+/// Indices into `meshes` in the order to draw them: opaque meshes as they are, then
+/// blended ones from the farthest to the closest to `eye`.
+///
+/// Without an eye to sort by, blended meshes keep their order.
+pub(crate) fn draw_order(meshes: &[Mesh], eye: Option<Vec3>) -> Vec<usize> {
+    let (mut blended, mut order): (Vec<usize>, Vec<usize>) =
+        (0..meshes.len()).partition(|&index| meshes[index].blend_mode == BlendMode::Blend);
+
+    if let Some(eye) = eye {
+        let distance = |index: usize| (meshes[index].origin - eye).mag_sq();
+        blended.sort_by(|&a, &b| distance(b).total_cmp(&distance(a)));
+    }
+
+    order.append(&mut blended);
+    order
+}
+
+/// Drop the meshes whose bounds are outside `frustum` from `order`, returning how many
+/// visible ones were dropped.
+pub(crate) fn cull_meshes(order: &mut Vec<usize>, meshes: &[Mesh], frustum: &Frustum) -> usize {
+    let mut culled = 0;
+    order.retain(|&index| {
+        let mesh = &meshes[index];
+        let inside = mesh
+            .bounds
+            .as_ref()
+            .is_none_or(|bounds| frustum.intersects(bounds));
+        if !inside && mesh.visible && mesh.passes.color() {
+            culled += 1;
+        }
+        inside
+    });
+    culled
+}
+
+/// Box around every visible mesh with known bounds, `None` if there is none.
+pub(crate) fn visible_bounds(meshes: &[Mesh]) -> Option<ModelBounds> {
+    meshes
+        .iter()
+        .filter(|mesh| mesh.visible)
+        .filter_map(|mesh| mesh.bounds)
+        .reduce(|bounds, other| bounds.union(&other))
+}
+
+/// How a mesh's color combines with what is already drawn behind it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Replaces the color behind it and writes depth.
+    #[default]
+    Opaque,
+    /// Blended by its alpha over what is behind it, without writing depth. Drawn after
+    /// the opaque meshes, farthest first.
+    Blend,
+}
+
+/// Passes that draw a mesh, see `Scene::merge_static_meshes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshPasses {
+    /// Drawn to the screen and into the picking pass.
+    #[default]
+    All,
+    /// Only drawn to the screen, e.g. a merged mesh standing in for the meshes it holds.
+    Color,
+    /// Only drawn into the picking pass, e.g. the originals of a merged mesh.
+    Picking,
+}
+
+impl MeshPasses {
+    pub fn color(self) -> bool {
+        self != MeshPasses::Picking
+    }
+
+    pub fn picking(self) -> bool {
+        self != MeshPasses::Color
+    }
+}
+
+impl Mesh {
+    /// Vertices processed per instance: the index count for indexed meshes, the vertex
+    /// count otherwise.
+    pub fn draw_count(&self) -> u32 {
+        match self.index_buffer_index {
+            Some(_) => self.index_count,
+            None => self.vertex_count,
+        }
+    }
+
+    /// Triangles drawn by the mesh across all of its instances.
+    pub fn triangle_count(&self) -> u32 {
+        self.draw_count() / 3 * self.instance_count
+    }
+
+    /// Buffer in `GpuResources` holding `attribute`, if the mesh has it.
+    pub fn vertex_buffer_index(
+        &self,
+        attribute: VertexAttribute,
+    ) -> Option<BufferIndex<AttributeData>> {
+        match attribute {
+            VertexAttribute::Position => Some(self.position_buffer_index.untyped()),
+            VertexAttribute::Normal => Some(self.normal_buffer_index.untyped()),
+            VertexAttribute::Uv => Some(self.uv_buffer_index.untyped()),
+            _ => self
+                .attribute_buffers
+                .iter()
+                .find(|(buffer_attribute, _)| *buffer_attribute == attribute)
+                .map(|&(_, buffer)| buffer),
+        }
+    }
+}
+
+/// Handle to a mesh in a `MeshStore`, unaffected by other meshes being removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshId {
+    slot: u32,
+    generation: u32,
+}
+
+#[derive(Default)]
+struct MeshSlot {
+    generation: u32,
+    // Position of the mesh in `MeshStore::meshes`, `None` while the slot is free
+    dense: Option<u32>,
+}
+
+/// Meshes of a scene, kept contiguous for drawing and addressed by `MeshId`.
+///
+/// Removing a mesh moves the last one into its place, ids stay valid since they point
+/// at a slot that follows the mesh. Slots are reused with a new generation, so ids of
+/// removed meshes never resolve to a later one.
+#[derive(Default)]
+pub struct MeshStore {
+    meshes: Vec<Mesh>,
+    // Slot of each mesh in `meshes`
+    owners: Vec<u32>,
+    slots: Vec<MeshSlot>,
+    free_slots: Vec<u32>,
 }
 
-type VertexBufferSet = (BufferIndex<Position>, BufferIndex<Normal>, BufferIndex<UV>);
+impl MeshStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, mesh: Mesh) -> MeshId {
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(MeshSlot::default());
+                self.slots.len() as u32 - 1
+            }
+        };
+
+        self.slots[slot as usize].dense = Some(self.meshes.len() as u32);
+        self.meshes.push(mesh);
+        self.owners.push(slot);
+
+        MeshId {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        }
+    }
+
+    pub fn remove(&mut self, id: MeshId) -> Option<Mesh> {
+        let dense = self.dense_index(id)?;
+
+        let slot = &mut self.slots[id.slot as usize];
+        slot.dense = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.slot);
+
+        self.owners.swap_remove(dense);
+        if let Some(&moved) = self.owners.get(dense) {
+            self.slots[moved as usize].dense = Some(dense as u32);
+        }
+        Some(self.meshes.swap_remove(dense))
+    }
+
+    pub fn get(&self, id: MeshId) -> Option<&Mesh> {
+        self.dense_index(id).map(|dense| &self.meshes[dense])
+    }
+
+    pub fn get_mut(&mut self, id: MeshId) -> Option<&mut Mesh> {
+        self.dense_index(id).map(|dense| &mut self.meshes[dense])
+    }
+
+    /// Every mesh, in no particular order once meshes have been removed.
+    pub fn as_slice(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Remove every mesh, invalidating all ids handed out so far.
+    pub fn clear(&mut self) {
+        for slot in self.owners.drain(..) {
+            let slot_data = &mut self.slots[slot as usize];
+            slot_data.dense = None;
+            slot_data.generation = slot_data.generation.wrapping_add(1);
+            self.free_slots.push(slot);
+        }
+        self.meshes.clear();
+    }
+
+    /// Id of the mesh at `index` in `as_slice`.
+    pub fn id_at(&self, index: usize) -> Option<MeshId> {
+        let slot = *self.owners.get(index)?;
+        Some(MeshId {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        })
+    }
+
+    fn dense_index(&self, id: MeshId) -> Option<usize> {
+        let slot = self.slots.get(id.slot as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.dense.map(|dense| dense as usize)
+    }
+}
+
+type VertexBufferSet = (
+    BufferIndex<Position>,
+    BufferIndex<Normal>,
+    BufferIndex<UV>,
+    u32,
+);
 type IndexBufferInfo = (BufferIndex<Index>, u32, wgpu::IndexFormat);
 
 pub fn mesh_vertex_layout() -> [wgpu::VertexBufferLayout<'static>; 4] {
@@ -155,12 +567,174 @@ pub fn mesh_vertex_layout() -> [wgpu::VertexBufferLayout<'static>; 4] {
     ]
 }
 
+/// Per-vertex attribute of a mesh, each one kept in its own vertex buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    Uv,
+    Color,
+    Tangent,
+    Joints,
+    Weights,
+}
+
+impl VertexAttribute {
+    /// The attributes every mesh has, bound by pipelines created from plain
+    /// `wgpu::VertexBufferLayout`s such as `mesh_vertex_layout`.
+    pub const STANDARD: [VertexAttribute; 3] = [
+        VertexAttribute::Position,
+        VertexAttribute::Normal,
+        VertexAttribute::Uv,
+    ];
+
+    pub fn format(self) -> wgpu::VertexFormat {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::Uv => wgpu::VertexFormat::Float32x2,
+            VertexAttribute::Color | VertexAttribute::Tangent | VertexAttribute::Weights => {
+                wgpu::VertexFormat::Float32x4
+            }
+            VertexAttribute::Joints => wgpu::VertexFormat::Uint16x4,
+        }
+    }
+}
+
+/// Vertex buffers of a pipeline: one per attribute at the shader location of its
+/// position in the list, then the instance model matrix over the next four locations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    vertex_attributes: Vec<wgpu::VertexAttribute>,
+    instance_attributes: [wgpu::VertexAttribute; 4],
+}
+
+impl VertexLayout {
+    /// Position, normal and uv, the same layout as `mesh_vertex_layout`.
+    pub fn standard() -> Self {
+        VertexAttribute::STANDARD
+            .into_iter()
+            .fold(VertexLayoutBuilder::new(), VertexLayoutBuilder::with)
+            .build()
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Layouts to create the pipeline with, in vertex buffer slot order.
+    pub fn buffer_layouts(&self) -> Vec<wgpu::VertexBufferLayout<'_>> {
+        let per_vertex = self
+            .vertex_attributes
+            .iter()
+            .map(|attribute| wgpu::VertexBufferLayout {
+                array_stride: attribute.format.size(),
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: std::slice::from_ref(attribute),
+            });
+
+        per_vertex
+            .chain(std::iter::once(wgpu::VertexBufferLayout {
+                array_stride: 64,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &self.instance_attributes,
+            }))
+            .collect()
+    }
+}
+
+/// Assembles a `VertexLayout` from the attributes a shader reads.
+#[derive(Clone, Debug, Default)]
+pub struct VertexLayoutBuilder {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayoutBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `attribute` at the next shader location, adding it twice has no effect.
+    pub fn with(mut self, attribute: VertexAttribute) -> Self {
+        if !self.attributes.contains(&attribute) {
+            self.attributes.push(attribute);
+        }
+        self
+    }
+
+    pub fn build(self) -> VertexLayout {
+        let vertex_attributes = self
+            .attributes
+            .iter()
+            .enumerate()
+            .map(|(location, attribute)| wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: location as u32,
+                format: attribute.format(),
+            })
+            .collect();
+
+        let first_instance_location = self.attributes.len() as u32;
+        let instance_attributes = std::array::from_fn(|column| wgpu::VertexAttribute {
+            offset: column as u64 * 16,
+            shader_location: first_instance_location + column as u32,
+            format: wgpu::VertexFormat::Float32x4,
+        });
+
+        VertexLayout {
+            attributes: self.attributes,
+            vertex_attributes,
+            instance_attributes,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MeshBuildError {
+    #[error(
+        "vertex attributes differ in length: {positions} positions, {normals} normals, {uvs} uvs"
+    )]
+    AttributeLengthMismatch {
+        positions: usize,
+        normals: usize,
+        uvs: usize,
+    },
+
+    #[error("{attribute:?} data holds {actual} entries for {expected} vertices")]
+    AttributeCountMismatch {
+        attribute: VertexAttribute,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("the mesh already has {0:?} data")]
+    DuplicateAttribute(VertexAttribute),
+}
+
+/// Reasons a scene could not be set up, see `Scene::try_setup`.
+#[derive(Debug, thiserror::Error)]
+pub enum SceneError {
+    #[error("failed to create pipeline '{name}': {message}")]
+    Pipeline { name: String, message: String },
+
+    #[error("failed to build a mesh")]
+    Mesh(#[from] MeshBuildError),
+
+    #[error("the scene's GPU resources failed validation")]
+    Validation(#[source] wgpu::Error),
+}
+
 pub struct MeshBuilder<I, V, P, M> {
     indices: I,
     vertices: V,
     pipeline: P,
     model_matrix: M,
+    attribute_buffers: Vec<(VertexAttribute, BufferIndex<AttributeData>)>,
     instance_count: u32,
+    params_index: Option<usize>,
+    blend_mode: BlendMode,
+    origin: Vec3,
+    name: Option<String>,
 }
 
 impl Default for MeshBuilder<(), (), (), ()> {
@@ -170,12 +744,20 @@ impl Default for MeshBuilder<(), (), (), ()> {
             vertices: (),
             pipeline: (),
             model_matrix: (),
+            attribute_buffers: Vec::new(),
             instance_count: 1,
+            params_index: None,
+            blend_mode: BlendMode::Opaque,
+            origin: Vec3::zero(),
+            name: None,
         }
     }
 }
 
 impl<P, M> MeshBuilder<(), (), P, M> {
+    /// Upload the vertex attributes, one entry per vertex in each slice.
+    ///
+    /// Fails without uploading anything when the slices differ in length.
     pub fn with_vertices(
         self,
         device: &wgpu::Device,
@@ -183,34 +765,97 @@ impl<P, M> MeshBuilder<(), (), P, M> {
         positions: &[[f32; 3]],
         normals: &[[f32; 3]],
         uvs: &[[f32; 2]],
-    ) -> MeshBuilder<(), VertexBufferSet, P, M> {
+    ) -> Result<MeshBuilder<(), VertexBufferSet, P, M>, MeshBuildError> {
+        if normals.len() != positions.len() || uvs.len() != positions.len() {
+            return Err(MeshBuildError::AttributeLengthMismatch {
+                positions: positions.len(),
+                normals: normals.len(),
+                uvs: uvs.len(),
+            });
+        }
+
         let position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Positions"),
             contents: bytemuck::cast_slice(positions),
-            usage: wgpu::BufferUsages::VERTEX,
+            // Positions, normals and indices are read by `Scene::merge_static_meshes`.
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
         });
         let normal_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Normals"),
             contents: bytemuck::cast_slice(normals),
-            usage: wgpu::BufferUsages::VERTEX,
+            // Copied instead when they are already in world space.
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC,
         });
         let uv_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh UVs"),
             contents: bytemuck::cast_slice(uvs),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
         });
 
         let position_buffer_index = resources.add_position_buffer(position_buffer);
         let normal_buffer_index = resources.add_normal_buffer(normal_buffer);
         let uv_buffer_index = resources.add_uv_buffer(uv_buffer);
 
-        MeshBuilder {
-            vertices: (position_buffer_index, normal_buffer_index, uv_buffer_index),
+        Ok(MeshBuilder {
+            vertices: (
+                position_buffer_index,
+                normal_buffer_index,
+                uv_buffer_index,
+                positions.len() as u32,
+            ),
             indices: self.indices,
             pipeline: self.pipeline,
             model_matrix: self.model_matrix,
+            attribute_buffers: self.attribute_buffers,
             instance_count: self.instance_count,
+            params_index: self.params_index,
+            blend_mode: self.blend_mode,
+            origin: self.origin,
+            name: self.name,
+        })
+    }
+}
+
+impl<I, P, M> MeshBuilder<I, VertexBufferSet, P, M> {
+    /// Upload an attribute beyond position, normal and uv, as raw bytes holding one
+    /// entry per vertex in the attribute's `format`.
+    pub fn with_attribute(
+        mut self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        attribute: VertexAttribute,
+        data: &[u8],
+    ) -> Result<Self, MeshBuildError> {
+        let already_set = VertexAttribute::STANDARD.contains(&attribute)
+            || self
+                .attribute_buffers
+                .iter()
+                .any(|(existing, _)| *existing == attribute);
+        if already_set {
+            return Err(MeshBuildError::DuplicateAttribute(attribute));
         }
+
+        let entry_size = attribute.format().size() as usize;
+        let vertex_count = self.vertices.3 as usize;
+        if data.len() % entry_size != 0 || data.len() / entry_size != vertex_count {
+            return Err(MeshBuildError::AttributeCountMismatch {
+                attribute,
+                expected: vertex_count,
+                actual: data.len() / entry_size,
+            });
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Attribute"),
+            contents: data,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+        });
+        let buffer_index = resources.add_attribute_buffer(buffer);
+        self.attribute_buffers.push((attribute, buffer_index));
+
+        Ok(self)
     }
 }
 
@@ -220,25 +865,59 @@ impl<V, P, M> MeshBuilder<(), V, P, M> {
         device: &wgpu::Device,
         resources: &mut GpuResources,
         indices: &[u32],
+    ) -> MeshBuilder<IndexBufferInfo, V, P, M> {
+        self.with_index_data(
+            device,
+            resources,
+            bytemuck::cast_slice(indices),
+            indices.len() as u32,
+            wgpu::IndexFormat::Uint32,
+        )
+    }
+
+    /// Like `with_indices`, with half the memory for meshes of up to 65536 vertices.
+    pub fn with_indices_u16(
+        self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        indices: &[u16],
+    ) -> MeshBuilder<IndexBufferInfo, V, P, M> {
+        self.with_index_data(
+            device,
+            resources,
+            bytemuck::cast_slice(indices),
+            indices.len() as u32,
+            wgpu::IndexFormat::Uint16,
+        )
+    }
+
+    fn with_index_data(
+        self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        contents: &[u8],
+        index_count: u32,
+        index_format: wgpu::IndexFormat,
     ) -> MeshBuilder<IndexBufferInfo, V, P, M> {
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Indices"),
-            contents: bytemuck::cast_slice(indices),
-            usage: wgpu::BufferUsages::INDEX,
+            contents,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::STORAGE,
         });
 
         let index_buffer_index = resources.add_index_buffer(index_buffer);
 
         MeshBuilder {
-            indices: (
-                index_buffer_index,
-                indices.len() as u32,
-                wgpu::IndexFormat::Uint32,
-            ),
+            indices: (index_buffer_index, index_count, index_format),
             vertices: self.vertices,
             pipeline: self.pipeline,
             model_matrix: self.model_matrix,
+            attribute_buffers: self.attribute_buffers,
             instance_count: self.instance_count,
+            params_index: self.params_index,
+            blend_mode: self.blend_mode,
+            origin: self.origin,
+            name: self.name,
         }
     }
 }
@@ -250,7 +929,12 @@ impl<I, V, M> MeshBuilder<I, V, (), M> {
             indices: self.indices,
             vertices: self.vertices,
             model_matrix: self.model_matrix,
+            attribute_buffers: self.attribute_buffers,
             instance_count: self.instance_count,
+            params_index: self.params_index,
+            blend_mode: self.blend_mode,
+            origin: self.origin,
+            name: self.name,
         }
     }
 }
@@ -262,52 +946,328 @@ impl<I, V, P> MeshBuilder<I, V, P, ()> {
         resources: &mut GpuResources,
         matrix_columns: Mat4,
     ) -> MeshBuilder<I, V, P, BufferIndex<ModelMatrix>> {
+        self.with_instances(device, resources, &[matrix_columns])
+    }
+
+    /// Draw the mesh once per matrix in a single instanced draw call.
+    ///
+    /// Blended meshes are sorted by the center of the instances' translations.
+    ///
+    /// # Panics
+    ///
+    /// If `matrices` is empty.
+    pub fn with_instances(
+        self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        matrices: &[Mat4],
+    ) -> MeshBuilder<I, V, P, BufferIndex<ModelMatrix>> {
+        assert!(!matrices.is_empty(), "a mesh needs at least one instance");
+
+        let contents: Vec<f32> = matrices
+            .iter()
+            .flat_map(|matrix| matrix.as_slice().iter().copied())
+            .collect();
         let model_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Mesh Model Matrix"),
-            contents: bytemuck::cast_slice(matrix_columns.as_slice()),
-            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&contents),
+            // Written again by `Scene::set_mesh_transform` when the mesh moves, read by
+            // `Scene::merge_static_meshes`.
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::STORAGE,
         });
 
         let model_buffer_index = resources.add_model_matrix_buffer(model_buffer);
+        let origin = matrices
+            .iter()
+            .map(|matrix| matrix.cols[3].xyz())
+            .fold(Vec3::zero(), |sum, translation| sum + translation)
+            / matrices.len() as f32;
 
         MeshBuilder {
             indices: self.indices,
             vertices: self.vertices,
             pipeline: self.pipeline,
             model_matrix: model_buffer_index,
-            instance_count: self.instance_count,
+            attribute_buffers: self.attribute_buffers,
+            instance_count: matrices.len() as u32,
+            params_index: self.params_index,
+            blend_mode: self.blend_mode,
+            origin,
+            name: self.name,
         }
     }
 }
 
+impl<I, V, P, M> MeshBuilder<I, V, P, M> {
+    /// Upload per-object params for this mesh, see `ObjectParams`.
+    pub fn with_params(
+        mut self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        params: ObjectParams,
+    ) -> Self {
+        self.params_index = Some(resources.add_object_params(device, params));
+        self
+    }
+
+    /// Draw the mesh alpha blended, see `BlendMode`.
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Name to find the mesh by, see `Scene::find_mesh_by_name`.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+}
+
 impl MeshBuilder<IndexBufferInfo, VertexBufferSet, usize, BufferIndex<ModelMatrix>> {
     pub fn build(self) -> Mesh {
+        let MeshBuilder {
+            indices,
+            vertices,
+            pipeline,
+            model_matrix,
+            attribute_buffers,
+            instance_count,
+            params_index,
+            blend_mode,
+            origin,
+            name,
+        } = self;
+
+        MeshBuilder {
+            indices: (),
+            vertices,
+            pipeline,
+            model_matrix,
+            attribute_buffers,
+            instance_count,
+            params_index,
+            blend_mode,
+            origin,
+            name,
+        }
+        .assemble(Some(indices))
+    }
+}
+
+impl MeshBuilder<(), VertexBufferSet, usize, BufferIndex<ModelMatrix>> {
+    /// Build a mesh without an index buffer, its vertices are drawn in order.
+    pub fn build(self) -> Mesh {
+        self.assemble(None)
+    }
+
+    fn assemble(self, indices: Option<IndexBufferInfo>) -> Mesh {
+        let (position_buffer_index, normal_buffer_index, uv_buffer_index, vertex_count) =
+            self.vertices;
+        let (index_buffer_index, index_count, index_format) = match indices {
+            Some((index, count, format)) => (Some(index), count, format),
+            None => (None, 0, wgpu::IndexFormat::Uint32),
+        };
+
         Mesh {
             pipeline_index: self.pipeline,
-            position_buffer_index: (self.vertices).0,
-            normal_buffer_index: (self.vertices).1,
-            uv_buffer_index: (self.vertices).2,
+            position_buffer_index,
+            normal_buffer_index,
+            uv_buffer_index,
+            attribute_buffers: self.attribute_buffers,
             model_buffer_index: self.model_matrix,
-            index_buffer_index: (self.indices).0,
-            index_count: (self.indices).1,
-            index_format: (self.indices).2,
+            index_buffer_index,
+            index_count,
+            index_format,
+            vertex_count,
             instance_count: self.instance_count,
+            params_index: self.params_index,
+            blend_mode: self.blend_mode,
+            origin: self.origin,
+            name: self.name,
+            visible: true,
+            passes: MeshPasses::All,
+            bounds: None,
         }
     }
 }
 
+/// What a render pass does with the previous contents of an attachment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachmentLoad {
+    /// Start from the clear value: the background color, or the farthest depth.
+    #[default]
+    Clear,
+    /// Keep what is already there and draw on top of it.
+    Load,
+}
+
 pub trait Scene: Sized {
     fn setup(renderer_context: &renderer::RendererContext, resources: &mut GpuResources) -> Self;
+
+    /// Like `setup`, returning an error instead of panicking, e.g. when a shader fails to
+    /// compile. The renderer sets scenes up through this, scenes whose setup can fail
+    /// override it and implement `setup` on top of it.
+    fn try_setup(
+        renderer_context: &renderer::RendererContext,
+        resources: &mut GpuResources,
+    ) -> Result<Self, SceneError> {
+        Ok(Self::setup(renderer_context, resources))
+    }
     fn bind_groups(&self) -> &[wgpu::BindGroup];
     fn meshes(&self) -> &[Mesh];
     fn handle_mouse_click(&mut self, x: f32, y: f32);
-    fn handle_zoom(&mut self, delta_y: f32);
     fn handle_orbit(&mut self, delta_x: f32, delta_y: f32);
     fn clear(&mut self);
-    fn add_mesh(&mut self, mesh: Mesh);
+    fn add_mesh(&mut self, mesh: Mesh) -> MeshId;
+    /// Take a mesh out of the scene, `None` if `id` was already removed.
+    fn remove_mesh(&mut self, id: MeshId) -> Option<Mesh>;
+    fn get_mesh_mut(&mut self, id: MeshId) -> Option<&mut Mesh>;
+    /// Id of the mesh at `index` in `meshes`.
+    fn mesh_id(&self, index: usize) -> Option<MeshId>;
     fn set_camera_depth_range(&mut self, near: f32, far: f32);
     fn set_camera_look_at(&mut self, eye: ultraviolet::Vec3, center: ultraviolet::Vec3);
 
+    /// Move a mesh by rewriting its model matrix in place, without rebuilding any buffer.
+    /// Only the first instance of an instanced mesh moves.
+    ///
+    /// The mesh's bounds no longer fit it, so it is not culled anymore.
+    ///
+    /// Returns `false` if the mesh was removed.
+    fn set_mesh_transform(
+        &mut self,
+        id: MeshId,
+        matrix: Mat4,
+        resources: &GpuResources,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        let Some(mesh) = self.get_mesh_mut(id) else {
+            return false;
+        };
+
+        // The first instance is the first 64-byte stride of the layout.
+        let buffer = resources.get_buffer(&mesh.model_buffer_index);
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(matrix.as_slice()));
+        mesh.origin = matrix.cols[3].xyz();
+        mesh.bounds = None;
+        true
+    }
+
+    /// Hide or show a mesh. Unlike `remove_mesh` its buffers stay on the GPU, so showing
+    /// it again is instant.
+    ///
+    /// Returns `false` if the mesh was removed.
+    fn set_mesh_visible(&mut self, id: MeshId, visible: bool) -> bool {
+        let Some(mesh) = self.get_mesh_mut(id) else {
+            return false;
+        };
+
+        mesh.visible = visible;
+        true
+    }
+
+    /// Combine the opaque meshes sharing a pipeline and object params into one mesh per
+    /// group, with their model matrices baked into the vertices, to cut the draw calls
+    /// of models made of many small primitives.
+    ///
+    /// With `keep_originals` the merged meshes stay in the scene, only drawn into the
+    /// picking pass so clicks still find them. Otherwise they are removed along with
+    /// their buffers. Either way, moving them no longer moves what is drawn.
+    fn merge_static_meshes(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resources: &mut GpuResources,
+        keep_originals: bool,
+    ) -> MergeStats {
+        let draw_calls_before = merge::draw_calls(self.meshes());
+        let groups = merge::merge_groups(self.meshes(), resources, &device.limits());
+        if groups.is_empty() {
+            return MergeStats {
+                draw_calls_before,
+                draw_calls_after: draw_calls_before,
+                merged_meshes: 0,
+            };
+        }
+
+        let merger = MeshMerger::new(device);
+        let mut merged = Vec::with_capacity(groups.len());
+        for group in &groups {
+            let meshes: Vec<&Mesh> = group.iter().map(|&index| &self.meshes()[index]).collect();
+            let ids: Vec<MeshId> = group
+                .iter()
+                .filter_map(|&index| self.mesh_id(index))
+                .collect();
+            merged.push((merger.merge(device, queue, resources, &meshes), ids));
+        }
+
+        let mut merged_meshes = 0;
+        for (mut mesh, ids) in merged {
+            merged_meshes += ids.len();
+            for id in ids {
+                if keep_originals {
+                    if let Some(original) = self.get_mesh_mut(id) {
+                        original.passes = MeshPasses::Picking;
+                    }
+                } else if let Some(original) = self.remove_mesh(id) {
+                    resources.remove_mesh_buffers(&original);
+                }
+            }
+            if keep_originals {
+                mesh.passes = MeshPasses::Color;
+            }
+            self.add_mesh(mesh);
+        }
+
+        let stats = MergeStats {
+            draw_calls_before,
+            draw_calls_after: merge::draw_calls(self.meshes()),
+            merged_meshes,
+        };
+        log::info!(
+            "Merged {} static meshes, {} draw calls down to {}",
+            stats.merged_meshes,
+            stats.draw_calls_before,
+            stats.draw_calls_after
+        );
+        stats
+    }
+
+    /// Id of the first mesh named `name`, `None` if no mesh has that name.
+    fn find_mesh_by_name(&self, name: &str) -> Option<MeshId> {
+        let index = self
+            .meshes()
+            .iter()
+            .position(|mesh| mesh.name.as_deref() == Some(name))?;
+        self.mesh_id(index)
+    }
+
+    /// Layouts `setup` passed to `GpuResources::set_bind_group_layouts`, set again when
+    /// the renderer switches back to this scene.
+    fn bind_group_layouts(&self) -> Option<&[wgpu::BindGroupLayout]> {
+        None
+    }
+
+    /// Directional light bound with the frame metadata, `None` for scenes without one.
+    fn light_mut(&mut self) -> Option<&mut Light> {
+        None
+    }
+
+    /// Point the scene light along `direction`, the way its rays travel.
+    fn set_light_direction(&mut self, direction: Vec3, queue: &wgpu::Queue) {
+        if let Some(light) = self.light_mut() {
+            light.set_direction(direction, queue);
+        }
+    }
+
+    /// Set the linear RGB color of the scene light.
+    fn set_light_color(&mut self, color: [f32; 3], queue: &wgpu::Queue) {
+        if let Some(light) = self.light_mut() {
+            light.set_color(color, queue);
+        }
+    }
+
     fn frame_metadata_mut(&mut self) -> Option<&mut FrameMetadata> {
         None
     }
@@ -316,10 +1276,236 @@ pub trait Scene: Sized {
         None
     }
 
+    /// Called when a left click lands on the `y = 0` ground plane, with the world
+    /// position it hit. Clicks towards the sky do not call it.
+    fn on_ground_click(&mut self, _point: ultraviolet::Vec3) {}
+
+    /// Called after a click with the mesh drawn under the cursor, `None` if there was none.
+    fn handle_pick(&mut self, _id: Option<MeshId>) {}
+
+    /// Called when the mesh under the cursor changes, `None` once it is over the
+    /// background or leaves the canvas, e.g. to tint the hovered mesh.
+    ///
+    /// The cursor is picked again after it moves, at most one pixel read back at a time.
+    fn handle_hover(&mut self, _id: Option<MeshId>) {}
+
+    /// Called after a left click with the world position of the surface drawn under the
+    /// cursor, read back from the depth buffer. Clicks on the background do not call it.
+    fn handle_world_click(&mut self, _world_pos: ultraviolet::Vec3) {}
+
+    /// Point the camera at a model so all of it is in view.
+    fn frame_bounds(&mut self, bounds: &ModelBounds) {
+        if let Some(camera) = self.camera_mut() {
+            camera.frame_bounds(bounds.min.into(), bounds.max.into());
+            return;
+        }
+
+        let framing = bounds.framing();
+        self.set_camera_depth_range(framing.near, framing.far);
+        self.set_camera_look_at(framing.eye, framing.target);
+    }
+
+    /// Return the camera to its home view: where it was created, then framing the
+    /// visible meshes if there are any, so a loaded model is what comes back into view.
+    fn reset_camera(&mut self) {
+        if let Some(camera) = self.camera_mut() {
+            camera.reset();
+        }
+        if let Some(bounds) = visible_bounds(self.meshes()) {
+            self.frame_bounds(&bounds);
+        }
+    }
+
+    /// Replace the camera, e.g. with one restored from a saved state or imported from glTF.
+    ///
+    /// The new camera keeps the current aspect ratio and its matrix is uploaded right away,
+    /// so the camera bind group created at setup keeps working.
+    fn set_camera(&mut self, mut camera: Camera, queue: &wgpu::Queue) {
+        let view_proj = if let Some(cam) = self.camera_mut() {
+            camera.update_aspect_ratio(cam.aspect_ratio());
+            *cam = camera;
+            cam.view_proj
+        } else {
+            return;
+        };
+
+        if let Some(buffers) = self.uniform_buffers() {
+            if buffers.len() >= 2 {
+                queue.write_buffer(&buffers[1], 0, bytemuck::cast_slice(&[view_proj]));
+            }
+        }
+    }
+
+    /// Right or shift + middle drag: slide the camera and its target sideways.
+    fn handle_pan(&mut self, delta_x: f32, delta_y: f32) {
+        if let Some(cam) = self.camera_mut() {
+            cam.pan(delta_x, delta_y);
+        }
+    }
+
+    /// Plain scroll: dolly the camera towards or away from the point under the cursor, at
+    /// `cursor` in normalized device coordinates.
+    fn handle_zoom(&mut self, msg: &WheelMessage, cursor: Vec2) {
+        if let Some(cam) = self.camera_mut() {
+            cam.zoom(msg, cursor);
+        }
+    }
+
+    /// Ctrl/Cmd + scroll: change the field of view, scrolling up narrows it.
+    fn handle_fov_zoom(&mut self, msg: &WheelMessage) {
+        if let Some(cam) = self.camera_mut() {
+            cam.zoom_fov(msg);
+        }
+    }
+
+    /// Called when a key is released, for interactions that depend on held keys.
+    fn handle_key_up(&mut self, _msg: &KeyboardMessage) {}
+
     fn uniform_buffers(&self) -> Option<&[wgpu::Buffer]> {
         None
     }
 
+    /// Background drawn behind the meshes, `None` clears to `Renderer::set_clear_color`.
+    fn background_mut(&mut self) -> Option<&mut Background> {
+        None
+    }
+
+    /// Replace the background, ignored with a warning by scenes without one.
+    fn set_background(&mut self, background: Background) {
+        match self.background_mut() {
+            Some(current) => *current = background,
+            None => log::warn!("Ignoring the background, the scene has none to replace"),
+        }
+    }
+
+    /// Reference grid drawn under the meshes, `None` if the scene has no grid.
+    fn grid(&self) -> Option<&Grid> {
+        None
+    }
+
+    fn grid_mut(&mut self) -> Option<&mut Grid> {
+        None
+    }
+
+    /// Build the grid with lines reaching `extent` from the origin along x and z,
+    /// `spacing` apart, replacing any previous one. Scenes without a grid ignore it.
+    ///
+    /// Once added, the grid is resized to fit every model loaded afterwards.
+    fn add_grid(
+        &mut self,
+        device: &wgpu::Device,
+        resources: &mut GpuResources,
+        surface_format: wgpu::TextureFormat,
+        extent: f32,
+        spacing: f32,
+    ) {
+        if let Some(grid) = self.grid_mut() {
+            if let Err(err) = grid.rebuild(device, resources, surface_format, extent, spacing) {
+                log::error!("Failed to build the grid: {}", err);
+            }
+        }
+    }
+
+    fn set_grid_visible(&mut self, visible: bool) {
+        if let Some(grid) = self.grid_mut() {
+            grid.set_visible(visible);
+        }
+    }
+
+    /// Hierarchy of the loaded model's nodes, `None` if the scene keeps no graph.
+    ///
+    /// Loading a model replaces it, clearing the scene should clear it too.
+    fn scene_graph(&self) -> Option<&SceneGraph> {
+        None
+    }
+
+    fn scene_graph_mut(&mut self) -> Option<&mut SceneGraph> {
+        None
+    }
+
+    /// Set the transform of a node relative to its parent, moving the meshes of the node
+    /// and of every node below it like `set_mesh_transform`.
+    ///
+    /// Returns `false` if the scene keeps no graph or the node is not in it.
+    fn set_node_transform(
+        &mut self,
+        id: NodeId,
+        transform: Mat4,
+        resources: &GpuResources,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        let Some(moved) = self
+            .scene_graph_mut()
+            .and_then(|graph| graph.set_local_transform(id, transform))
+        else {
+            return false;
+        };
+
+        for (mesh, world) in moved {
+            self.set_mesh_transform(mesh, world, resources, queue);
+        }
+        true
+    }
+
+    /// Sprites drawn over the scene this frame, `None` if the scene has no overlay.
+    fn sprites_mut(&mut self) -> Option<&mut SpriteBatch> {
+        None
+    }
+
+    /// Queue `src` of `texture`, in texels, to be drawn at `dest` for this frame only.
+    ///
+    /// `dest` is in physical pixels from the top-left corner of the surface. Sprites are
+    /// drawn after the 3D scene, in queue order, alpha blended and without depth testing.
+    fn draw_sprite(&mut self, texture: &SpriteTexture, dest: Rect, src: Rect) {
+        if let Some(batch) = self.sprites_mut() {
+            batch.push(Sprite {
+                texture: texture.clone(),
+                dest,
+                src,
+            });
+        }
+    }
+
+    /// Whether the frame starts from the background color or draws over the previous
+    /// contents of the color target, e.g. when compositing layered scenes.
+    ///
+    /// With `Load` the background is not drawn, so it does not cover those contents.
+    fn color_load_op(&self) -> AttachmentLoad {
+        AttachmentLoad::Clear
+    }
+
+    /// Whether the frame starts from a cleared depth buffer or tests against the depth
+    /// left by a previous render.
+    fn depth_load_op(&self) -> AttachmentLoad {
+        AttachmentLoad::Clear
+    }
+
+    /// Called once the surface has been configured, at startup and after every resize.
+    ///
+    /// Scene-owned render targets that must match the surface size or format can be
+    /// recreated here.
+    fn on_surface_configured(
+        &mut self,
+        _config: &wgpu::SurfaceConfiguration,
+        _device: &wgpu::Device,
+    ) {
+    }
+
+    /// Set the diameter of point cloud points in physical pixels, applied with the next
+    /// frame.
+    fn set_point_size(&mut self, size: f32) {
+        if let Some(fm) = self.frame_metadata_mut() {
+            fm.point_size = size.max(0.0);
+        }
+    }
+
+    /// Set one of the app-defined frame uniforms, uploaded with the next frame.
+    fn set_user_param(&mut self, index: usize, value: f32) {
+        if let Some(fm) = self.frame_metadata_mut() {
+            fm.set_user_param(index, value);
+        }
+    }
+
     fn resize(&mut self, width: f64, height: f64, _scale_factor: f64, queue: &wgpu::Queue) {
         let fm_copy = if let Some(fm) = self.frame_metadata_mut() {
             let dimension = ultraviolet::Vec2::new(width as f32, height as f32);
@@ -330,7 +1516,7 @@ pub trait Scene: Sized {
         };
 
         let view_proj_copy = if let Some(cam) = self.camera_mut() {
-            cam.update_aspect_ratio(width as f32 / height as f32);
+            cam.update_aspect_ratio(width as f32 / height.max(1.0) as f32);
             cam.view_proj
         } else {
             return;
@@ -344,10 +1530,18 @@ pub trait Scene: Sized {
         }
     }
 
+    /// Called before every frame with the frame's time in seconds since the page loaded
+    /// and the seconds since the previous update, which is 0 for the first frame and the
+    /// first one after a pause.
+    ///
+    /// Scenes that animate override it, advancing by `delta_time`, and upload their
+    /// uniforms like the default does.
     fn update(
         &mut self,
         renderer_context: &renderer::RendererContext,
         _resources: &mut GpuResources,
+        frame_time: f32,
+        _delta_time: f32,
     ) {
         let camera_position = if let Some(cam) = self.camera_mut() {
             cam.position()
@@ -356,8 +1550,7 @@ pub trait Scene: Sized {
         };
 
         let fm_copy = if let Some(fm) = self.frame_metadata_mut() {
-            let time = (js_sys::Date::now() as f32) * 0.001;
-            fm.time = time;
+            fm.set_time(frame_time);
             fm.set_camera_position(camera_position);
             *fm
         } else {
@@ -386,3 +1579,7 @@ pub trait Scene: Sized {
         }
     }
 }
+
+#[cfg(test)]
+#[path = "scene_tests.rs"]
+mod tests;
@@ -0,0 +1,169 @@
+use ultraviolet::Mat4;
+
+use crate::gltf::ParsedModel;
+
+use super::scene::MeshId;
+
+/// Handle to a node in a `SceneGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+/// A transform in the hierarchy, moving its meshes and every node below it.
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    name: Option<String>,
+    local: Mat4,
+    world: Mat4,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    meshes: Vec<MeshId>,
+}
+
+impl SceneNode {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Transform relative to the parent node.
+    pub fn local_transform(&self) -> Mat4 {
+        self.local
+    }
+
+    /// Transform of the node's meshes, its local transform under all of its ancestors'.
+    pub fn world_transform(&self) -> Mat4 {
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn meshes(&self) -> &[MeshId] {
+        &self.meshes
+    }
+}
+
+/// Parent-child hierarchy of the scene meshes, e.g. the nodes of a glTF model.
+///
+/// Meshes keep their world matrix in their model buffer, the graph tells which of them
+/// move when a node does, see `Scene::set_node_transform`.
+#[derive(Clone, Debug, Default)]
+pub struct SceneGraph {
+    // Parents always come before their children
+    nodes: Vec<SceneNode>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Graph of the nodes of `model`, with `mesh_ids` the meshes uploaded for each of
+    /// `model.primitives` in order, e.g. by `upload_model`.
+    pub fn from_model(model: &ParsedModel, mesh_ids: &[MeshId]) -> Self {
+        let mut graph = Self::new();
+        for node in &model.nodes {
+            let parent = node.parent.map(|parent| NodeId(parent as u32));
+            graph.add_node(parent, node.name.clone(), node.transform, Vec::new());
+        }
+        for (primitive, &mesh) in model.primitives.iter().zip(mesh_ids) {
+            if let Some(node) = graph.nodes.get_mut(primitive.node) {
+                node.meshes.push(mesh);
+            }
+        }
+        graph
+    }
+
+    /// Add a node under `parent`, or a root without one.
+    ///
+    /// Panics if `parent` is not in the graph.
+    pub fn add_node(
+        &mut self,
+        parent: Option<NodeId>,
+        name: Option<String>,
+        local: Mat4,
+        meshes: Vec<MeshId>,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        let world = match parent {
+            Some(parent) => {
+                let parent = &mut self.nodes[parent.0 as usize];
+                parent.children.push(id);
+                parent.world * local
+            }
+            None => local,
+        };
+
+        self.nodes.push(SceneNode {
+            name,
+            local,
+            world,
+            parent,
+            children: Vec::new(),
+            meshes,
+        });
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&SceneNode> {
+        self.nodes.get(id.0 as usize)
+    }
+
+    /// Nodes without a parent.
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.ids()
+            .filter(|&id| self.nodes[id.0 as usize].parent.is_none())
+    }
+
+    /// Every node, parents before their children.
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    /// Id of the first node named `name`.
+    pub fn find_node_by_name(&self, name: &str) -> Option<NodeId> {
+        self.ids()
+            .find(|&id| self.nodes[id.0 as usize].name.as_deref() == Some(name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Replace the local transform of `id` and recompute the world transforms of it and
+    /// its descendants.
+    ///
+    /// Returns each mesh that moved with its new world matrix, `None` if the node is not
+    /// in the graph.
+    pub fn set_local_transform(&mut self, id: NodeId, local: Mat4) -> Option<Vec<(MeshId, Mat4)>> {
+        let node = self.nodes.get_mut(id.0 as usize)?;
+        node.local = local;
+
+        let mut moved = Vec::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            let node = &self.nodes[id.0 as usize];
+            let parent_world = node.parent.map_or(Mat4::identity(), |parent| {
+                self.nodes[parent.0 as usize].world
+            });
+
+            let node = &mut self.nodes[id.0 as usize];
+            node.world = parent_world * node.local;
+            moved.extend(node.meshes.iter().map(|&mesh| (mesh, node.world)));
+            pending.extend_from_slice(&node.children);
+        }
+        Some(moved)
+    }
+}
@@ -0,0 +1,1625 @@
+use super::*;
+use crate::gltf::{
+    self,
+    tests::{textured_triangle_glb, two_triangle_glb, unlit_triangle_glb},
+    ModelBounds,
+};
+use crate::platform::native::test_device;
+use crate::renderer::capture::CaptureTarget;
+use crate::renderer::grid::{grid_lines, Grid};
+use crate::renderer::picking::PickingPass;
+use crate::renderer::{
+    GpuMemoryUsage, MouseMessage, PipelineOptions, PipelineVariant, Renderer, RendererConfig,
+    RendererContext, RendererInitError, SamplerConfig, SceneGraph, Viewport,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use ultraviolet::Vec3;
+
+/// Minimal scene: a camera and whatever meshes are added to it.
+struct TestScene {
+    cam: Camera,
+    // Empty unless a test sets its own layouts
+    bind_groups: Vec<wgpu::BindGroup>,
+    meshes: MeshStore,
+    graph: SceneGraph,
+    hovered: Option<MeshId>,
+    // Frame and delta time of every update
+    updates: Vec<(f32, f32)>,
+}
+
+impl Scene for TestScene {
+    fn setup(renderer_context: &RendererContext, _resources: &mut GpuResources) -> Self {
+        let (width, height) = renderer_context.render_extent();
+        Self {
+            cam: Camera::new(width / height),
+            bind_groups: Vec::new(),
+            meshes: MeshStore::new(),
+            graph: SceneGraph::new(),
+            hovered: None,
+            updates: Vec::new(),
+        }
+    }
+
+    fn bind_groups(&self) -> &[wgpu::BindGroup] {
+        &self.bind_groups
+    }
+
+    fn meshes(&self) -> &[Mesh] {
+        self.meshes.as_slice()
+    }
+
+    fn handle_mouse_click(&mut self, _x: f32, _y: f32) {}
+
+    fn handle_hover(&mut self, id: Option<MeshId>) {
+        self.hovered = id;
+    }
+
+    fn update(
+        &mut self,
+        _renderer_context: &RendererContext,
+        _resources: &mut GpuResources,
+        frame_time: f32,
+        delta_time: f32,
+    ) {
+        self.updates.push((frame_time, delta_time));
+    }
+
+    fn handle_orbit(&mut self, delta_x: f32, delta_y: f32) {
+        self.cam.orbit(delta_x, delta_y);
+    }
+
+    fn clear(&mut self) {
+        self.meshes.clear();
+        self.graph.clear();
+    }
+
+    fn add_mesh(&mut self, mesh: Mesh) -> MeshId {
+        self.meshes.insert(mesh)
+    }
+
+    fn remove_mesh(&mut self, id: MeshId) -> Option<Mesh> {
+        self.meshes.remove(id)
+    }
+
+    fn get_mesh_mut(&mut self, id: MeshId) -> Option<&mut Mesh> {
+        self.meshes.get_mut(id)
+    }
+
+    fn mesh_id(&self, index: usize) -> Option<MeshId> {
+        self.meshes.id_at(index)
+    }
+
+    fn set_camera_depth_range(&mut self, near: f32, far: f32) {
+        self.cam.set_depth_range(near, far);
+    }
+
+    fn set_camera_look_at(&mut self, eye: ultraviolet::Vec3, center: ultraviolet::Vec3) {
+        self.cam.look_at(eye, center);
+    }
+
+    fn camera_mut(&mut self) -> Option<&mut Camera> {
+        Some(&mut self.cam)
+    }
+
+    fn scene_graph_mut(&mut self) -> Option<&mut SceneGraph> {
+        Some(&mut self.graph)
+    }
+}
+
+/// Scene whose only pipeline has a shader that does not compile.
+struct BrokenScene;
+
+impl Scene for BrokenScene {
+    fn setup(renderer_context: &RendererContext, resources: &mut GpuResources) -> Self {
+        resources.get_or_create_pipeline(
+            &renderer_context.device,
+            "broken",
+            &mesh_vertex_layout(),
+            "@vertex fn vs_main() -> @builtin(position) vec4<f32> { return 1.0; }",
+            renderer_context.surface_config.format,
+        );
+        BrokenScene
+    }
+
+    fn bind_groups(&self) -> &[wgpu::BindGroup] {
+        &[]
+    }
+
+    fn meshes(&self) -> &[Mesh] {
+        &[]
+    }
+
+    fn handle_mouse_click(&mut self, _x: f32, _y: f32) {}
+
+    fn handle_orbit(&mut self, _delta_x: f32, _delta_y: f32) {}
+
+    fn clear(&mut self) {}
+
+    fn add_mesh(&mut self, _mesh: Mesh) -> MeshId {
+        unreachable!("the broken scene is never set up")
+    }
+
+    fn remove_mesh(&mut self, _id: MeshId) -> Option<Mesh> {
+        None
+    }
+
+    fn get_mesh_mut(&mut self, _id: MeshId) -> Option<&mut Mesh> {
+        None
+    }
+
+    fn mesh_id(&self, _index: usize) -> Option<MeshId> {
+        None
+    }
+
+    fn set_camera_depth_range(&mut self, _near: f32, _far: f32) {}
+
+    fn set_camera_look_at(&mut self, _eye: ultraviolet::Vec3, _center: ultraviolet::Vec3) {}
+}
+
+/// Environment variable letting GPU tests pass without running, e.g. on CI machines
+/// with no adapter. Unset, they fail when there is nothing to run them on.
+const SKIP_GPU_TESTS: &str = "YAWN_SKIP_GPU_TESTS";
+
+/// Let a GPU test return early for `reason` when `SKIP_GPU_TESTS` is set, fail it
+/// otherwise.
+fn skip_gpu_test(reason: impl std::fmt::Display) {
+    assert!(
+        std::env::var_os(SKIP_GPU_TESTS).is_some(),
+        "{reason}, set {SKIP_GPU_TESTS}=1 to skip GPU tests"
+    );
+    log::warn!("Skipping a GPU test: {reason}");
+}
+
+/// Device and queue from `test_device`, `None` only for a skipped test.
+fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let device = test_device();
+    if device.is_none() {
+        skip_gpu_test("no headless adapter available");
+    }
+    device
+}
+
+fn headless_context() -> Option<RendererContext> {
+    let (device, queue) = headless_device()?;
+
+    Some(RendererContext::headless(
+        device,
+        queue,
+        640,
+        480,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ))
+}
+
+/// Headless renderer of `width` x `height` within the limits any adapter has.
+fn try_headless_renderer<S: Scene + 'static>(
+    width: u32,
+    height: u32,
+) -> Result<Renderer<S>, RendererInitError> {
+    let config = RendererConfig {
+        required_limits: wgpu::Limits::downlevel_defaults(),
+        ..Default::default()
+    };
+    futures::executor::block_on(Renderer::<S>::headless(width, height, config))
+}
+
+/// `try_headless_renderer`, `None` only for a skipped test without an adapter. Any
+/// other failure to start fails the test.
+fn headless_renderer<S: Scene + 'static>(width: u32, height: u32) -> Option<Renderer<S>> {
+    match try_headless_renderer(width, height) {
+        Ok(renderer) => Some(renderer),
+        Err(err @ RendererInitError::NoAdapter(_)) => {
+            skip_gpu_test(err);
+            None
+        }
+        Err(err) => panic!("the headless renderer failed to start: {err}"),
+    }
+}
+
+/// WGSL drawing each vertex at clip position `position`, computed from its `pos`, in
+/// the solid `color`.
+fn flat_shader(position: &str, color: &str) -> String {
+    format!(
+        "@vertex fn vs_main(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {{
+            return {position};
+        }}
+        @fragment fn fs_main() -> @location(0) vec4<f32> {{ return {color}; }}"
+    )
+}
+
+/// Counts the allocations made on each thread, so tests running in parallel do not
+/// add to each other's counts.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations `f` makes on the current thread.
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Pipeline named "flat" drawing mesh positions straight to clip space in white.
+fn flat_pipeline(context: &RendererContext, resources: &mut GpuResources) -> usize {
+    resources
+        .create_pipeline(
+            &context.device,
+            "flat",
+            &mesh_vertex_layout(),
+            &flat_shader("vec4<f32>(pos, 1.0)", "vec4<f32>(1.0)"),
+            context.surface_config.format,
+        )
+        .unwrap()
+}
+
+/// Positions, normals, uvs and indices of a mesh.
+type MeshData = (Vec<[f32; 3]>, Vec<[f32; 3]>, Vec<[f32; 2]>, Vec<u32>);
+
+/// Unit cube with four vertices per face so each face gets its own normal.
+fn cube_vertices() -> MeshData {
+    let faces = [
+        (Vec3::unit_x(), Vec3::unit_y()),
+        (-Vec3::unit_x(), Vec3::unit_y()),
+        (Vec3::unit_y(), Vec3::unit_z()),
+        (-Vec3::unit_y(), Vec3::unit_z()),
+        (Vec3::unit_z(), Vec3::unit_x()),
+        (-Vec3::unit_z(), Vec3::unit_x()),
+    ];
+
+    let (mut positions, mut normals, mut uvs, mut indices) = (vec![], vec![], vec![], vec![]);
+    for (normal, up) in faces {
+        let right = up.cross(normal);
+        let base = positions.len() as u32;
+        for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let corner = (normal + right * (u * 2.0 - 1.0) + up * (v * 2.0 - 1.0)) * 0.5;
+            positions.push([corner.x, corner.y, corner.z]);
+            normals.push([normal.x, normal.y, normal.z]);
+            uvs.push([u, v]);
+        }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    (positions, normals, uvs, indices)
+}
+
+#[test]
+fn cube_reports_vertex_and_triangle_counts() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+
+    let indexed = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+
+    assert_eq!(indexed.vertex_count, 24);
+    assert_eq!(indexed.index_count, 36);
+    assert_eq!(indexed.triangle_count(), 12);
+
+    // The same cube unrolled into a triangle list, without an index buffer.
+    let unroll = |attribute: &[[f32; 3]]| -> Vec<[f32; 3]> {
+        indices.iter().map(|&i| attribute[i as usize]).collect()
+    };
+    let flat_uvs: Vec<[f32; 2]> = indices.iter().map(|&i| uvs[i as usize]).collect();
+
+    let non_indexed = MeshBuilder::default()
+        .with_vertices(
+            device,
+            &mut resources,
+            &unroll(&positions),
+            &unroll(&normals),
+            &flat_uvs,
+        )
+        .unwrap()
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+
+    assert!(non_indexed.index_buffer_index.is_none());
+    assert_eq!(non_indexed.vertex_count, 36);
+    assert_eq!(non_indexed.index_count, 0);
+    assert_eq!(non_indexed.triangle_count(), 12);
+}
+
+#[test]
+fn instanced_mesh_uploads_every_matrix() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let matrices: Vec<Mat4> = (0..3)
+        .map(|i| Mat4::from_translation(Vec3::new(i as f32 * 2.0, 0.0, 0.0)))
+        .collect();
+
+    let mesh = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(0)
+        .with_instances(device, &mut resources, &matrices)
+        .build();
+
+    assert_eq!(mesh.instance_count, 3);
+    assert_eq!(mesh.triangle_count(), 36);
+    assert_eq!(
+        resources.get_buffer(&mesh.model_buffer_index).size(),
+        3 * 64
+    );
+    assert_eq!(mesh.origin, Vec3::new(2.0, 0.0, 0.0));
+}
+
+#[test]
+fn mesh_ids_survive_removing_other_meshes() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, _) = cube_vertices();
+
+    // Meshes are told apart by their params index.
+    let mut mesh = |tag: usize| {
+        let mut mesh = MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_pipeline(0)
+            .with_model_matrix(device, &mut resources, Mat4::identity())
+            .build();
+        mesh.params_index = Some(tag);
+        mesh
+    };
+    let tag = |mesh: Option<&Mesh>| mesh.and_then(|mesh| mesh.params_index);
+
+    let mut store = MeshStore::new();
+    let first = store.insert(mesh(0));
+    let second = store.insert(mesh(1));
+    let third = store.insert(mesh(2));
+
+    assert_eq!(tag(store.remove(first).as_ref()), Some(0));
+    assert_eq!(store.len(), 2);
+    assert_eq!(tag(store.get(second)), Some(1));
+    assert_eq!(tag(store.get(third)), Some(2));
+    assert!(store.remove(first).is_none());
+
+    // The freed slot is reused without the stale id resolving to the new mesh.
+    let fourth = store.insert(mesh(3));
+    assert!(store.get(first).is_none());
+    assert_eq!(tag(store.get(fourth)), Some(3));
+
+    store.get_mut(third).unwrap().instance_count = 4;
+    assert_eq!(store.get(third).unwrap().instance_count, 4);
+
+    store.clear();
+    assert!(store.is_empty());
+    assert!(store.get(second).is_none());
+}
+
+#[test]
+fn memory_usage_counts_buffers_and_textures() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+
+    MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+
+    let usage = resources.gpu_memory_usage();
+    assert_eq!(usage.vertex, 24 * (12 + 12 + 8) + 64);
+    assert_eq!(usage.index, 36 * 4);
+
+    // 4x4 RGBA with its 2x2 and 1x1 mips.
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 3,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let texture = resources.add_texture(texture);
+    assert_eq!(resources.gpu_memory_usage().texture, (16 + 4 + 1) * 4);
+    assert_eq!(resources.estimated_gpu_memory(), usage.total() + 84);
+
+    resources.free_texture(texture);
+    assert_eq!(resources.gpu_memory_usage(), usage);
+
+    // Uploaded pixels reuse the freed slot, the old handle still doesn't reach them.
+    let uploaded = resources.upload_texture_2d(device, &context.queue, &[255; 2 * 3 * 4], 2, 3);
+    assert_eq!(uploaded.index, texture.index);
+    assert!(resources.try_get_texture(&texture).is_none());
+    assert!(resources.free_texture(texture).is_none());
+    let size = resources.get_texture(&uploaded).size();
+    assert_eq!((size.width, size.height), (2, 3));
+    assert_eq!(resources.gpu_memory_usage().texture, 24);
+}
+
+#[test]
+fn removed_buffers_free_their_slot_for_reuse() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+
+    let mesh = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+
+    resources.remove_mesh_buffers(&mesh);
+    assert_eq!(resources.gpu_memory_usage(), GpuMemoryUsage::default());
+    assert!(resources
+        .try_get_buffer(&mesh.position_buffer_index)
+        .is_none());
+    assert!(resources
+        .remove_buffer(mesh.position_buffer_index)
+        .is_none());
+
+    // The next buffer fills the last freed slot, the old handle still doesn't reach it.
+    let index_buffer = mesh.index_buffer_index.unwrap();
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 64,
+        usage: wgpu::BufferUsages::VERTEX,
+        mapped_at_creation: false,
+    });
+    let matrix = resources.add_model_matrix_buffer(buffer);
+    assert_eq!(matrix.index, index_buffer.index);
+    assert_eq!(resources.get_buffer(&matrix).size(), 64);
+    assert!(resources.try_get_buffer(&index_buffer).is_none());
+    assert_eq!(resources.gpu_memory_usage().vertex, 64);
+}
+
+#[test]
+fn reloading_a_broken_shader_keeps_the_old_pipeline() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let shader = |color: &str| flat_shader("vec4<f32>(pos, 1.0)", color);
+
+    let index = resources
+        .create_pipeline(
+            device,
+            "flat",
+            &mesh_vertex_layout(),
+            &shader("vec4<f32>(1.0)"),
+            context.surface_config.format,
+        )
+        .unwrap();
+    let original = resources.get_pipeline_by_index(index).clone();
+
+    let reload = |resources: &mut GpuResources, name: &str, source: String| {
+        futures::executor::block_on(resources.reload_pipeline(device, name, &source))
+    };
+
+    assert!(reload(&mut resources, "flat", shader("oops")).is_err());
+    assert!(*resources.get_pipeline_by_index(index) == original);
+
+    reload(&mut resources, "flat", shader("vec4<f32>(0.5)")).unwrap();
+    assert!(*resources.get_pipeline_by_index(index) != original);
+    assert_eq!(resources.get_pipeline("flat"), Some(index));
+
+    assert!(reload(&mut resources, "missing", shader("vec4<f32>(1.0)")).is_err());
+}
+
+#[test]
+fn pipelines_share_layouts_per_set_of_bind_groups() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let uniform = |label| {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    };
+    resources.set_bind_group_layouts(&[uniform("uniforms"), uniform("camera")]);
+
+    // Only reads the camera, which lands at group 0.
+    let shader = "@group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+        @vertex fn vs_main(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+            return camera * vec4<f32>(pos, 1.0);
+        }
+        @fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+    let camera_only = PipelineOptions {
+        bind_groups: Some(&[1]),
+        ..Default::default()
+    };
+    let mut create = |name: &str, options| {
+        resources.create_pipeline_with_options(
+            device,
+            name,
+            &mesh_vertex_layout(),
+            shader,
+            context.surface_config.format,
+            options,
+        )
+    };
+
+    let first = create("camera_only", camera_only).unwrap();
+    create("camera_only_again", camera_only).unwrap();
+    let all = create("all_groups", PipelineOptions::default()).unwrap();
+    assert!(create(
+        "missing_group",
+        PipelineOptions {
+            bind_groups: Some(&[2]),
+            ..Default::default()
+        }
+    )
+    .is_err());
+
+    let layouts = resources
+        .pipeline_layouts
+        .iter()
+        .map(|layouts| layouts.len())
+        .sum::<usize>();
+    assert_eq!(layouts, 2);
+    assert_eq!(resources.pipeline_bind_groups(first), [1]);
+    assert_eq!(resources.pipeline_bind_groups(all), [0, 1]);
+
+    // Variants look the shared layout up again, which should not allocate
+    let source = resources.pipeline_sources[&all].clone();
+    let allocations = allocations_during(|| {
+        resources.get_or_create_pipeline_layout(device, &source);
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn wireframe_variant_draws_triangle_edges() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    resources.set_polygon_mode_line(
+        device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE),
+    );
+    if !resources.supports_wireframe() {
+        skip_gpu_test("adapter has no line polygon mode");
+        return;
+    }
+
+    let index = flat_pipeline(&context, &mut resources);
+    let wireframe =
+        resources.get_or_create_pipeline_variant(device, index, PipelineVariant::Wireframe);
+
+    assert_ne!(wireframe, index);
+    assert_eq!(
+        resources.pipeline_variant(index, PipelineVariant::Wireframe),
+        Some(wireframe)
+    );
+}
+
+#[test]
+fn picking_finds_the_mesh_under_a_pixel() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let pipeline = flat_pipeline(&context, &mut resources);
+
+    let mut scene = TestScene::setup(&context, &mut resources);
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let mut cube = |offset: Vec3| {
+        MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_indices(device, &mut resources, &indices)
+            .with_pipeline(pipeline)
+            .with_model_matrix(device, &mut resources, Mat4::from_translation(offset))
+            .build()
+    };
+    let behind = cube(Vec3::new(0.0, 0.0, -4.0));
+    let front = cube(Vec3::zero());
+    scene.add_mesh(behind);
+    let front = scene.add_mesh(front);
+    scene.set_camera_look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+
+    let mut picking = PickingPass::new(device);
+    let mut pick = |scene: &mut TestScene, x, y| {
+        let ids = (0..scene.meshes().len())
+            .map(|index| scene.mesh_id(index))
+            .collect();
+        let view_proj = Mat4::from(scene.camera_mut().unwrap().view_proj);
+        let readback =
+            picking.pick(&context, &resources, scene.meshes(), ids, view_proj, (x, y))?;
+        device.poll(wgpu::PollType::Wait).unwrap();
+        futures::executor::block_on(readback.resolve())
+    };
+
+    // The closer cube wins over the one behind it, the corner only shows the background.
+    assert_eq!(pick(&mut scene, 320, 240), Some(front));
+    assert_eq!(pick(&mut scene, 0, 0), None);
+    assert_eq!(pick(&mut scene, 640, 0), None);
+
+    // Only the picked pixel counts, right up to the edge of the cube.
+    let edge = scene
+        .camera_mut()
+        .unwrap()
+        .world_to_screen(Vec3::new(0.5, 0.0, 0.5), Vec2::new(640.0, 480.0))
+        .unwrap();
+    let edge_x = edge.x as u32;
+    assert_eq!(pick(&mut scene, edge_x - 2, 240), Some(front));
+    assert_eq!(pick(&mut scene, edge_x + 2, 240), None);
+
+    // Hidden meshes are skipped, the cube behind shows through.
+    assert!(scene.set_mesh_visible(front, false));
+    assert_eq!(pick(&mut scene, 320, 240), scene.mesh_id(0));
+}
+
+#[test]
+fn captured_frames_drop_row_padding_and_come_out_rgba() {
+    let Some((device, queue)) = headless_device() else {
+        return;
+    };
+    // Three texels per row leave most of each 256 byte row as padding.
+    let context =
+        RendererContext::headless(device, queue, 3, 2, wgpu::TextureFormat::Bgra8UnormSrgb);
+
+    let target = CaptureTarget::new(&context);
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            depth_slice: None,
+            view: &target.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    let readback = target.read(&context, encoder);
+    context.device.poll(wgpu::PollType::Wait).unwrap();
+    let png = futures::executor::block_on(readback.resolve()).unwrap();
+
+    let image = image::load_from_memory(&png).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+}
+
+#[test]
+fn vertex_layout_places_instance_matrix_after_attributes() {
+    let layout = VertexLayoutBuilder::new()
+        .with(VertexAttribute::Position)
+        .with(VertexAttribute::Color)
+        .with(VertexAttribute::Position)
+        .build();
+    assert_eq!(
+        layout.attributes(),
+        &[VertexAttribute::Position, VertexAttribute::Color]
+    );
+
+    let buffers = layout.buffer_layouts();
+    assert_eq!(buffers.len(), 3);
+    assert_eq!(buffers[1].array_stride, 16);
+    assert_eq!(buffers[1].attributes[0].shader_location, 1);
+    assert_eq!(buffers[2].step_mode, wgpu::VertexStepMode::Instance);
+    let instance_locations: Vec<u32> = buffers[2]
+        .attributes
+        .iter()
+        .map(|attribute| attribute.shader_location)
+        .collect();
+    assert_eq!(instance_locations, [2, 3, 4, 5]);
+
+    // The standard layout matches the hand-written one.
+    let standard = VertexLayout::standard();
+    for (built, fixed) in standard.buffer_layouts().iter().zip(mesh_vertex_layout()) {
+        assert_eq!(built.array_stride, fixed.array_stride);
+        assert_eq!(built.attributes, fixed.attributes);
+    }
+}
+
+#[test]
+fn builds_multi_mesh_scene_headlessly() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+
+    // Meshes are only assembled here, never drawn, so any pipeline index will do.
+    let (meshes, bounds) = gltf::visit_gltf(&two_triangle_glb(), &mut |primitive| {
+        let mesh = gltf::upload_primitive(&context.device, &mut resources, &primitive, 0);
+        Some(mesh.unwrap())
+    })
+    .unwrap();
+
+    for mesh in meshes {
+        scene.add_mesh(mesh);
+    }
+
+    assert_eq!(scene.meshes().len(), 2);
+    for mesh in scene.meshes() {
+        assert_eq!(mesh.index_count, 3);
+        assert_eq!(mesh.index_format, wgpu::IndexFormat::Uint16);
+        assert_eq!(mesh.instance_count, 1);
+        assert_eq!(mesh.params_index, None);
+    }
+    assert_eq!(
+        bounds,
+        Some(ModelBounds::new([-3.0, 0.0, 0.0], [2.0, 3.0, 0.0]))
+    );
+}
+
+#[test]
+fn uploaded_models_bind_their_materials() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let mut meshes = Vec::new();
+
+    // The glTF shader reads the frame metadata, light and camera, like the editor's.
+    let uniform = |label| {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    };
+    let frame = FrameMetadata::new(ultraviolet::Vec2::new(1.0, 1.0))
+        .create_uniform_resource(device, &Light::new(device));
+    resources.set_bind_group_layouts(&[frame.bind_group_layout, uniform("camera")]);
+
+    for glb in [
+        textured_triangle_glb(),
+        two_triangle_glb(),
+        unlit_triangle_glb(),
+    ] {
+        let model = gltf::parse_gltf(&glb).unwrap();
+        gltf::upload_model(
+            device,
+            &context.queue,
+            &mut resources,
+            &mut meshes,
+            &model,
+            context.surface_config.format,
+        )
+        .unwrap();
+    }
+
+    // Every glTF mesh gets a material, untextured ones share the white fallback.
+    assert_eq!(meshes.len(), 4);
+    assert!(meshes.iter().all(|mesh| mesh.params_index.is_some()));
+    assert!(
+        resources
+            .pipeline_options(meshes[0].pipeline_index)
+            .textured
+    );
+    assert_eq!(meshes[1].params_index, meshes[2].params_index);
+    assert_eq!(meshes[0].blend_mode, BlendMode::Blend);
+    assert_eq!(meshes[1].blend_mode, BlendMode::Opaque);
+
+    // Unlit materials still sample their texture, without lighting.
+    let unlit = meshes[3].pipeline_index;
+    assert_ne!(unlit, meshes[0].pipeline_index);
+    assert_eq!(resources.get_pipeline("gltf_unlit"), Some(unlit));
+    assert!(resources.pipeline_options(unlit).textured);
+
+    // The 2x1 texture of each textured model and the 1x1 white one.
+    assert_eq!(resources.gpu_memory_usage().texture, 20);
+}
+
+#[test]
+fn gltf_models_draw_in_scenes_with_only_frame_and_camera_groups() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(64, 48) else {
+        return;
+    };
+    renderer.set_clear_color(wgpu::Color::GREEN);
+
+    let device = &renderer.context.device;
+    let frame = FrameMetadata::new(ultraviolet::Vec2::new(64.0, 48.0))
+        .create_uniform_resource(device, &Light::new(device));
+    let mut camera = Camera::new(64.0 / 48.0);
+    camera.look_at(Vec3::new(0.25, 0.25, 2.0), Vec3::new(0.25, 0.25, 0.0));
+    let camera = camera.create_uniform_resource(device);
+    renderer
+        .resources
+        .set_bind_group_layouts(&[frame.bind_group_layout, camera.bind_group_layout]);
+    renderer.scene.bind_groups = vec![frame.bind_group, camera.bind_group];
+
+    let model = gltf::parse_gltf(&textured_triangle_glb()).unwrap();
+    let mut meshes = Vec::new();
+    gltf::upload_model(
+        device,
+        &renderer.context.queue,
+        &mut renderer.resources,
+        &mut meshes,
+        &model,
+        renderer.context.surface_config.format,
+    )
+    .unwrap();
+    for mesh in meshes {
+        renderer.scene.add_mesh(mesh);
+    }
+
+    // Object params land in group 2, right after the camera.
+    let pixels = renderer.render_offscreen().unwrap();
+    let center = &pixels[(24 * 64 + 32) * 4..][..4];
+    assert_ne!(center, [0, 255, 0, 255]);
+}
+
+#[test]
+fn removing_model_materials_frees_their_params_and_textures() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+    let model = gltf::parse_gltf(&textured_triangle_glb()).unwrap();
+
+    // The first upload also creates the white fallback texture, which stays.
+    gltf::upload_materials(&context.device, &context.queue, &mut resources, &model)
+        .remove(&mut resources);
+    let usage = resources.gpu_memory_usage();
+
+    let materials = gltf::upload_materials(&context.device, &context.queue, &mut resources, &model);
+    let index = materials.params()[0];
+    assert!(resources.gpu_memory_usage().texture > usage.texture);
+    assert!(resources.gpu_memory_usage().uniform > usage.uniform);
+
+    materials.remove(&mut resources);
+    assert_eq!(resources.gpu_memory_usage(), usage);
+    assert!(!resources.remove_object_params(index));
+    // Meshes still holding the index are drawn with the default material.
+    assert!(resources
+        .object_params_bind_group(Some(index), true)
+        .is_some());
+}
+
+#[test]
+fn changing_the_sampler_rebinds_existing_materials() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+    let model = gltf::parse_gltf(&textured_triangle_glb()).unwrap();
+    let materials = gltf::upload_materials(&context.device, &context.queue, &mut resources, &model);
+    let index = materials.params()[0];
+    let bind_groups = |resources: &GpuResources| {
+        [Some(index), None].map(|index| {
+            resources
+                .object_params_bind_group(index, true)
+                .unwrap()
+                .clone()
+        })
+    };
+    let before = bind_groups(&resources);
+
+    resources.set_default_sampler(
+        &context.device,
+        SamplerConfig {
+            mag_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        },
+    );
+
+    // Both the material and the default one pick up the new sampler.
+    let after = bind_groups(&resources);
+    assert_ne!(before[0], after[0]);
+    assert_ne!(before[1], after[1]);
+}
+
+#[test]
+fn models_loaded_from_bytes_can_be_freed() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let frame = FrameMetadata::new(ultraviolet::Vec2::new(1.0, 1.0))
+        .create_uniform_resource(device, &Light::new(device));
+    let camera = Camera::new(1.0).create_uniform_resource(device);
+    resources.set_bind_group_layouts(&[frame.bind_group_layout, camera.bind_group_layout]);
+
+    let load_and_free = |resources: &mut GpuResources| {
+        let mut meshes = Vec::new();
+        let loaded = gltf::load_gltf_from_bytes(
+            device,
+            &context.queue,
+            resources,
+            &mut meshes,
+            context.surface_config.format,
+            &textured_triangle_glb(),
+        )
+        .unwrap();
+        assert!(loaded.bounds.is_some());
+        assert!(!loaded.materials.params().is_empty());
+
+        for mesh in &meshes {
+            resources.remove_mesh_buffers(mesh);
+        }
+        loaded.materials.remove(resources);
+    };
+
+    // The first load also creates the white fallback texture, which stays.
+    load_and_free(&mut resources);
+    let usage = resources.gpu_memory_usage();
+    load_and_free(&mut resources);
+    assert_eq!(resources.gpu_memory_usage(), usage);
+}
+
+#[test]
+fn visitor_can_skip_primitives() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+
+    let (meshes, _) = gltf::visit_gltf(&two_triangle_glb(), &mut |primitive| {
+        let keep = primitive.transform.cols[3].x > 0.0;
+        keep.then(|| {
+            gltf::upload_primitive(&context.device, &mut resources, &primitive, 0).unwrap()
+        })
+    })
+    .unwrap();
+
+    assert_eq!(meshes.len(), 1);
+}
+
+#[test]
+fn meshes_are_found_by_node_or_mesh_name() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+
+    let (meshes, _) = gltf::visit_gltf(&two_triangle_glb(), &mut |primitive| {
+        gltf::upload_primitive(&context.device, &mut resources, &primitive, 0).ok()
+    })
+    .unwrap();
+    let ids: Vec<MeshId> = meshes
+        .into_iter()
+        .map(|mesh| scene.add_mesh(mesh))
+        .collect();
+
+    // The second node has no name of its own, so it goes by its mesh's.
+    assert_eq!(scene.find_mesh_by_name("child"), Some(ids[0]));
+    assert_eq!(scene.find_mesh_by_name("triangle"), Some(ids[1]));
+    assert_eq!(scene.find_mesh_by_name("roof"), None);
+
+    scene.remove_mesh(ids[0]);
+    assert_eq!(scene.find_mesh_by_name("child"), None);
+}
+
+#[test]
+fn moving_a_node_moves_the_meshes_below_it() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+
+    let model = gltf::parse_gltf(&two_triangle_glb()).unwrap();
+    let ids: Vec<MeshId> = model
+        .primitives
+        .iter()
+        .map(|primitive| {
+            let mesh = gltf::upload_primitive(&context.device, &mut resources, primitive, 0);
+            scene.add_mesh(mesh.unwrap())
+        })
+        .collect();
+    scene.graph = SceneGraph::from_model(&model, &ids);
+
+    let root = scene.graph.roots().next().unwrap();
+    let child = scene.graph.find_node_by_name("child").unwrap();
+    assert_eq!(scene.graph.node(child).unwrap().meshes(), &ids[..1]);
+
+    // Raising the root carries its child's triangle along, the other root stays put.
+    assert!(scene.set_node_transform(
+        root,
+        Mat4::from_translation(Vec3::new(0.0, 5.0, 0.0)),
+        &resources,
+        &context.queue,
+    ));
+    let world = scene.graph.node(child).unwrap().world_transform();
+    assert_eq!(world.cols[3].xyz(), Vec3::new(1.0, 5.0, 0.0));
+    assert_eq!(
+        scene.meshes.get(ids[0]).unwrap().origin,
+        Vec3::new(1.0, 5.0, 0.0)
+    );
+    assert_eq!(
+        scene.meshes.get(ids[1]).unwrap().origin,
+        Vec3::new(-3.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+fn meshes_outside_the_view_are_culled() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let mut cube = |x: f32| {
+        let mut mesh = MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_indices(device, &mut resources, &indices)
+            .with_pipeline(0)
+            .with_model_matrix(
+                device,
+                &mut resources,
+                Mat4::from_translation(Vec3::new(x, 0.0, 0.0)),
+            )
+            .build();
+        mesh.bounds = Some(ModelBounds::new([x - 1.0, -1.0, -1.0], [x + 1.0, 1.0, 1.0]));
+        mesh
+    };
+    let mut hidden = cube(-50.0);
+    hidden.visible = false;
+    let mut unbounded = cube(80.0);
+    unbounded.bounds = None;
+    let meshes = [cube(0.0), cube(50.0), hidden, unbounded];
+
+    let mut camera = Camera::new(1.0);
+    camera.look_at(Vec3::new(0.0, 0.0, 10.0), Vec3::zero());
+    let frustum = Frustum::from_view_proj(Mat4::from(camera.view_proj));
+
+    // Hidden meshes are dropped without counting, meshes without bounds are kept.
+    let mut order = vec![0, 1, 2, 3];
+    assert_eq!(cull_meshes(&mut order, &meshes, &frustum), 1);
+    assert_eq!(order, [0, 3]);
+}
+
+#[test]
+fn blended_meshes_draw_after_opaque_ones_farthest_first() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let device = &context.device;
+    let mut resources = GpuResources::new();
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let mut cube = |z: f32, blend_mode| {
+        MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_indices(device, &mut resources, &indices)
+            .with_pipeline(0)
+            .with_model_matrix(
+                device,
+                &mut resources,
+                Mat4::from_translation(Vec3::new(0.0, 0.0, z)),
+            )
+            .with_blend_mode(blend_mode)
+            .build()
+    };
+
+    let meshes = [
+        cube(-1.0, BlendMode::Blend),
+        cube(0.0, BlendMode::Opaque),
+        cube(-5.0, BlendMode::Blend),
+        cube(-3.0, BlendMode::Opaque),
+    ];
+
+    let eye = Vec3::new(0.0, 0.0, 5.0);
+    assert_eq!(draw_order(&meshes, Some(eye)), [1, 3, 2, 0]);
+    assert_eq!(draw_order(&meshes, None), [1, 3, 0, 2]);
+}
+
+#[test]
+fn grid_lines_cross_at_the_origin() {
+    let (positions, colors) = grid_lines(2.0, 1.0);
+
+    // Five lines along each axis, two vertices each.
+    assert_eq!(positions.len(), 20);
+    assert_eq!(colors.len(), positions.len());
+    assert!(positions
+        .iter()
+        .all(|p| p[1] == 0.0 && p[0].abs() <= 2.0 && p[2].abs() <= 2.0));
+    assert!(positions.contains(&[0.0, 0.0, -2.0]) && positions.contains(&[-2.0, 0.0, 0.0]));
+}
+
+#[test]
+fn grid_size_follows_the_model() {
+    let small = ModelBounds::new([-0.3, 0.0, -0.2], [0.25, 1.0, 0.1]);
+    assert_eq!(Grid::size_for(&small), (0.3, 0.1));
+
+    // Height does not matter, the grid lies on the ground.
+    let wide = ModelBounds::new([-40.0, -500.0, 0.0], [35.0, 500.0, 12.0]);
+    assert_eq!(Grid::size_for(&wide), (40.0, 10.0));
+}
+
+#[test]
+fn frame_bounds_points_camera_at_model() {
+    let bounds = ModelBounds::new([-3.0, 0.0, 0.0], [2.0, 3.0, 0.0]);
+    let framing = bounds.framing();
+
+    let mut scene = TestScene {
+        cam: Camera::new(4.0 / 3.0),
+        bind_groups: Vec::new(),
+        meshes: MeshStore::new(),
+        graph: SceneGraph::new(),
+        hovered: None,
+        updates: Vec::new(),
+    };
+    scene.frame_bounds(&bounds);
+
+    let camera = scene.camera_mut().unwrap();
+    assert!((camera.target() - framing.target).mag() < 1e-4);
+
+    // The model center lands in the middle of the screen.
+    let clip = Mat4::from(camera.view_proj) * framing.target.into_homogeneous_point();
+    assert!(clip.x.abs() / clip.w < 1e-4);
+    assert!(clip.y.abs() / clip.w < 1e-4);
+}
+
+#[test]
+fn headless_renderer_draws_offscreen_frames() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(64, 48) else {
+        return;
+    };
+    renderer.set_clear_color(wgpu::Color::RED);
+
+    // Flattened onto one depth, so whichever faces survive culling cover the center.
+    let device = &renderer.context.device;
+    let pipeline = renderer
+        .resources
+        .create_pipeline(
+            device,
+            "flat",
+            &mesh_vertex_layout(),
+            &flat_shader("vec4<f32>(pos.xy, 0.5, 1.0)", "vec4<f32>(1.0)"),
+            renderer.context.surface_config.format,
+        )
+        .unwrap();
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let cube = MeshBuilder::default()
+        .with_vertices(device, &mut renderer.resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut renderer.resources, &indices)
+        .with_pipeline(pipeline)
+        .with_model_matrix(device, &mut renderer.resources, Mat4::identity())
+        .build();
+    renderer.scene.add_mesh(cube);
+    renderer
+        .scene
+        .set_camera_look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+
+    let pixels = renderer.render_offscreen().unwrap();
+    assert_eq!(pixels.len(), 64 * 48 * 4);
+    let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..][..4];
+    assert_eq!(pixel(32, 24), [255, 255, 255, 255]);
+    assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+}
+
+#[test]
+fn light_direction_is_normalized_and_ignores_zero_vectors() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let mut light = Light::new(&context.device);
+
+    light.set_direction(Vec3::new(0.0, -4.0, 0.0), &context.queue);
+    assert_eq!(light.uniform().direction, [0.0, -1.0, 0.0, 0.0]);
+
+    light.set_direction(Vec3::zero(), &context.queue);
+    assert_eq!(light.uniform().direction, [0.0, -1.0, 0.0, 0.0]);
+
+    light.set_color([2.0, 1.0, 0.5], &context.queue);
+    assert_eq!(light.uniform().color, [2.0, 1.0, 0.5, 1.0]);
+}
+
+#[test]
+fn thumbnails_leave_the_live_camera_in_place() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(64, 48) else {
+        return;
+    };
+    let pipeline = flat_pipeline(&renderer.context, &mut renderer.resources);
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let device = &renderer.context.device;
+    let cube = MeshBuilder::default()
+        .with_vertices(device, &mut renderer.resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut renderer.resources, &indices)
+        .with_pipeline(pipeline)
+        .with_model_matrix(device, &mut renderer.resources, Mat4::identity())
+        .build();
+    renderer.scene.add_mesh(cube);
+    let eye = Vec3::new(0.0, 0.0, 5.0);
+    renderer.scene.set_camera_look_at(eye, Vec3::zero());
+
+    let thumbnail = renderer.render_thumbnail(16, 16);
+    renderer.context.device.poll(wgpu::PollType::Wait).unwrap();
+    let pixels = futures::executor::block_on(thumbnail).unwrap();
+    assert_eq!(pixels.len(), 16 * 16 * 4);
+
+    let camera = renderer.scene.camera_mut().unwrap();
+    assert_eq!(camera.position(), eye);
+    assert!((camera.aspect_ratio() - 64.0 / 48.0).abs() < 1e-6);
+}
+
+#[test]
+fn letterboxed_viewports_center_the_image_and_offset_the_cursor() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(64, 48) else {
+        return;
+    };
+    renderer
+        .scene
+        .set_camera_look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+
+    renderer.set_letterbox(1.0);
+    assert_eq!(renderer.context.render_extent(), (48.0, 48.0));
+    assert_eq!(
+        renderer.context.viewport,
+        Some(Viewport::new(8.0, 0.0, 48.0, 48.0))
+    );
+
+    // Cursor positions count from the viewport's corner, the bars are outside the view.
+    assert_eq!(renderer.client_to_ndc(8.0, 24.0), Vec2::new(-1.0, 0.0));
+    assert_eq!(renderer.client_to_ndc(56.0, 0.0), Vec2::new(1.0, 1.0));
+    assert!(renderer.start_pick(4, 24).is_none());
+    assert!(renderer.start_pick(32, 24).is_some());
+
+    renderer.clear_viewport();
+    assert_eq!(renderer.context.viewport, None);
+}
+
+#[test]
+fn on_demand_rendering_only_draws_after_changes() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(8, 8) else {
+        return;
+    };
+    assert!(renderer.needs_redraw());
+
+    renderer.set_render_on_demand(true);
+    renderer.redraw_requested = false;
+    assert!(!renderer.needs_redraw());
+
+    renderer.request_redraw();
+    assert!(renderer.needs_redraw());
+
+    renderer.set_paused(true);
+    assert!(!renderer.needs_redraw());
+    renderer.set_paused(false);
+    assert!(renderer.needs_redraw());
+}
+
+#[test]
+fn scenes_update_with_the_time_since_the_previous_frame() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(8, 8) else {
+        return;
+    };
+
+    renderer.update_scene(1000.0);
+    renderer.update_scene(1016.0);
+    // Time spent paused is not passed on as one long frame.
+    renderer.set_paused(true);
+    renderer.set_paused(false);
+    renderer.update_scene(9000.0);
+
+    let updates = &renderer.scene.updates;
+    assert_eq!(updates.len(), 3);
+    assert_eq!(updates[0], (1.0, 0.0));
+    assert!((updates[1].0 - 1.016).abs() < 1e-6);
+    assert!((updates[1].1 - 0.016).abs() < 1e-6);
+    assert_eq!(updates[2], (9.0, 0.0));
+}
+
+#[test]
+fn invalid_scene_shaders_fail_renderer_creation() {
+    match try_headless_renderer::<BrokenScene>(8, 8) {
+        Err(err @ RendererInitError::SceneSetupFailed(SceneError::Validation(_))) => {
+            assert_eq!(err.kind(), "SceneSetupFailed");
+        }
+        Err(err @ RendererInitError::NoAdapter(_)) => skip_gpu_test(err),
+        Err(err) => panic!("the broken shader was reported as {err:?}"),
+        Ok(_) => panic!("the broken shader was not reported"),
+    }
+}
+
+#[test]
+fn hover_picks_again_only_after_the_cursor_moves() {
+    let Some(mut renderer) = headless_renderer::<TestScene>(64, 48) else {
+        return;
+    };
+
+    let pipeline = flat_pipeline(&renderer.context, &mut renderer.resources);
+    let device = &renderer.context.device;
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let cube = MeshBuilder::default()
+        .with_vertices(device, &mut renderer.resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut renderer.resources, &indices)
+        .with_pipeline(pipeline)
+        .with_model_matrix(device, &mut renderer.resources, Mat4::identity())
+        .build();
+    let cube = renderer.scene.add_mesh(cube);
+    renderer
+        .scene
+        .set_camera_look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero());
+
+    let hover_at = |renderer: &mut Renderer<TestScene>, x: f64, y: f64| {
+        renderer.mouse_move(MouseMessage {
+            scale_factor: 1.0,
+            button: 0.0,
+            buttons: 0,
+            client_x: x,
+            client_y: y,
+            movement_x: 0.0,
+            movement_y: 0.0,
+            offset_x: x,
+            offset_y: y,
+            shift_key: false,
+        });
+        let readback = renderer.start_hover_pick()?;
+        // Only one pixel is read back at a time.
+        assert!(renderer.start_hover_pick().is_none());
+        renderer.context.device.poll(wgpu::PollType::Wait).unwrap();
+        let hovered = futures::executor::block_on(readback.resolve());
+        renderer.finish_hover_pick(hovered);
+        Some(hovered)
+    };
+
+    assert_eq!(hover_at(&mut renderer, 32.0, 24.0), Some(Some(cube)));
+    assert_eq!(renderer.scene.hovered, Some(cube));
+    // A cursor that has not moved is not picked again.
+    assert!(renderer.start_hover_pick().is_none());
+
+    assert_eq!(hover_at(&mut renderer, 1.0, 1.0), Some(None));
+    assert_eq!(renderer.scene.hovered, None);
+
+    // Leaving the surface clears the hover without reading anything back.
+    renderer.set_hovered(Some(cube));
+    assert_eq!(hover_at(&mut renderer, -5.0, 10.0), None);
+    assert_eq!(renderer.hovered(), None);
+    assert_eq!(renderer.scene.hovered, None);
+}
+
+/// Contents of a `COPY_SRC` buffer, as 32-bit words.
+fn read_buffer(context: &RendererContext, buffer: &wgpu::Buffer) -> Vec<u32> {
+    let (device, queue) = (&context.device, &context.queue);
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+    queue.submit(std::iter::once(encoder.finish()));
+    staging.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::PollType::Wait).unwrap();
+    let data = bytemuck::cast_slice(&staging.slice(..).get_mapped_range()).to_vec();
+    data
+}
+
+/// Vertex `vertex` of a buffer of three floats per vertex, from `read_buffer`.
+fn vec3_at(data: &[u32], vertex: usize) -> Vec3 {
+    let [x, y, z] = [0, 1, 2].map(|axis| f32::from_bits(data[vertex * 3 + axis]));
+    Vec3::new(x, y, z)
+}
+
+#[test]
+fn merging_static_meshes_bakes_their_transforms_into_one_draw() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let (device, queue) = (&context.device, &context.queue);
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+
+    let (positions, normals, uvs, indices) = cube_vertices();
+    let small_indices: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+    let rotation = Mat4::from_rotation_y(std::f32::consts::FRAC_PI_2);
+    let transform =
+        Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0)) * rotation * Mat4::from_scale(2.0);
+    let first = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+    let second = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices_u16(device, &mut resources, &small_indices)
+        .with_pipeline(0)
+        .with_model_matrix(device, &mut resources, transform)
+        .build();
+    // Another pipeline, left on its own.
+    let other = MeshBuilder::default()
+        .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+        .unwrap()
+        .with_indices(device, &mut resources, &indices)
+        .with_pipeline(1)
+        .with_model_matrix(device, &mut resources, Mat4::identity())
+        .build();
+    scene.add_mesh(first);
+    scene.add_mesh(second);
+    let other = scene.add_mesh(other);
+
+    let stats = scene.merge_static_meshes(device, queue, &mut resources, false);
+    assert_eq!(
+        stats,
+        MergeStats {
+            draw_calls_before: 3,
+            draw_calls_after: 2,
+            merged_meshes: 2,
+        }
+    );
+    assert_eq!(scene.mesh_id(0), Some(other));
+    let merged = &scene.meshes()[1];
+    assert_eq!(merged.vertex_count, 48);
+    assert_eq!(merged.index_count, 72);
+    assert_eq!(merged.index_format, wgpu::IndexFormat::Uint32);
+
+    let merged_positions = read_buffer(
+        &context,
+        resources.get_buffer(&merged.position_buffer_index),
+    );
+    let merged_normals = read_buffer(&context, resources.get_buffer(&merged.normal_buffer_index));
+    let merged_indices = read_buffer(
+        &context,
+        resources.get_buffer(&merged.index_buffer_index.unwrap()),
+    );
+
+    assert_eq!(vec3_at(&merged_positions, 0), Vec3::from(positions[0]));
+    let position = transform.transform_point3(Vec3::from(positions[0]));
+    assert!((vec3_at(&merged_positions, 24) - position).mag() < 1e-5);
+    // Normals turn with the mesh but keep their length under the scale.
+    let normal = rotation.transform_vec3(Vec3::from(normals[0]));
+    assert!((vec3_at(&merged_normals, 24) - normal).mag() < 1e-5);
+    assert_eq!(merged_indices[36], indices[0] + 24);
+    assert_eq!(merged_indices[71], indices[35] + 24);
+}
+
+#[test]
+fn merging_copies_normals_already_in_world_space() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let (device, queue) = (&context.device, &context.queue);
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+    let pipeline = resources
+        .create_pipeline_with_options(
+            device,
+            "world normals",
+            &mesh_vertex_layout(),
+            &flat_shader("vec4<f32>(pos, 1.0)", "vec4<f32>(1.0)"),
+            context.surface_config.format,
+            PipelineOptions {
+                world_space_normals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    // Like glTF imports, whose normals already went through the node's transform.
+    let (positions, normals, uvs, indices) = cube_vertices();
+    for angle in [0.0, std::f32::consts::FRAC_PI_2] {
+        let cube = MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_indices(device, &mut resources, &indices)
+            .with_pipeline(pipeline)
+            .with_model_matrix(device, &mut resources, Mat4::from_rotation_y(angle))
+            .build();
+        scene.add_mesh(cube);
+    }
+
+    let stats = scene.merge_static_meshes(device, queue, &mut resources, false);
+    assert_eq!(stats.merged_meshes, 2);
+    let merged = &scene.meshes()[0];
+    let merged_normals = read_buffer(&context, resources.get_buffer(&merged.normal_buffer_index));
+    assert_eq!(vec3_at(&merged_normals, 24), Vec3::from(normals[0]));
+}
+
+#[test]
+fn merged_meshes_can_stay_for_picking_only() {
+    let Some(context) = headless_context() else {
+        return;
+    };
+    let (device, queue) = (&context.device, &context.queue);
+    let mut resources = GpuResources::new();
+    let mut scene = TestScene::setup(&context, &mut resources);
+
+    let (positions, normals, uvs, indices) = cube_vertices();
+    for x in [0.0, 3.0] {
+        let cube = MeshBuilder::default()
+            .with_vertices(device, &mut resources, &positions, &normals, &uvs)
+            .unwrap()
+            .with_indices(device, &mut resources, &indices)
+            .with_pipeline(0)
+            .with_model_matrix(
+                device,
+                &mut resources,
+                Mat4::from_translation(Vec3::unit_x() * x),
+            )
+            .build();
+        scene.add_mesh(cube);
+    }
+
+    let stats = scene.merge_static_meshes(device, queue, &mut resources, true);
+    assert_eq!(stats.draw_calls_after, 1);
+    let passes: Vec<MeshPasses> = scene.meshes().iter().map(|mesh| mesh.passes).collect();
+    assert_eq!(
+        passes,
+        [MeshPasses::Picking, MeshPasses::Picking, MeshPasses::Color]
+    );
+
+    // Nothing left to merge the second time around.
+    let stats = scene.merge_static_meshes(device, queue, &mut resources, true);
+    assert_eq!(stats.merged_meshes, 0);
+}